@@ -0,0 +1,36 @@
+//! Smoke test: render every example in the gallery in each speech style and compare the speech to what is expected.
+//! Usage: cargo run --example gallery
+//! The mismatches are listed and the exit status is non-zero if there are any (or if an example couldn't be rendered).
+use math_cat_demo::*;
+use math_cat_demo::gallery::check_example;
+
+fn main() {
+    if let Err(e) = init("Rules") {
+        eprintln!("{}", libmathcat::errors_to_string(&e));
+        std::process::exit(1);
+    }
+
+    let mut n_failed = 0;
+    for example in EXAMPLES {
+        match check_example(example) {
+            Ok(mismatches) => {
+                if !mismatches.is_empty() {
+                    n_failed += 1;
+                }
+                for mismatch in mismatches {
+                    println!("{} / {} ({}):", example.category, example.name, mismatch.speech_style);
+                    println!("  expected: {}", mismatch.expected);
+                    println!("  actual:   {}", mismatch.actual);
+                }
+            },
+            Err(e) => {
+                println!("{} / {}: {}", example.category, example.name, libmathcat::errors_to_string(&e));
+                n_failed += 1;
+            },
+        }
+    }
+    println!("{} of {} examples passed", EXAMPLES.len() - n_failed, EXAMPLES.len());
+    if n_failed > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,39 @@
+//! Run the speech and braille pipeline natively (no browser).
+//! Usage: cargo run --example render -- '<math><mi>x</mi><mo>=</mo><mn>2</mn></math>'
+//! If there are several delimited expressions (e.g., '$x^2$ and `y/2`'), each one is rendered.
+use math_cat_demo::*;
+
+fn main() {
+    let math_str = std::env::args().skip(1).collect::<Vec<String>>().join(" ");
+    if math_str.is_empty() {
+        eprintln!("Usage: render <math>");
+        std::process::exit(1);
+    }
+
+    if let Err(e) = init("Rules") {
+        eprintln!("{}", libmathcat::errors_to_string(&e));
+        std::process::exit(1);
+    }
+
+    let settings = Settings::default();
+    let mut failed = false;
+    for (i, result) in render_all(&math_str, &settings).into_iter().enumerate() {
+        if i > 0 {
+            println!("\n----------\n");
+        }
+        match result {
+            Ok(rendering) => {
+                println!("MathML:\n{}\n", rendering.mathml);
+                println!("Speech:\n{}\n", rendering.speech);
+                println!("Braille:\n{}", rendering.braille);
+            },
+            Err(e) => {
+                eprintln!("{}", libmathcat::errors_to_string(&e));
+                failed = true;
+            },
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
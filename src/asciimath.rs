@@ -0,0 +1,728 @@
+//! Convert ASCIIMath (see <http://asciimath.org>) to MathML.
+//!
+//! The grammar is the one from the ASCIIMath spec:
+//! ```text
+//! S ::= v | lEr | uS | bSS     (simple expression)
+//! I ::= S_S | S^S | S_S^S | S  (intermediate expression)
+//! E ::= IE | I/I               (expression)
+//! ```
+//! Unlike ASCIIMathML.js, unmatched brackets and missing arguments are reported as errors (with their position)
+//! rather than being silently accepted.
+use std::collections::HashSet;
+use anyhow::Result;
+use phf::phf_map;
+use crate::mathml::{MathNode, math_to_string};
+use crate::convert::ParseError;
+
+/// Convert `ascii_math` to a MathML string (display math)
+pub fn asciimath_to_mathml(ascii_math: &str) -> Result<String> {
+    return Ok( math_to_string(parse_asciimath(ascii_math)?, true) );
+}
+
+/// Convert `ascii_math` to a list of MathML nodes (the children of the `math` element)
+pub fn parse_asciimath(ascii_math: &str) -> Result<Vec<MathNode>> {
+    let mut parser = AsciiMathParser::new(ascii_math);
+    let (pieces, end) = parser.parse_expression(false)?;
+    if let Some(token) = end {
+        return Err( ParseError::new(&format!("Unmatched '{}'", token.text), token.start, token.end).into() );
+    }
+    return Ok( flatten(pieces) );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// A token element; the value is the element name ("mi", "mo", ...)
+    Const(&'static str),
+    /// A large operator whose scripts go under and over
+    UnderOver,
+    /// A function name (e.g, "sin")
+    Function,
+    /// A function name whose scripts go under and over (e.g, "lim")
+    UnderOverFunction,
+    LeftBracket,
+    RightBracket,
+    /// `|` and `||` -- these can be either a left or right bracket
+    LeftRight,
+    Sqrt,
+    Text,
+    /// A font change (the value is the mathvariant)
+    Font(&'static str),
+    /// An accent: (is over, is stretchy)
+    Accent(bool, bool),
+    /// Functions such as `abs(x)` that put fences around their argument: the value is the closing fence
+    Fence(&'static str),
+    Cancel,
+    Frac,
+    Root,
+    Over,
+    Under,
+    Color,
+    Subscript,
+    Superscript,
+    Divide,
+}
+
+/// The ASCIIMath symbol table: input => (output text, kind)
+static SYMBOLS: phf::Map<&'static str, (&'static str, Kind)> = phf_map! {
+    // Greek
+    "alpha" => ("α", Kind::Const("mi")), "beta" => ("β", Kind::Const("mi")), "chi" => ("χ", Kind::Const("mi")),
+    "delta" => ("δ", Kind::Const("mi")), "Delta" => ("Δ", Kind::Const("mo")), "epsi" => ("ε", Kind::Const("mi")),
+    "epsilon" => ("ε", Kind::Const("mi")), "varepsilon" => ("ɛ", Kind::Const("mi")), "eta" => ("η", Kind::Const("mi")),
+    "gamma" => ("γ", Kind::Const("mi")), "Gamma" => ("Γ", Kind::Const("mo")), "iota" => ("ι", Kind::Const("mi")),
+    "kappa" => ("κ", Kind::Const("mi")), "lambda" => ("λ", Kind::Const("mi")), "Lambda" => ("Λ", Kind::Const("mo")),
+    "lamda" => ("λ", Kind::Const("mi")), "Lamda" => ("Λ", Kind::Const("mo")), "mu" => ("μ", Kind::Const("mi")),
+    "nu" => ("ν", Kind::Const("mi")), "omega" => ("ω", Kind::Const("mi")), "Omega" => ("Ω", Kind::Const("mo")),
+    "phi" => ("ϕ", Kind::Const("mi")), "varphi" => ("φ", Kind::Const("mi")), "Phi" => ("Φ", Kind::Const("mo")),
+    "pi" => ("π", Kind::Const("mi")), "Pi" => ("Π", Kind::Const("mo")), "psi" => ("ψ", Kind::Const("mi")),
+    "Psi" => ("Ψ", Kind::Const("mi")), "rho" => ("ρ", Kind::Const("mi")), "sigma" => ("σ", Kind::Const("mi")),
+    "Sigma" => ("Σ", Kind::Const("mo")), "tau" => ("τ", Kind::Const("mi")), "theta" => ("θ", Kind::Const("mi")),
+    "vartheta" => ("ϑ", Kind::Const("mi")), "Theta" => ("Θ", Kind::Const("mo")), "upsilon" => ("υ", Kind::Const("mi")),
+    "xi" => ("ξ", Kind::Const("mi")), "Xi" => ("Ξ", Kind::Const("mo")), "zeta" => ("ζ", Kind::Const("mi")),
+
+    // operation symbols
+    "+" => ("+", Kind::Const("mo")), "-" => ("−", Kind::Const("mo")), "*" => ("⋅", Kind::Const("mo")),
+    "cdot" => ("⋅", Kind::Const("mo")), "**" => ("∗", Kind::Const("mo")), "ast" => ("∗", Kind::Const("mo")),
+    "***" => ("⋆", Kind::Const("mo")), "star" => ("⋆", Kind::Const("mo")), "//" => ("/", Kind::Const("mo")),
+    "\\\\" => ("\\", Kind::Const("mo")), "backslash" => ("\\", Kind::Const("mo")),
+    "setminus" => ("\\", Kind::Const("mo")), "xx" => ("×", Kind::Const("mo")), "times" => ("×", Kind::Const("mo")),
+    "|><" => ("⋉", Kind::Const("mo")), "ltimes" => ("⋉", Kind::Const("mo")), "><|" => ("⋊", Kind::Const("mo")),
+    "rtimes" => ("⋊", Kind::Const("mo")), "|><|" => ("⋈", Kind::Const("mo")), "bowtie" => ("⋈", Kind::Const("mo")),
+    "-:" => ("÷", Kind::Const("mo")), "div" => ("÷", Kind::Const("mo")), "divide" => ("÷", Kind::Const("mo")),
+    "@" => ("∘", Kind::Const("mo")), "circ" => ("∘", Kind::Const("mo")), "o+" => ("⊕", Kind::Const("mo")),
+    "oplus" => ("⊕", Kind::Const("mo")), "ox" => ("⊗", Kind::Const("mo")), "otimes" => ("⊗", Kind::Const("mo")),
+    "o." => ("⊙", Kind::Const("mo")), "odot" => ("⊙", Kind::Const("mo")), "sum" => ("∑", Kind::UnderOver),
+    "prod" => ("∏", Kind::UnderOver), "^^" => ("∧", Kind::Const("mo")), "wedge" => ("∧", Kind::Const("mo")),
+    "^^^" => ("⋀", Kind::UnderOver), "bigwedge" => ("⋀", Kind::UnderOver), "vv" => ("∨", Kind::Const("mo")),
+    "vee" => ("∨", Kind::Const("mo")), "vvv" => ("⋁", Kind::UnderOver), "bigvee" => ("⋁", Kind::UnderOver),
+    "nn" => ("∩", Kind::Const("mo")), "cap" => ("∩", Kind::Const("mo")), "nnn" => ("⋂", Kind::UnderOver),
+    "bigcap" => ("⋂", Kind::UnderOver), "uu" => ("∪", Kind::Const("mo")), "cup" => ("∪", Kind::Const("mo")),
+    "uuu" => ("⋃", Kind::UnderOver), "bigcup" => ("⋃", Kind::UnderOver),
+
+    // relation symbols
+    "=" => ("=", Kind::Const("mo")), "!=" => ("≠", Kind::Const("mo")), "ne" => ("≠", Kind::Const("mo")),
+    ":=" => ("≔", Kind::Const("mo")), "<" => ("<", Kind::Const("mo")), "lt" => ("<", Kind::Const("mo")),
+    ">" => (">", Kind::Const("mo")), "gt" => (">", Kind::Const("mo")), "<=" => ("≤", Kind::Const("mo")),
+    "le" => ("≤", Kind::Const("mo")), "lt=" => ("≤", Kind::Const("mo")), ">=" => ("≥", Kind::Const("mo")),
+    "ge" => ("≥", Kind::Const("mo")), "gt=" => ("≥", Kind::Const("mo")), "mlt" => ("≪", Kind::Const("mo")),
+    "ll" => ("≪", Kind::Const("mo")), "mgt" => ("≫", Kind::Const("mo")), "gg" => ("≫", Kind::Const("mo")),
+    "-<" => ("≺", Kind::Const("mo")), "-lt" => ("≺", Kind::Const("mo")), "prec" => ("≺", Kind::Const("mo")),
+    ">-" => ("≻", Kind::Const("mo")), "succ" => ("≻", Kind::Const("mo")), "-<=" => ("⪯", Kind::Const("mo")),
+    "preceq" => ("⪯", Kind::Const("mo")), ">-=" => ("⪰", Kind::Const("mo")), "succeq" => ("⪰", Kind::Const("mo")),
+    "in" => ("∈", Kind::Const("mo")), "!in" => ("∉", Kind::Const("mo")), "notin" => ("∉", Kind::Const("mo")),
+    "sub" => ("⊂", Kind::Const("mo")), "subset" => ("⊂", Kind::Const("mo")), "sup" => ("⊃", Kind::Const("mo")),
+    "supset" => ("⊃", Kind::Const("mo")), "sube" => ("⊆", Kind::Const("mo")), "subseteq" => ("⊆", Kind::Const("mo")),
+    "supe" => ("⊇", Kind::Const("mo")), "supseteq" => ("⊇", Kind::Const("mo")), "-=" => ("≡", Kind::Const("mo")),
+    "equiv" => ("≡", Kind::Const("mo")), "~=" => ("≅", Kind::Const("mo")), "cong" => ("≅", Kind::Const("mo")),
+    "~~" => ("≈", Kind::Const("mo")), "approx" => ("≈", Kind::Const("mo")), "~" => ("∼", Kind::Const("mo")),
+    "sim" => ("∼", Kind::Const("mo")), "prop" => ("∝", Kind::Const("mo")), "propto" => ("∝", Kind::Const("mo")),
+
+    // logical symbols
+    "and" => (" and ", Kind::Const("mtext")), "or" => (" or ", Kind::Const("mtext")),
+    "not" => ("¬", Kind::Const("mo")), "neg" => ("¬", Kind::Const("mo")), "=>" => ("⇒", Kind::Const("mo")),
+    "implies" => ("⇒", Kind::Const("mo")), "if" => (" if ", Kind::Const("mtext")), "<=>" => ("⇔", Kind::Const("mo")),
+    "iff" => ("⇔", Kind::Const("mo")), "AA" => ("∀", Kind::Const("mo")), "forall" => ("∀", Kind::Const("mo")),
+    "EE" => ("∃", Kind::Const("mo")), "exists" => ("∃", Kind::Const("mo")), "_|_" => ("⊥", Kind::Const("mo")),
+    "bot" => ("⊥", Kind::Const("mo")), "TT" => ("⊤", Kind::Const("mo")), "top" => ("⊤", Kind::Const("mo")),
+    "|--" => ("⊢", Kind::Const("mo")), "vdash" => ("⊢", Kind::Const("mo")), "|==" => ("⊨", Kind::Const("mo")),
+    "models" => ("⊨", Kind::Const("mo")),
+
+    // grouping brackets
+    "(" => ("(", Kind::LeftBracket), ")" => (")", Kind::RightBracket), "[" => ("[", Kind::LeftBracket),
+    "]" => ("]", Kind::RightBracket), "{" => ("{", Kind::LeftBracket), "}" => ("}", Kind::RightBracket),
+    "|" => ("|", Kind::LeftRight), "||" => ("‖", Kind::LeftRight), ":|:" => ("|", Kind::Const("mo")),
+    "|:" => ("|", Kind::LeftBracket), ":|" => ("|", Kind::RightBracket),
+    "(:" => ("⟨", Kind::LeftBracket), ":)" => ("⟩", Kind::RightBracket), "<<" => ("⟨", Kind::LeftBracket),
+    ">>" => ("⟩", Kind::RightBracket), "langle" => ("⟨", Kind::LeftBracket), "rangle" => ("⟩", Kind::RightBracket),
+    "{:" => ("", Kind::LeftBracket), ":}" => ("", Kind::RightBracket),
+
+    // miscellaneous symbols
+    "int" => ("∫", Kind::Const("mo")), "integral" => ("∫", Kind::Const("mo")), "dx" => ("dx", Kind::Const("mi")),
+    "dy" => ("dy", Kind::Const("mi")), "dz" => ("dz", Kind::Const("mi")), "dt" => ("dt", Kind::Const("mi")),
+    "oint" => ("∮", Kind::Const("mo")), "del" => ("∂", Kind::Const("mo")), "partial" => ("∂", Kind::Const("mo")),
+    "grad" => ("∇", Kind::Const("mo")), "nabla" => ("∇", Kind::Const("mo")), "+-" => ("±", Kind::Const("mo")),
+    "pm" => ("±", Kind::Const("mo")), "-+" => ("∓", Kind::Const("mo")), "mp" => ("∓", Kind::Const("mo")),
+    "O/" => ("∅", Kind::Const("mo")), "emptyset" => ("∅", Kind::Const("mo")), "oo" => ("∞", Kind::Const("mo")),
+    "infty" => ("∞", Kind::Const("mo")), "aleph" => ("ℵ", Kind::Const("mo")), "..." => ("...", Kind::Const("mo")),
+    "ldots" => ("…", Kind::Const("mo")), ":." => ("∴", Kind::Const("mo")), "therefore" => ("∴", Kind::Const("mo")),
+    ":'" => ("∵", Kind::Const("mo")), "because" => ("∵", Kind::Const("mo")), "/_" => ("∠", Kind::Const("mo")),
+    "angle" => ("∠", Kind::Const("mo")), "/_\\" => ("△", Kind::Const("mo")), "triangle" => ("△", Kind::Const("mo")),
+    "'" => ("′", Kind::Const("mo")), "prime" => ("′", Kind::Const("mo")), "\\ " => ("\u{A0}", Kind::Const("mo")),
+    "frown" => ("⌢", Kind::Const("mo")), "quad" => ("\u{A0}\u{A0}", Kind::Const("mo")),
+    "qquad" => ("\u{A0}\u{A0}\u{A0}\u{A0}", Kind::Const("mo")), "cdots" => ("⋯", Kind::Const("mo")),
+    "vdots" => ("⋮", Kind::Const("mo")), "ddots" => ("⋱", Kind::Const("mo")), "diamond" => ("⋄", Kind::Const("mo")),
+    "square" => ("□", Kind::Const("mo")), "|__" => ("⌊", Kind::Const("mo")), "lfloor" => ("⌊", Kind::Const("mo")),
+    "__|" => ("⌋", Kind::Const("mo")), "rfloor" => ("⌋", Kind::Const("mo")), "|~" => ("⌈", Kind::Const("mo")),
+    "lceiling" => ("⌈", Kind::Const("mo")), "~|" => ("⌉", Kind::Const("mo")), "rceiling" => ("⌉", Kind::Const("mo")),
+    "CC" => ("ℂ", Kind::Const("mo")), "NN" => ("ℕ", Kind::Const("mo")), "QQ" => ("ℚ", Kind::Const("mo")),
+    "RR" => ("ℝ", Kind::Const("mo")), "ZZ" => ("ℤ", Kind::Const("mo")),
+
+    // functions
+    "sin" => ("sin", Kind::Function), "cos" => ("cos", Kind::Function), "tan" => ("tan", Kind::Function),
+    "sec" => ("sec", Kind::Function), "csc" => ("csc", Kind::Function), "cot" => ("cot", Kind::Function),
+    "arcsin" => ("arcsin", Kind::Function), "arccos" => ("arccos", Kind::Function), "arctan" => ("arctan", Kind::Function),
+    "sinh" => ("sinh", Kind::Function), "cosh" => ("cosh", Kind::Function), "tanh" => ("tanh", Kind::Function),
+    "sech" => ("sech", Kind::Function), "csch" => ("csch", Kind::Function), "coth" => ("coth", Kind::Function),
+    "exp" => ("exp", Kind::Function), "log" => ("log", Kind::Function), "ln" => ("ln", Kind::Function),
+    "det" => ("det", Kind::Function), "dim" => ("dim", Kind::Function), "mod" => ("mod", Kind::Function),
+    "gcd" => ("gcd", Kind::Function), "lcm" => ("lcm", Kind::Function), "lub" => ("lub", Kind::Function),
+    "glb" => ("glb", Kind::Function), "min" => ("min", Kind::UnderOverFunction),
+    "max" => ("max", Kind::UnderOverFunction), "lim" => ("lim", Kind::UnderOverFunction),
+    "Lim" => ("Lim", Kind::UnderOverFunction), "f" => ("f", Kind::Function), "g" => ("g", Kind::Function),
+
+    // arrows
+    "uarr" => ("↑", Kind::Const("mo")), "uparrow" => ("↑", Kind::Const("mo")), "darr" => ("↓", Kind::Const("mo")),
+    "downarrow" => ("↓", Kind::Const("mo")), "rarr" => ("→", Kind::Const("mo")), "rightarrow" => ("→", Kind::Const("mo")),
+    "->" => ("→", Kind::Const("mo")), "to" => ("→", Kind::Const("mo")), ">->" => ("↣", Kind::Const("mo")),
+    "rightarrowtail" => ("↣", Kind::Const("mo")), "->>" => ("↠", Kind::Const("mo")),
+    "twoheadrightarrow" => ("↠", Kind::Const("mo")), ">->>" => ("⤖", Kind::Const("mo")),
+    "twoheadrightarrowtail" => ("⤖", Kind::Const("mo")), "|->" => ("↦", Kind::Const("mo")),
+    "mapsto" => ("↦", Kind::Const("mo")), "larr" => ("←", Kind::Const("mo")), "leftarrow" => ("←", Kind::Const("mo")),
+    "harr" => ("↔", Kind::Const("mo")), "leftrightarrow" => ("↔", Kind::Const("mo")), "rArr" => ("⇒", Kind::Const("mo")),
+    "Rightarrow" => ("⇒", Kind::Const("mo")), "lArr" => ("⇐", Kind::Const("mo")), "Leftarrow" => ("⇐", Kind::Const("mo")),
+    "hArr" => ("⇔", Kind::Const("mo")), "Leftrightarrow" => ("⇔", Kind::Const("mo")),
+
+    // unary and binary commands
+    "sqrt" => ("", Kind::Sqrt), "root" => ("", Kind::Root), "frac" => ("", Kind::Frac),
+    "stackrel" => ("", Kind::Over), "overset" => ("", Kind::Over), "underset" => ("", Kind::Under),
+    "color" => ("", Kind::Color), "text" => ("", Kind::Text), "mbox" => ("", Kind::Text),
+    "/" => ("", Kind::Divide), "_" => ("", Kind::Subscript), "^" => ("", Kind::Superscript),
+    "abs" => ("|", Kind::Fence("|")), "floor" => ("⌊", Kind::Fence("⌋")), "ceil" => ("⌈", Kind::Fence("⌉")),
+    "norm" => ("‖", Kind::Fence("‖")), "hat" => ("^", Kind::Accent(true, false)),
+    "bar" => ("¯", Kind::Accent(true, true)), "overline" => ("¯", Kind::Accent(true, true)),
+    "vec" => ("→", Kind::Accent(true, false)), "tilde" => ("~", Kind::Accent(true, false)),
+    "dot" => (".", Kind::Accent(true, false)), "ddot" => ("..", Kind::Accent(true, false)),
+    "overarc" => ("⏜", Kind::Accent(true, true)), "overparen" => ("⏜", Kind::Accent(true, true)),
+    "ul" => ("_", Kind::Accent(false, true)), "underline" => ("_", Kind::Accent(false, true)),
+    "ubrace" => ("⏟", Kind::Accent(false, true)), "underbrace" => ("⏟", Kind::Accent(false, true)),
+    "obrace" => ("⏞", Kind::Accent(true, true)), "overbrace" => ("⏞", Kind::Accent(true, true)),
+    "cancel" => ("", Kind::Cancel),
+    "bb" => ("", Kind::Font("bold")), "mathbf" => ("", Kind::Font("bold")), "sf" => ("", Kind::Font("sans-serif")),
+    "mathsf" => ("", Kind::Font("sans-serif")), "bbb" => ("", Kind::Font("double-struck")),
+    "mathbb" => ("", Kind::Font("double-struck")), "cc" => ("", Kind::Font("script")),
+    "mathcal" => ("", Kind::Font("script")), "tt" => ("", Kind::Font("monospace")),
+    "mathtt" => ("", Kind::Font("monospace")), "fr" => ("", Kind::Font("fraktur")),
+    "mathfrak" => ("", Kind::Font("fraktur")), "rm" => ("", Kind::Font("normal")),
+    "mathrm" => ("", Kind::Font("normal")),
+};
+
+/// The longest input in `SYMBOLS`
+const MAX_SYMBOL_LEN: usize = 21;
+
+/// Returns true if `name` is in the ASCIIMath symbol table (e.g., "sqrt", "xx", "->")
+pub fn is_asciimath_symbol(name: &str) -> bool {
+    return SYMBOLS.contains_key(name);
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    output: &'static str,
+    kind: Option<Kind>,     // None for numbers, letters, and quoted text
+    start: usize,
+    end: usize,
+}
+
+/// A parsed simple or intermediate expression
+struct Piece {
+    node: MathNode,
+    /// For a bracketed expression: the contents (without the brackets) split at top level commas
+    cells: Option<Vec<Vec<MathNode>>>,
+    /// For a bracketed expression: the opening and closing bracket
+    brackets: (&'static str, &'static str),
+}
+
+impl Piece {
+    fn new(node: MathNode) -> Piece {
+        return Piece { node, cells: None, brackets: ("", "") };
+    }
+
+    /// The node with any brackets removed (e.g., the `(a+b)` in `(a+b)/2` loses its parens)
+    fn without_brackets(self) -> MathNode {
+        return match self.cells {
+            Some(cells) if self.brackets.0 != "|" && self.brackets.0 != "‖" => MathNode::mrow(join_cells(cells)),
+            _ => self.node,
+        };
+    }
+}
+
+struct AsciiMathParser {
+    chars: Vec<char>,
+    pos: usize,
+    /// The positions after the '|'s that have a later '|' at the same bracket depth (see `find_bars_with_match`).
+    /// Only these can start `|...|`.
+    bars_with_match: HashSet<usize>,
+    /// The positions after the '|'s that were found to not have a matching '|'.
+    /// Without this, each nested '|' would be tried again every time an enclosing '|' fails to match (2^n).
+    unmatched_bars: HashSet<usize>,
+}
+
+impl AsciiMathParser {
+    fn new(ascii_math: &str) -> AsciiMathParser {
+        let mut parser = AsciiMathParser {
+            chars: ascii_math.chars().collect(),
+            pos: 0,
+            bars_with_match: HashSet::new(),
+            unmatched_bars: HashSet::new(),
+        };
+        parser.find_bars_with_match();
+        return parser;
+    }
+
+    /// Find the '|'s that have a later '|' at the same bracket depth (before the enclosing bracket is closed).
+    /// This is done in one pass so that trying to parse what follows a '|' as the contents of `|...|` is only done
+    /// when it might work.
+    fn find_bars_with_match(&mut self) {
+        // the '|'s at each depth that haven't been matched yet
+        let mut pending: Vec<Vec<usize>> = vec![vec![]];
+        while let Some(token) = self.next_token() {
+            match token.kind {
+                Some(Kind::LeftRight) => {
+                    let bars = pending.last_mut().unwrap();
+                    self.bars_with_match.extend(bars.drain(..));
+                    bars.push(self.pos);
+                },
+                Some(Kind::LeftBracket) => pending.push(vec![]),
+                Some(Kind::RightBracket) if pending.len() > 1 => {pending.pop();},
+                Some(Kind::RightBracket) => pending[0].clear(),
+                _ => (),
+            }
+        }
+        self.pos = 0;
+    }
+
+    fn skip_white_space(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        self.skip_white_space();
+        let start = self.pos;
+        if start >= self.chars.len() {
+            return None;
+        }
+
+        let ch = self.chars[start];
+        if ch.is_ascii_digit() || (ch == '.' && self.chars.get(start+1).is_some_and(|ch| ch.is_ascii_digit())) {
+            let mut seen_point = false;
+            while self.pos < self.chars.len() {
+                let ch = self.chars[self.pos];
+                if ch.is_ascii_digit() {
+                    self.pos += 1;
+                } else if ch == '.' && !seen_point && self.chars.get(self.pos+1).is_some_and(|ch| ch.is_ascii_digit()) {
+                    seen_point = true;
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            return Some( self.token(start, "", None) );
+        }
+
+        if ch == '"' {
+            let end = self.chars[start+1..].iter().position(|&ch| ch == '"').map(|i| start + 1 + i);
+            self.pos = match end {
+                Some(end) => end + 1,
+                None => self.chars.len(),       // error reported by caller
+            };
+            return Some( self.token(start, "", None) );
+        }
+
+        // longest match in the symbol table
+        let max_len = MAX_SYMBOL_LEN.min(self.chars.len() - start);
+        for len in (1..=max_len).rev() {
+            let text: String = self.chars[start..start+len].iter().collect();
+            if let Some(&(output, kind)) = SYMBOLS.get(text.as_str()) {
+                self.pos = start + len;
+                return Some( self.token(start, output, Some(kind)) );
+            }
+        }
+
+        self.pos += 1;
+        return Some( self.token(start, "", None) );
+    }
+
+    fn token(&self, start: usize, output: &'static str, kind: Option<Kind>) -> Token {
+        return Token { text: self.chars[start..self.pos].iter().collect(), output, kind, start, end: self.pos };
+    }
+
+    fn peek_token(&mut self) -> Option<Token> {
+        let pos = self.pos;
+        let token = self.next_token();
+        self.pos = pos;
+        return token;
+    }
+
+    /// E ::= IE | I/I
+    /// Parse until a right bracket (or the end) which is returned (and consumed).
+    /// If `in_bars` is true, a `|` or `||` ends the expression.
+    fn parse_expression(&mut self, in_bars: bool) -> Result<(Vec<Piece>, Option<Token>)> {
+        let mut pieces = vec![];
+        loop {
+            let token = match self.peek_token() {
+                None => return Ok( (pieces, None) ),
+                Some(token) => token,
+            };
+            match token.kind {
+                Some(Kind::RightBracket) => {
+                    self.next_token();
+                    return Ok( (pieces, Some(token)) );
+                },
+                Some(Kind::LeftRight) if in_bars => {
+                    self.next_token();
+                    return Ok( (pieces, Some(token)) );
+                },
+                _ => (),
+            }
+
+            let mut is_function = matches!(token.kind, Some(Kind::Function) | Some(Kind::UnderOverFunction));
+            let mut piece = self.parse_intermediate()?;
+            while let Some(Token{kind: Some(Kind::Divide), ..}) = self.peek_token() {
+                let divide = self.next_token().unwrap();
+                if self.at_end_of_expression(in_bars) {
+                    return Err( ParseError::new("Missing denominator after '/'", divide.start, divide.end).into() );
+                }
+                let denominator = self.parse_intermediate()?;
+                piece = Piece::new( MathNode::new("mfrac", vec![piece.without_brackets(), denominator.without_brackets()]) );
+                is_function = false;
+            }
+            pieces.push(piece);
+            if is_function && !self.at_end_of_expression(in_bars) {
+                pieces.push( Piece::new(MathNode::mo("\u{2061}")) );
+            }
+        }
+    }
+
+    fn at_end_of_expression(&mut self, in_bars: bool) -> bool {
+        return match self.peek_token() {
+            None => true,
+            Some(token) => token.kind == Some(Kind::RightBracket) || (in_bars && token.kind == Some(Kind::LeftRight)),
+        };
+    }
+
+    /// I ::= S_S | S^S | S_S^S | S
+    fn parse_intermediate(&mut self) -> Result<Piece> {
+        let first = self.peek_token();
+        let base = self.parse_simple()?;
+        let limits = matches!(first.map(|token| token.kind), Some(Some(Kind::UnderOver)) | Some(Some(Kind::UnderOverFunction)));
+
+        let mut sub = None;
+        let mut sup = None;
+        loop {
+            let token = match self.peek_token() {
+                Some(token) if token.kind == Some(Kind::Subscript) || token.kind == Some(Kind::Superscript) => token,
+                _ => break,
+            };
+            self.next_token();
+            let script = if token.kind == Some(Kind::Subscript) {&mut sub} else {&mut sup};
+            if script.is_some() {
+                return Err( ParseError::new(&format!("Double '{}'", token.text), token.start, token.end).into() );
+            }
+            if self.at_end_of_expression(false) {
+                return Err( ParseError::new(&format!("Missing script after '{}'", token.text), token.start, token.end).into() );
+            }
+            *script = Some(self.parse_simple()?.without_brackets());
+        }
+
+        let base = match (sub.is_none() && sup.is_none(), base) {
+            (true, base) => return Ok(base),
+            (false, base) => base.node,
+        };
+        return Ok( Piece::new( match (sub, sup) {
+            (Some(sub), None) => MathNode::new(if limits {"munder"} else {"msub"}, vec![base, sub]),
+            (None, Some(sup)) => MathNode::new(if limits {"mover"} else {"msup"}, vec![base, sup]),
+            (Some(sub), Some(sup)) => MathNode::new(if limits {"munderover"} else {"msubsup"}, vec![base, sub, sup]),
+            (None, None) => unreachable!(),
+        }));
+    }
+
+    /// S ::= v | lEr | uS | bSS
+    fn parse_simple(&mut self) -> Result<Piece> {
+        let token = match self.next_token() {
+            None => return Err( ParseError::new("Unexpected end of input", self.chars.len(), self.chars.len() + 1).into() ),
+            Some(token) => token,
+        };
+        let kind = match token.kind {
+            Some(kind) => kind,
+            None => return self.parse_token_without_kind(token),
+        };
+        return Ok( match kind {
+            Kind::Const(element) => Piece::new(MathNode::token(element, token.output)),
+            Kind::UnderOver => Piece::new(MathNode::mo(token.output).attr("largeop", "true")),
+            Kind::Function | Kind::UnderOverFunction => Piece::new(MathNode::mi(token.output)),
+            Kind::LeftBracket => self.parse_bracketed(token)?,
+            Kind::LeftRight => {
+                // a '|' is a left bracket only if there is a matching '|'
+                let pos = self.pos;
+                let matched = if self.unmatched_bars.contains(&pos) || !self.bars_with_match.contains(&pos) {
+                    None
+                } else {
+                    match self.parse_expression(true) {
+                        Ok( (pieces, Some(close)) ) if close.kind == Some(Kind::LeftRight) => Some( (pieces, close) ),
+                        _ => None,
+                    }
+                };
+                match matched {
+                    Some( (pieces, close) ) => make_bracketed(pieces, token.output, close.output),
+                    None => {
+                        self.unmatched_bars.insert(pos);
+                        self.pos = pos;
+                        Piece::new(MathNode::mo(token.output))
+                    },
+                }
+            },
+            Kind::RightBracket => return Err( ParseError::new(&format!("Unmatched '{}'", token.text), token.start, token.end).into() ),
+            Kind::Divide => Piece::new(MathNode::mo("/")),
+            Kind::Subscript | Kind::Superscript => {
+                // script without a base
+                self.pos = token.start;
+                Piece::new(MathNode::new("mrow", vec![]))
+            },
+            Kind::Text => {
+                let text = self.read_raw_arg(&token)?;
+                Piece::new(MathNode::mtext(&text))
+            },
+            Kind::Color => {
+                let color = self.read_raw_arg(&token)?;
+                let arg = self.parse_argument(&token)?;
+                Piece::new(MathNode::new("mstyle", vec![arg.without_brackets()]).attr("mathcolor", color.trim()))
+            },
+            Kind::Sqrt => Piece::new(MathNode::new("msqrt", vec![self.parse_argument(&token)?.without_brackets()])),
+            Kind::Font(variant) => Piece::new(set_mathvariant(self.parse_argument(&token)?.without_brackets(), variant)),
+            Kind::Accent(is_over, is_stretchy) => {
+                let base = self.parse_argument(&token)?.without_brackets();
+                let accent = MathNode::mo(token.output).attr("stretchy", if is_stretchy {"true"} else {"false"});
+                Piece::new( if is_over {
+                    MathNode::new("mover", vec![base, accent]).attr("accent", "true")
+                } else {
+                    MathNode::new("munder", vec![base, accent]).attr("accentunder", "true")
+                })
+            },
+            Kind::Fence(close) => {
+                let arg = self.parse_argument(&token)?.without_brackets();
+                Piece::new( MathNode::new("mrow", vec![MathNode::mo(token.output), arg, MathNode::mo(close)]) )
+            },
+            Kind::Cancel => Piece::new(
+                MathNode::new("menclose", vec![self.parse_argument(&token)?.without_brackets()]).attr("notation", "updiagonalstrike")
+            ),
+            Kind::Frac | Kind::Root | Kind::Over | Kind::Under => {
+                let first = self.parse_argument(&token)?.without_brackets();
+                let second = self.parse_argument(&token)?.without_brackets();
+                Piece::new( match kind {
+                    Kind::Frac => MathNode::new("mfrac", vec![first, second]),
+                    Kind::Root => MathNode::new("mroot", vec![second, first]),
+                    Kind::Over => MathNode::new("mover", vec![second, first]),
+                    _ => MathNode::new("munder", vec![second, first]),
+                })
+            },
+        });
+    }
+
+    /// Numbers, quoted text, and chars not in the symbol table
+    fn parse_token_without_kind(&mut self, token: Token) -> Result<Piece> {
+        let first = token.text.chars().next().unwrap();
+        if first == '"' {
+            if token.text.len() < 2 || !token.text.ends_with('"') {
+                return Err( ParseError::new("Missing closing '\"'", token.start, token.end).into() );
+            }
+            return Ok( Piece::new(MathNode::mtext(&token.text[1..token.text.len()-1])) );
+        }
+        if first.is_ascii_digit() || first == '.' {
+            return Ok( Piece::new(MathNode::mn(&token.text)) );
+        }
+        if first.is_alphabetic() {
+            return Ok( Piece::new(MathNode::mi(&token.text)) );
+        }
+        return Ok( Piece::new(MathNode::mo(&token.text)) );
+    }
+
+    /// The argument to a command such as `sqrt`
+    fn parse_argument(&mut self, command: &Token) -> Result<Piece> {
+        if self.at_end_of_expression(false) {
+            return Err( ParseError::new(&format!("Missing argument for '{}'", command.text), command.start, command.end).into() );
+        }
+        return self.parse_simple();
+    }
+
+    /// The argument to `text(...)` or `color(...)` -- this isn't parsed
+    fn read_raw_arg(&mut self, command: &Token) -> Result<String> {
+        self.skip_white_space();
+        let open = self.chars.get(self.pos).copied();
+        let close = match open {
+            Some('(') => ')',
+            Some('[') => ']',
+            Some('{') => '}',
+            _ => return Err( ParseError::new(&format!("'{}' must be followed by a bracketed argument", command.text), command.start, command.end).into() ),
+        };
+        let start = self.pos;
+        return match self.chars[start+1..].iter().position(|&ch| ch == close) {
+            None => Err( ParseError::new(&format!("Missing '{}'", close), start, self.chars.len()).into() ),
+            Some(len) => {
+                self.pos = start + 1 + len + 1;
+                Ok( self.chars[start+1..start+1+len].iter().collect() )
+            },
+        };
+    }
+
+    /// lEr -- the left bracket has been read
+    fn parse_bracketed(&mut self, open: Token) -> Result<Piece> {
+        let (pieces, close) = self.parse_expression(false)?;
+        let close = match close {
+            None => return Err( ParseError::new(&format!("Missing closing bracket for '{}'", open.text), open.start, open.end).into() ),
+            Some(close) => close,
+        };
+        if let Some(table) = make_matrix(&pieces, open.output, close.output) {
+            return Ok( Piece::new(table) );
+        }
+        return Ok( make_bracketed(pieces, open.output, close.output) );
+    }
+}
+
+/// Turn a list of pieces into a list of nodes
+fn flatten(pieces: Vec<Piece>) -> Vec<MathNode> {
+    return pieces.into_iter().map(|piece| piece.node).collect();
+}
+
+fn is_comma(node: &MathNode) -> bool {
+    return node.is("mo") && node.text == ",";
+}
+
+/// Put the commas back in between the cells
+fn join_cells(cells: Vec<Vec<MathNode>>) -> Vec<MathNode> {
+    let mut result = vec![];
+    for (i, mut cell) in cells.into_iter().enumerate() {
+        if i > 0 {
+            result.push(MathNode::mo(","));
+        }
+        result.append(&mut cell);
+    }
+    return result;
+}
+
+fn make_bracketed(pieces: Vec<Piece>, open: &'static str, close: &'static str) -> Piece {
+    let contents = flatten(pieces);
+    let mut cells = vec![vec![]];
+    for node in &contents {
+        if is_comma(node) {
+            cells.push(vec![]);
+        } else {
+            cells.last_mut().unwrap().push(node.clone());
+        }
+    }
+
+    let mut children = Vec::with_capacity(contents.len() + 2);
+    if !open.is_empty() {
+        children.push(MathNode::mo(open));
+    }
+    children.push(MathNode::mrow(contents));
+    if !close.is_empty() {
+        children.push(MathNode::mo(close));
+    }
+    return Piece { node: MathNode::new("mrow", children), cells: Some(cells), brackets: (open, close) };
+}
+
+/// A matrix is a bracketed, comma separated list of bracketed rows that all use the same brackets
+/// and all have the same number of entries, e.g., `[[a,b],[c,d]]`.
+fn make_matrix(pieces: &[Piece], open: &str, close: &str) -> Option<MathNode> {
+    if pieces.len() < 3 {
+        return None;        // need at least two rows
+    }
+    let first_row = pieces[0].cells.as_ref()?;
+    let n_columns = first_row.len();
+    let row_brackets = pieces[0].brackets;
+    if row_brackets.0 == "|" || row_brackets.0 == "‖" {
+        return None;
+    }
+    let mut rows = vec![];
+    for (i, piece) in pieces.iter().enumerate() {
+        if i % 2 == 1 {
+            if !is_comma(&piece.node) {
+                return None;
+            }
+            continue;
+        }
+        match &piece.cells {
+            Some(cells) if cells.len() == n_columns && piece.brackets == row_brackets => {
+                rows.push( MathNode::new("mtr",
+                    cells.iter().map(|cell| MathNode::new("mtd", cell.clone())).collect()
+                ));
+            },
+            _ => return None,
+        }
+    }
+    if is_comma(&pieces[pieces.len() - 1].node) {
+        return None;        // ends in a comma
+    }
+
+    let mut table = MathNode::new("mtable", rows);
+    if open.is_empty() || open == "{" {
+        table.set_attr("columnalign", "left");      // e.g, cases
+    }
+    let mut children = vec![];
+    if !open.is_empty() {
+        children.push(MathNode::mo(open));
+    }
+    children.push(table);
+    if !close.is_empty() {
+        children.push(MathNode::mo(close));
+    }
+    return Some( MathNode::new("mrow", children) );
+}
+
+/// Set the mathvariant on all the token elements in `node`
+fn set_mathvariant(mut node: MathNode, variant: &str) -> MathNode {
+    if node.is_token() {
+        return node.attr("mathvariant", variant);
+    }
+    node.children = node.children.into_iter().map(|child| set_mathvariant(child, variant)).collect();
+    return node;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The MathML for `ascii_math` without the `<math>` wrapper
+    fn mathml(ascii_math: &str) -> String {
+        let mathml = asciimath_to_mathml(ascii_math).unwrap();
+        return mathml.trim_start_matches("<math display=\"block\">").trim_end_matches("</math>").to_string();
+    }
+
+    /// The position (start, end) and message of the error for `ascii_math`
+    fn error(ascii_math: &str) -> (usize, usize, String) {
+        let e = asciimath_to_mathml(ascii_math).unwrap_err();
+        let e = e.downcast_ref::<ParseError>().expect("a ParseError");
+        return (e.start, e.end, e.message.clone());
+    }
+
+    #[test]
+    fn absolute_value() {
+        assert_eq!(mathml("|x|"), "<mrow><mo>|</mo><mi>x</mi><mo>|</mo></mrow>");
+        assert_eq!(mathml("a|b"), "<mi>a</mi><mo>|</mo><mi>b</mi>");
+    }
+
+    #[test]
+    fn matrix() {
+        assert_eq!(mathml("[[1,2],[3,4]]"),
+            "<mrow><mo>[</mo><mtable>\
+                <mtr><mtd><mn>1</mn></mtd><mtd><mn>2</mn></mtd></mtr>\
+                <mtr><mtd><mn>3</mn></mtd><mtd><mn>4</mn></mtd></mtr>\
+            </mtable><mo>]</mo></mrow>");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("a/"), (1, 2, "Missing denominator after '/'".to_string()));
+        assert_eq!(error("(a"), (0, 1, "Missing closing bracket for '('".to_string()));
+        assert_eq!(error("sqrt"), (0, 4, "Missing argument for 'sqrt'".to_string()));
+    }
+
+    #[test]
+    fn unmatched_bars_are_not_exponential() {
+        // each '|' could start '|...|', so without remembering what failed this takes 2^n tries
+        assert!(asciimath_to_mathml(&"|(".repeat(20)).is_err());
+        assert!(asciimath_to_mathml(&"|[|(".repeat(20)).is_err());
+        assert!(asciimath_to_mathml(&"|(|x|)".repeat(20)).is_ok());
+    }
+}
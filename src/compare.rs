@@ -0,0 +1,227 @@
+//! Compare the speech and braille for an expression under several sets of preferences
+//! (e.g., ClearSpeak vs. SimpleSpeak, or Nemeth vs. UEB).
+//!
+//! Each result is split into parts that are marked if they aren't in the first (baseline) result.
+//! Speech is compared word by word and braille cell by cell.
+use crate::pipeline::{speech, braille};
+use crate::settings::Settings;
+
+/// The values to compare for each kind of preference. An empty list means "use the current setting".
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ComparisonChoices {
+    pub languages: Vec<String>,
+    pub speech_styles: Vec<String>,
+    pub braille_codes: Vec<String>,
+}
+
+impl ComparisonChoices {
+    /// Add `value` to the list for `kind` ("language", "speech_style", or "braille_code") or remove it if it is there
+    pub fn toggle(&mut self, kind: &str, value: &str) {
+        let values = match kind {
+            "language" => &mut self.languages,
+            "speech_style" => &mut self.speech_styles,
+            "braille_code" => &mut self.braille_codes,
+            _ => return,
+        };
+        match values.iter().position(|v| v == value) {
+            Some(i) => {values.remove(i);},
+            None => values.push(value.to_string()),
+        }
+    }
+
+    /// The sets of preferences to compare (with a label for each): every combination of the choices, starting from `settings`.
+    /// TTS markup is turned off so that only the words are compared.
+    pub fn variants(&self, settings: &Settings) -> Vec<(String, Settings)> {
+        fn or_current(values: &[String], current: &str) -> Vec<String> {
+            return if values.is_empty() {vec![current.to_string()]} else {values.to_vec()};
+        }
+        let mut variants = vec![];
+        for language in or_current(&self.languages, &settings.language) {
+            for speech_style in or_current(&self.speech_styles, &settings.speech_style) {
+                for braille_code in or_current(&self.braille_codes, &settings.braille_code) {
+                    let label = format!("{}, {}, {}", language, speech_style, braille_code);
+                    let variant = Settings {
+                        language: language.clone(),
+                        speech_style: speech_style.clone(),
+                        braille_code,
+                        tts: "None".to_string(),
+                        ..settings.clone()
+                    };
+                    variants.push( (label, variant) );
+                }
+            }
+        }
+        return variants;
+    }
+}
+
+/// Part of a result
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffPart {
+    pub text: String,
+    /// True if this part isn't in the baseline result
+    pub changed: bool,
+}
+
+/// The speech and braille for one set of preferences
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub label: String,
+    pub speech: Vec<DiffPart>,
+    /// Unicode braille
+    pub braille: Vec<DiffPart>,
+    /// Set if the speech or braille couldn't be generated
+    pub error: Option<String>,
+}
+
+/// Generate the speech and braille for the MathML most recently given to MathCAT under each of `variants`.
+/// The differences from the first variant are marked.
+/// This changes MathCAT's preferences -- the caller should restore them afterwards (see `Settings::apply`).
+pub fn compare(variants: &[(String, Settings)]) -> Vec<Comparison> {
+    let results: Vec<(String, String, Option<String>)> = variants.iter()
+        .map(|(_, settings)| match speech(settings).and_then(|speech| Ok( (speech, braille(settings, "")?) )) {
+            Ok( (speech, braille) ) => (speech, braille, None),
+            Err(e) => (String::default(), String::default(), Some(format!("{:#}", e))),
+        })
+        .collect();
+    let (baseline_speech, baseline_braille) = match results.first() {
+        Some( (speech, braille, _) ) => (words(speech), cells(braille)),
+        None => return vec![],
+    };
+    return variants.iter().zip(results.iter())
+        .map(|((label, _), (speech, braille, error))| Comparison {
+            label: label.clone(),
+            speech: diff_parts(&baseline_speech, &words(speech), " "),
+            braille: diff_parts(&baseline_braille, &cells(braille), ""),
+            error: error.clone(),
+        })
+        .collect();
+}
+
+fn words(speech: &str) -> Vec<String> {
+    return speech.split_whitespace().map(|word| word.to_string()).collect();
+}
+
+fn cells(braille: &str) -> Vec<String> {
+    return braille.chars().map(|ch| ch.to_string()).collect();
+}
+
+/// Split `tokens` into parts, marking the tokens that aren't part of the longest common subsequence with `baseline`.
+/// The tokens in a part are joined with `separator`.
+fn diff_parts(baseline: &[String], tokens: &[String], separator: &str) -> Vec<DiffPart> {
+    // lcs[i][j] is the length of the longest common subsequence of baseline[i..] and tokens[j..]
+    let mut lcs = vec![vec![0usize; tokens.len() + 1]; baseline.len() + 1];
+    for i in (0..baseline.len()).rev() {
+        for j in (0..tokens.len()).rev() {
+            lcs[i][j] = if baseline[i] == tokens[j] {lcs[i+1][j+1] + 1} else {lcs[i+1][j].max(lcs[i][j+1])};
+        }
+    }
+
+    let mut parts: Vec<DiffPart> = vec![];
+    let (mut i, mut j) = (0, 0);
+    while j < tokens.len() {
+        let changed = if i < baseline.len() && baseline[i] == tokens[j] {
+            i += 1;
+            false
+        } else if i < baseline.len() && lcs[i+1][j] >= lcs[i][j+1] {
+            i += 1;     // skip the baseline token (it was removed)
+            continue;
+        } else {
+            true
+        };
+        match parts.last_mut() {
+            Some(part) if part.changed == changed => {
+                part.text.push_str(separator);
+                part.text.push_str(&tokens[j]);
+            },
+            Some(part) => {
+                // the parts are concatenated when shown, so the separator goes on the unchanged side
+                if part.changed {
+                    parts.push( DiffPart { text: separator.to_string() + &tokens[j], changed } );
+                } else {
+                    part.text.push_str(separator);
+                    parts.push( DiffPart { text: tokens[j].clone(), changed } );
+                }
+            },
+            None => parts.push( DiffPart { text: tokens[j].clone(), changed } ),
+        }
+        j += 1;
+    }
+    return parts;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (text, changed) for each part of the word-by-word diff of `speech` against `baseline`
+    fn diff(baseline: &str, speech: &str) -> Vec<(String, bool)> {
+        return diff_parts(&words(baseline), &words(speech), " ").into_iter().map(|part| (part.text, part.changed)).collect();
+    }
+
+    fn part(text: &str, changed: bool) -> (String, bool) {
+        return (text.to_string(), changed);
+    }
+
+    #[test]
+    fn insertions() {
+        assert_eq!(diff("a b c", "x a b c"), vec![part("x", true), part(" a b c", false)]);
+        assert_eq!(diff("a c", "a b c"), vec![part("a ", false), part("b", true), part(" c", false)]);
+        assert_eq!(diff("a b", "a b c"), vec![part("a b ", false), part("c", true)]);
+    }
+
+    #[test]
+    fn deletions() {
+        assert_eq!(diff("x a b", "a b"), vec![part("a b", false)]);
+        assert_eq!(diff("a x b", "a b"), vec![part("a b", false)]);
+        assert_eq!(diff("a b x", "a b"), vec![part("a b", false)]);
+        assert_eq!(diff("a b", ""), vec![]);
+    }
+
+    #[test]
+    fn replacements() {
+        assert_eq!(diff("x b", "y b"), vec![part("y", true), part(" b", false)]);
+        assert_eq!(diff("a x c", "a y z c"), vec![part("a ", false), part("y z", true), part(" c", false)]);
+        assert_eq!(diff("a x", "a y"), vec![part("a ", false), part("y", true)]);
+        assert_eq!(diff("a b", "a b"), vec![part("a b", false)]);
+    }
+
+    #[test]
+    fn braille_cells() {
+        let parts = diff_parts(&cells("⠁⠃⠉"), &cells("⠁⠙⠋⠉"), "");
+        let parts: Vec<(String, bool)> = parts.into_iter().map(|part| (part.text, part.changed)).collect();
+        assert_eq!(parts, vec![part("⠁", false), part("⠙⠋", true), part("⠉", false)]);
+    }
+
+    #[test]
+    fn variants() {
+        let settings = Settings { tts: "SSML".to_string(), ..Settings::default() };
+        let labels = |choices: &ComparisonChoices| -> Vec<String> {
+            let variants = choices.variants(&settings);
+            assert!(variants.iter().all(|(_, variant)| variant.tts == "None"));
+            return variants.into_iter().map(|(label, _)| label).collect();
+        };
+        // an empty list means the current setting
+        assert_eq!(labels(&ComparisonChoices::default()), vec!["en, ClearSpeak, Nemeth"]);
+        let choices = ComparisonChoices {
+            languages: vec![],
+            speech_styles: vec!["ClearSpeak".to_string(), "SimpleSpeak".to_string()],
+            braille_codes: vec!["Nemeth".to_string(), "UEB".to_string()],
+        };
+        assert_eq!(labels(&choices), vec!["en, ClearSpeak, Nemeth", "en, ClearSpeak, UEB", "en, SimpleSpeak, Nemeth", "en, SimpleSpeak, UEB"]);
+        assert_eq!(choices.variants(&settings)[3].1.braille_code, "UEB");
+    }
+
+    #[test]
+    fn toggle() {
+        let mut choices = ComparisonChoices::default();
+        choices.toggle("language", "fr");
+        choices.toggle("language", "es");
+        choices.toggle("braille_code", "UEB");
+        choices.toggle("nav_mode", "Simple");
+        assert_eq!( (choices.languages.clone(), choices.braille_codes.clone()), (vec!["fr".to_string(), "es".to_string()], vec!["UEB".to_string()]) );
+        choices.toggle("language", "fr");
+        assert_eq!(choices.languages, vec!["es"]);
+        assert!(choices.speech_styles.is_empty());
+    }
+}
@@ -0,0 +1,761 @@
+//! Convert Content MathML and OpenMath (e.g., the output of a computer algebra system) to presentation MathML.
+//!
+//! Both are read into the same `Content` tree (OpenMath symbols are renamed to the Content MathML names) and then
+//! written out as presentation MathML. Where the notation is ambiguous (e.g., `|x|` or `(a, b]`), an `intent` is added
+//! so that the meaning isn't lost. `intent` attributes in the input are kept.
+use anyhow::{Result, bail};
+use phf::{phf_map, phf_set};
+use roxmltree::Node;
+use crate::convert::ParseError;
+use crate::mathml::{MathNode, math_to_string};
+
+/// The precedence of an atom (a token, fraction, function call, ...) -- it never needs parentheses
+const ATOM: u8 = 10;
+
+/// Operators written between their arguments: content name => (operator, precedence)
+static INFIX: phf::Map<&'static str, (&'static str, u8)> = phf_map! {
+    "implies" => ("⇒", 1), "equivalent" => ("⇔", 1),
+    "or" => ("∨", 2), "xor" => ("⊻", 2), "and" => ("∧", 3),
+    "eq" => ("=", 4), "neq" => ("≠", 4), "lt" => ("<", 4), "gt" => (">", 4), "leq" => ("≤", 4), "geq" => ("≥", 4),
+    "approx" => ("≈", 4), "factorof" => ("|", 4), "tendsto" => ("→", 4),
+    "in" => ("∈", 4), "notin" => ("∉", 4), "subset" => ("⊆", 4), "prsubset" => ("⊂", 4),
+    "notsubset" => ("⊈", 4), "notprsubset" => ("⊄", 4),
+    "union" => ("∪", 5), "intersect" => ("∩", 5), "setdiff" => ("∖", 5), "cartesianproduct" => ("×", 5),
+    "plus" => ("+", 6), "minus" => ("−", 6),
+    "times" => ("\u{2062}", 7), "compose" => ("∘", 7), "rem" => ("mod", 7),
+};
+
+/// Operators where `a op b op c` means `(a op b) op c` -- the right operands need parentheses if they have the same precedence
+static NON_ASSOCIATIVE: phf::Set<&'static str> = phf_set! { "minus", "setdiff", "rem" };
+
+/// Functions written as `name(args)`: content name => name
+static FUNCTIONS: phf::Map<&'static str, &'static str> = phf_map! {
+    "sin" => "sin", "cos" => "cos", "tan" => "tan", "sec" => "sec", "csc" => "csc", "cot" => "cot",
+    "sinh" => "sinh", "cosh" => "cosh", "tanh" => "tanh", "sech" => "sech", "csch" => "csch", "coth" => "coth",
+    "arcsin" => "arcsin", "arccos" => "arccos", "arctan" => "arctan", "arcsec" => "arcsec", "arccsc" => "arccsc",
+    "arccot" => "arccot", "arcsinh" => "arcsinh", "arccosh" => "arccosh", "arctanh" => "arctanh",
+    "ln" => "ln", "log" => "log", "min" => "min", "max" => "max", "gcd" => "gcd", "lcm" => "lcm",
+    "arg" => "arg", "real" => "Re", "imaginary" => "Im", "card" => "card", "quotient" => "quotient",
+};
+
+/// Symbols that are constants: content name => presentation
+static CONSTANTS: phf::Map<&'static str, &'static str> = phf_map! {
+    "pi" => "π", "exponentiale" => "ⅇ", "imaginaryi" => "ⅈ", "infinity" => "∞", "emptyset" => "∅",
+    "eulergamma" => "γ", "notanumber" => "NaN", "true" => "true", "false" => "false",
+    "naturalnumbers" => "ℕ", "integers" => "ℤ", "rationals" => "ℚ", "reals" => "ℝ", "complexes" => "ℂ", "primes" => "ℙ",
+};
+
+/// OpenMath symbol names (and `csymbol` names) that have a different name in Content MathML
+static OPENMATH_NAMES: phf::Map<&'static str, &'static str> = phf_map! {
+    "unary_minus" => "minus", "e" => "exponentiale", "i" => "imaginaryi", "defint" => "int", "rational" => "divide",
+    "integer_interval" => "interval_cc", "NaN" => "notanumber", "gamma" => "eulergamma",
+};
+
+/// OpenMath symbols whose last argument is a `lambda` that binds the variable (e.g., `defint(interval(a,b), lambda(x, f))`)
+static BINDING_OPERATORS: phf::Set<&'static str> = phf_set! { "int", "sum", "product", "limit", "diff" };
+
+#[derive(Debug, Clone, PartialEq)]
+enum ContentKind {
+    /// A variable (`ci` or `OMV`)
+    Identifier(String),
+    /// A number (`cn`, `OMI`, or `OMF`)
+    Number(String),
+    /// An operator or constant (e.g., `plus` or `pi`) from an empty element, `csymbol`, or `OMS`
+    Symbol(String),
+    /// A string (`cs` or `OMSTR`)
+    Text(String),
+    /// An application of `head` (`apply`, `bind`, `OMA`, or `OMBIND`) or a container (e.g., `set`).
+    /// `qualifiers` are things like ("lowlimit", ...) or ("degree", ...).
+    Apply { head: Box<Content>, bvars: Vec<Content>, qualifiers: Vec<(String, Content)>, args: Vec<Content> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Content {
+    kind: ContentKind,
+    /// An `intent` given in the input
+    intent: Option<String>,
+}
+
+impl Content {
+    fn new(kind: ContentKind) -> Content {
+        return Content { kind, intent: None };
+    }
+
+    fn symbol(name: &str) -> Content {
+        let name = OPENMATH_NAMES.get(name).copied().unwrap_or(name);
+        return Content::new(ContentKind::Symbol(name.to_string()));
+    }
+
+    fn apply(head: Content, args: Vec<Content>) -> Content {
+        return Content::new(ContentKind::Apply { head: Box::new(head), bvars: vec![], qualifiers: vec![], args });
+    }
+
+    fn symbol_name(&self) -> Option<&str> {
+        return match &self.kind {
+            ContentKind::Symbol(name) => Some(name),
+            _ => None,
+        };
+    }
+
+    /// The name of the head if this is an application of a symbol
+    fn head_name(&self) -> Option<&str> {
+        return match &self.kind {
+            ContentKind::Apply { head, .. } => head.symbol_name(),
+            _ => None,
+        };
+    }
+}
+
+/// Convert Content MathML (with or without the `math` element) to a MathML string (display math)
+pub fn content_mathml_to_mathml(content_mathml: &str) -> Result<String> {
+    let document = parse_xml(content_mathml, "The Content MathML")?;
+    let content = read_content_mathml(document.root_element())?;
+    return Ok( math_to_string(vec![to_presentation(&content).0], true) );
+}
+
+/// Convert OpenMath (XML encoding, with or without the `OMOBJ` element) to a MathML string (display math)
+pub fn openmath_to_mathml(openmath: &str) -> Result<String> {
+    let document = parse_xml(openmath, "The OpenMath")?;
+    let content = read_openmath(document.root_element())?;
+    return Ok( math_to_string(vec![to_presentation(&content).0], true) );
+}
+
+fn parse_xml<'input>(xml: &'input str, what: &str) -> Result<roxmltree::Document<'input>> {
+    return match roxmltree::Document::parse(xml) {
+        Ok(document) => Ok(document),
+        Err(e) => Err( ParseError::from_xml_error(&format!("{} isn't valid XML", what), &e, xml, 0).into() ),
+    };
+}
+
+fn element_children<'a, 'input: 'a>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    return node.children().filter(|child| child.is_element());
+}
+
+fn text_of(node: Node) -> String {
+    return node.descendants().filter(|descendant| descendant.is_text()).filter_map(|text| text.text()).collect::<String>().trim().to_string();
+}
+
+fn with_intent(mut content: Content, node: Node) -> Content {
+    if let Some(intent) = node.attribute("intent") {
+        content.intent = Some(intent.to_string());
+    }
+    return content;
+}
+
+/// Read a Content MathML element
+fn read_content_mathml(node: Node) -> Result<Content> {
+    let name = node.tag_name().name();
+    let content = match name {
+        "semantics" => {
+            // use the Content MathML annotation if there is one
+            let annotation = element_children(node).find(|child| child.tag_name().name() == "annotation-xml" &&
+                    child.attribute("encoding").is_some_and(|encoding| encoding.starts_with("MathML-Content")));
+            match annotation.and_then(|annotation| element_children(annotation).next()).or_else(|| element_children(node).next()) {
+                Some(child) => read_content_mathml(child)?,
+                None => bail!("<semantics> is empty"),
+            }
+        },
+        "math" => match element_children(node).next() {
+            Some(child) => read_content_mathml(child)?,
+            None => bail!("<{}> is empty", name),
+        },
+        "ci" => Content::new(ContentKind::Identifier(text_of(node))),
+        "cs" => Content::new(ContentKind::Text(text_of(node))),
+        "csymbol" => Content::symbol(&text_of(node)),
+        "cn" => read_cn(node),
+        "apply" | "bind" => {
+            let mut children = element_children(node);
+            let head = match children.next() {
+                Some(head) => read_content_mathml(head)?,
+                None => bail!("<{}> is empty", name),
+            };
+            read_arguments(head, children)?
+        },
+        "interval" => {
+            let head = match node.attribute("closure").unwrap_or("closed") {
+                "open" => "interval_oo",
+                "open-closed" => "interval_oc",
+                "closed-open" => "interval_co",
+                _ => "interval_cc",
+            };
+            read_arguments(Content::symbol(head), element_children(node))?
+        },
+        "set" | "list" | "vector" | "matrix" | "matrixrow" | "piecewise" | "piece" | "otherwise" | "lambda" =>
+            read_arguments(Content::symbol(name), element_children(node))?,
+        "mi" | "mn" | "mo" | "mrow" | "mfrac" | "msup" | "msub" | "mtext" =>
+            bail!("Presentation MathML (<{}>) can't be mixed with Content MathML", name),
+        _ => {
+            if element_children(node).next().is_some() {
+                bail!("Unknown Content MathML element <{}>", name);
+            }
+            Content::symbol(name)       // an operator such as <plus/> (or one we don't know about)
+        },
+    };
+    return Ok( with_intent(content, node) );
+}
+
+/// Read the bound variables, qualifiers, and arguments of an `apply` (or something with the same structure)
+fn read_arguments<'a, 'input: 'a>(head: Content, children: impl Iterator<Item = Node<'a, 'input>>) -> Result<Content> {
+    let mut bvars = vec![];
+    let mut qualifiers = vec![];
+    let mut args = vec![];
+    for child in children {
+        let name = child.tag_name().name();
+        match name {
+            "bvar" => {
+                for bvar_child in element_children(child) {
+                    if bvar_child.tag_name().name() == "degree" {
+                        qualifiers.push( ("degree".to_string(), read_qualifier(bvar_child)?) );
+                    } else {
+                        bvars.push(read_content_mathml(bvar_child)?);
+                    }
+                }
+            },
+            "lowlimit" | "uplimit" | "degree" | "logbase" | "condition" | "domainofapplication" | "momentabout" =>
+                qualifiers.push( (name.to_string(), read_qualifier(child)?) ),
+            _ => args.push(read_content_mathml(child)?),
+        }
+    }
+    return Ok( Content::new(ContentKind::Apply { head: Box::new(head), bvars, qualifiers, args }) );
+}
+
+fn read_qualifier(node: Node) -> Result<Content> {
+    return match element_children(node).next() {
+        Some(child) => read_content_mathml(child),
+        None => bail!("<{}> is empty", node.tag_name().name()),
+    };
+}
+
+/// Read a `cn`. Numbers with parts (e.g., `<cn type="rational">1<sep/>2</cn>`) become applications.
+fn read_cn(node: Node) -> Content {
+    let parts: Vec<String> = node.children()
+        .filter(|child| child.is_text())
+        .filter_map(|child| child.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .collect();
+    let number = |text: &str| Content::new(ContentKind::Number(text.to_string()));
+    if parts.len() != 2 {
+        return number(&parts.concat());
+    }
+    return match node.attribute("type").unwrap_or("") {
+        "rational" => Content::apply(Content::symbol("divide"), vec![number(&parts[0]), number(&parts[1])]),
+        "e-notation" => Content::apply(Content::symbol("times"), vec![
+            number(&parts[0]), Content::apply(Content::symbol("power"), vec![number("10"), number(&parts[1])])
+        ]),
+        "complex-cartesian" => Content::apply(Content::symbol("plus"), vec![
+            number(&parts[0]), Content::apply(Content::symbol("times"), vec![number(&parts[1]), Content::symbol("imaginaryi")])
+        ]),
+        "complex-polar" => Content::apply(Content::symbol("times"), vec![
+            number(&parts[0]), Content::apply(Content::symbol("exp"), vec![
+                Content::apply(Content::symbol("times"), vec![number(&parts[1]), Content::symbol("imaginaryi")])
+            ])
+        ]),
+        _ => number(&parts.concat()),
+    };
+}
+
+/// Read an OpenMath element
+fn read_openmath(node: Node) -> Result<Content> {
+    let name = node.tag_name().name();
+    let content = match name {
+        "OMOBJ" => match element_children(node).next() {
+            Some(child) => read_openmath(child)?,
+            None => bail!("<OMOBJ> is empty"),
+        },
+        "OMI" => Content::new(ContentKind::Number(text_of(node))),
+        "OMF" => Content::new(ContentKind::Number(node.attribute("dec").map_or_else(|| text_of(node), |dec| dec.to_string()))),
+        "OMV" => Content::new(ContentKind::Identifier(node.attribute("name").unwrap_or("").to_string())),
+        "OMSTR" => Content::new(ContentKind::Text(text_of(node))),
+        "OMS" => Content::symbol(node.attribute("name").unwrap_or("")),
+        "OMA" => {
+            let mut children = element_children(node);
+            let head = match children.next() {
+                Some(head) => read_openmath(head)?,
+                None => bail!("<OMA> is empty"),
+            };
+            let args = children.map(read_openmath).collect::<Result<Vec<Content>>>()?;
+            openmath_application(head, args)
+        },
+        "OMBIND" => {
+            let mut head = None;
+            let mut bvars = vec![];
+            let mut args = vec![];
+            for child in element_children(node) {
+                if child.tag_name().name() == "OMBVAR" {
+                    bvars = element_children(child).map(read_openmath).collect::<Result<Vec<Content>>>()?;
+                } else if head.is_none() {
+                    head = Some(read_openmath(child)?);
+                } else {
+                    args.push(read_openmath(child)?);
+                }
+            }
+            let head = match head {
+                Some(head) => Box::new(head),
+                None => bail!("<OMBIND> is empty"),
+            };
+            Content::new(ContentKind::Apply { head, bvars, qualifiers: vec![], args })
+        },
+        "OMATTR" => match element_children(node).last() {     // the attributions don't change the meaning
+            Some(child) => read_openmath(child)?,
+            None => bail!("<OMATTR> is empty"),
+        },
+        "OME" => bail!("The OpenMath contains an error (<OME>)"),
+        _ => bail!("Unknown OpenMath element <{}>", name),
+    };
+    return Ok( with_intent(content, node) );
+}
+
+/// OpenMath puts some things in arguments that Content MathML puts in qualifiers and bound variables
+/// (e.g., `defint(interval(a,b), lambda(x, f))` is `<int/><bvar>x</bvar><lowlimit>a</lowlimit>...f`)
+fn openmath_application(head: Content, mut args: Vec<Content>) -> Content {
+    let name = head.symbol_name().unwrap_or("").to_string();
+    let mut bvars = vec![];
+    let mut qualifiers = vec![];
+    match name.as_str() {
+        "root" if args.len() == 2 => qualifiers.push( ("degree".to_string(), args.pop().unwrap()) ),
+        "log" if args.len() == 2 => qualifiers.push( ("logbase".to_string(), args.remove(0)) ),
+        _ if BINDING_OPERATORS.contains(name.as_str()) => {
+            if let Some(ContentKind::Apply { head: lambda, bvars: lambda_bvars, args: body, .. }) = args.last().map(|last| &last.kind) {
+                if lambda.symbol_name() == Some("lambda") && body.len() == 1 {
+                    bvars = lambda_bvars.clone();
+                    let body = body[0].clone();
+                    args.pop();
+                    if name == "limit" && !args.is_empty() {
+                        qualifiers.push( ("lowlimit".to_string(), args.remove(0)) );
+                        args.clear();   // the limit type (e.g., "above")
+                    } else if let Some(range) = args.pop() {
+                        match range.kind {
+                            ContentKind::Apply { head, args: mut limits, .. }
+                                    if head.symbol_name().is_some_and(|name| name.starts_with("interval")) && limits.len() == 2 => {
+                                qualifiers.push( ("uplimit".to_string(), limits.pop().unwrap()) );
+                                qualifiers.push( ("lowlimit".to_string(), limits.pop().unwrap()) );
+                            },
+                            _ => qualifiers.push( ("domainofapplication".to_string(), range) ),
+                        }
+                    }
+                    args.push(body);
+                }
+            }
+        },
+        _ => (),
+    }
+    return Content::new(ContentKind::Apply { head: Box::new(head), bvars, qualifiers, args });
+}
+
+/// Convert `content` to presentation MathML, returning the node and its precedence
+fn to_presentation(content: &Content) -> (MathNode, u8) {
+    let (mut node, precedence) = match &content.kind {
+        ContentKind::Identifier(name) => (MathNode::mi(name), ATOM),
+        ContentKind::Number(number) => match number.strip_prefix('-') {
+            Some(number) => (MathNode::mrow(vec![MathNode::mo("−"), MathNode::mn(number)]), 6),
+            None => (MathNode::mn(number), ATOM),
+        },
+        ContentKind::Text(text) => (MathNode::mtext(text), ATOM),
+        ContentKind::Symbol(name) => match CONSTANTS.get(name.as_str()) {
+            Some(constant) => (MathNode::mi(constant), ATOM),
+            None => (MathNode::mi(FUNCTIONS.get(name.as_str()).copied().unwrap_or(name)), ATOM),
+        },
+        ContentKind::Apply { head, bvars, qualifiers, args } => apply_to_presentation(head, bvars, qualifiers, args),
+    };
+    if let Some(intent) = &content.intent {
+        node.set_attr("intent", intent);
+    }
+    return (node, precedence);
+}
+
+/// The presentation MathML for `content`, in parentheses if its precedence is less than `min_precedence`
+fn operand(content: &Content, min_precedence: u8) -> MathNode {
+    let (node, precedence) = to_presentation(content);
+    if precedence < min_precedence {
+        return fenced("(", vec![node], ")");
+    }
+    return node;
+}
+
+fn fenced(open: &str, mut contents: Vec<MathNode>, close: &str) -> MathNode {
+    contents.insert(0, MathNode::mo(open));
+    contents.push(MathNode::mo(close));
+    return MathNode::new("mrow", contents);
+}
+
+/// `args` separated by commas
+fn comma_list(args: &[Content]) -> Vec<MathNode> {
+    let mut result = vec![];
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            result.push(MathNode::mo(","));
+        }
+        result.push(operand(arg, 1));
+    }
+    return result;
+}
+
+/// Mark `args` as the arguments of the intent `name` -- returns the marked args and the intent
+fn intent_args(name: &str, args: Vec<MathNode>) -> (Vec<MathNode>, String) {
+    let refs: Vec<String> = (1..=args.len()).map(|i| format!("$a{}", i)).collect();
+    let args = args.into_iter().enumerate().map(|(i, arg)| arg.attr("arg", &format!("a{}", i + 1))).collect();
+    return (args, format!("{}({})", name, refs.join(",")));
+}
+
+/// A function applied to arguments: `f(a, b)`, or `f x` if there is a single simple argument
+fn function_call(name: MathNode, args: &[Content]) -> MathNode {
+    let args = match args {
+        [arg] => match to_presentation(arg) {
+            (arg, ATOM) if arg.is_token() => arg,
+            _ => fenced("(", comma_list(args), ")"),
+        },
+        _ => fenced("(", comma_list(args), ")"),
+    };
+    return MathNode::new("mrow", vec![name, MathNode::mo("\u{2061}"), args]);
+}
+
+fn qualifier<'a>(qualifiers: &'a [(String, Content)], name: &str) -> Option<&'a Content> {
+    return qualifiers.iter().find(|(qualifier, _)| qualifier == name).map(|(_, content)| content);
+}
+
+/// The lower and upper limits (the lower limit might be a condition such as `x ∈ S`) for a sum, integral, ...
+fn limits(qualifiers: &[(String, Content)], args: &[Content]) -> (Option<MathNode>, Option<MathNode>) {
+    if let (Some(low), Some(high)) = (qualifier(qualifiers, "lowlimit"), qualifier(qualifiers, "uplimit")) {
+        return (Some(to_presentation(low).0), Some(to_presentation(high).0));
+    }
+    // Content MathML 2 allows an interval argument instead of limits
+    if let Some(ContentKind::Apply { head, args: limits, .. }) = args.first().filter(|_| args.len() > 1).map(|arg| &arg.kind) {
+        if head.symbol_name().is_some_and(|name| name.starts_with("interval")) && limits.len() == 2 {
+            return (Some(to_presentation(&limits[0]).0), Some(to_presentation(&limits[1]).0));
+        }
+    }
+    let lower = qualifier(qualifiers, "condition").or_else(|| qualifier(qualifiers, "domainofapplication"));
+    return (lower.map(|lower| to_presentation(lower).0), None);
+}
+
+/// The last argument (the body of a sum, integral, ...)
+fn body(args: &[Content], min_precedence: u8) -> MathNode {
+    return match args.last() {
+        Some(body) => operand(body, min_precedence),
+        None => MathNode::new("mrow", vec![]),
+    };
+}
+
+fn apply_to_presentation(head: &Content, bvars: &[Content], qualifiers: &[(String, Content)], args: &[Content]) -> (MathNode, u8) {
+    let name = match head.symbol_name() {
+        Some(name) => name,
+        None => {
+            // a function such as `f` or `inverse(f)` applied to the args
+            return (function_call(operand(head, ATOM), args), ATOM);
+        },
+    };
+
+    if let Some(&(op, precedence)) = INFIX.get(name) {
+        if args.len() == 1 && name == "minus" {
+            return (MathNode::new("mrow", vec![MathNode::mo("−"), operand(&args[0], precedence + 1)]), precedence);
+        }
+        let is_relation = precedence == 4;
+        let mut row = vec![];
+        for (i, arg) in args.iter().enumerate() {
+            if i == 0 {
+                row.push(operand(arg, if is_relation {precedence + 1} else {precedence}));
+                continue;
+            }
+            // write `a + -b` as `a - b`
+            if name == "plus" && arg.head_name() == Some("minus") {
+                if let ContentKind::Apply { args: negated, .. } = &arg.kind {
+                    if negated.len() == 1 {
+                        row.push(MathNode::mo("−"));
+                        row.push(operand(&negated[0], precedence + 1));
+                        continue;
+                    }
+                }
+            }
+            // invisible times between things like `2x`, but a visible one between numbers
+            let op = if name == "times" && matches!(arg.kind, ContentKind::Number(_)) {"×"} else {op};
+            row.push(MathNode::mo(op));
+            let is_non_associative = is_relation || NON_ASSOCIATIVE.contains(name);
+            row.push(operand(arg, if is_non_associative {precedence + 1} else {precedence}));
+        }
+        return (MathNode::mrow(row), if args.len() < 2 {ATOM} else {precedence});
+    }
+
+    if let Some(function_name) = FUNCTIONS.get(name) {
+        let function = match qualifier(qualifiers, "logbase") {
+            Some(base) => MathNode::new("msub", vec![MathNode::mi(function_name), to_presentation(base).0]),
+            None => MathNode::mi(function_name),
+        };
+        return (function_call(function, args), ATOM);
+    }
+
+    let arg = |i: usize| args.get(i).map_or_else(|| MathNode::new("mrow", vec![]), |arg| operand(arg, ATOM));
+    let fenced_with_intent = |intent_name: &str, build: &dyn Fn(Vec<MathNode>) -> MathNode, n_args: usize| {
+        let nodes = (0..n_args).map(|i| args.get(i).map_or_else(|| MathNode::new("mrow", vec![]), |arg| to_presentation(arg).0)).collect();
+        let (nodes, intent) = intent_args(intent_name, nodes);
+        return (build(nodes).attr("intent", &intent), ATOM);
+    };
+
+    return match name {
+        "divide" => (MathNode::new("mfrac", vec![to_presentation_or_empty(args, 0), to_presentation_or_empty(args, 1)]), ATOM),
+        "power" => (MathNode::new("msup", vec![arg(0), to_presentation_or_empty(args, 1)]), 9),
+        "root" => match qualifier(qualifiers, "degree") {
+            Some(degree) => (MathNode::new("mroot", vec![to_presentation_or_empty(args, 0), to_presentation(degree).0]), ATOM),
+            None => (MathNode::new("msqrt", vec![to_presentation_or_empty(args, 0)]), ATOM),
+        },
+        "exp" => (MathNode::new("msup", vec![MathNode::mi("ⅇ"), to_presentation_or_empty(args, 0)]), 9),
+        "not" => (MathNode::new("mrow", vec![MathNode::mo("¬"), arg(0)]), 8),
+        "abs" => fenced_with_intent("absolute-value", &|nodes| fenced("|", nodes, "|"), 1),
+        "floor" => fenced_with_intent("floor", &|nodes| fenced("⌊", nodes, "⌋"), 1),
+        "ceiling" => fenced_with_intent("ceiling", &|nodes| fenced("⌈", nodes, "⌉"), 1),
+        "determinant" => fenced_with_intent("determinant", &|nodes| fenced("|", nodes, "|"), 1),
+        "factorial" => {
+            let (nodes, intent) = intent_args("factorial", vec![arg(0)]);
+            (MathNode::new("mrow", vec![nodes[0].clone(), MathNode::mo("!")]).attr("intent", &intent), 9)
+        },
+        "conjugate" => {
+            let (nodes, intent) = intent_args("complex-conjugate", vec![arg(0)]);
+            (MathNode::new("mover", vec![nodes[0].clone(), MathNode::mo("¯")]).attr("accent", "true").attr("intent", &intent), ATOM)
+        },
+        "transpose" => {
+            let (nodes, intent) = intent_args("transpose", vec![arg(0)]);
+            (MathNode::new("msup", vec![nodes[0].clone(), MathNode::mi("T")]).attr("intent", &intent), 9)
+        },
+        "inverse" => {
+            let (nodes, intent) = intent_args("inverse", vec![arg(0)]);
+            (MathNode::new("msup", vec![nodes[0].clone(), MathNode::mrow(vec![MathNode::mo("−"), MathNode::mn("1")])])
+                .attr("intent", &intent), 9)
+        },
+        "binomial" => fenced_with_intent("binomial", &|nodes| fenced("(", vec![MathNode::new("mfrac", nodes).attr("linethickness", "0")], ")"), 2),
+        "interval_oo" => fenced_with_intent("open-interval", &|nodes| fenced("(", comma_separated(nodes), ")"), 2),
+        "interval_cc" => fenced_with_intent("closed-interval", &|nodes| fenced("[", comma_separated(nodes), "]"), 2),
+        "interval_oc" => fenced_with_intent("open-closed-interval", &|nodes| fenced("(", comma_separated(nodes), "]"), 2),
+        "interval_co" => fenced_with_intent("closed-open-interval", &|nodes| fenced("[", comma_separated(nodes), ")"), 2),
+        "set" if !bvars.is_empty() => {
+            // {x | condition}
+            let mut contents = comma_list(bvars);
+            if let Some(condition) = qualifier(qualifiers, "condition") {
+                contents.push(MathNode::mo("|"));
+                contents.push(to_presentation(condition).0);
+            }
+            (fenced("{", contents, "}"), ATOM)
+        },
+        "set" => (fenced("{", comma_list(args), "}"), ATOM),
+        "list" => (fenced("(", comma_list(args), ")"), ATOM),
+        "vector" => (fenced("(", vec![MathNode::new("mtable", args.iter()
+                        .map(|arg| MathNode::new("mtr", vec![MathNode::new("mtd", vec![to_presentation(arg).0])]))
+                        .collect())], ")"), ATOM),
+        "matrix" => (fenced("(", vec![MathNode::new("mtable", args.iter().map(|row| to_presentation(row).0).collect())], ")"), ATOM),
+        "matrixrow" => (MathNode::new("mtr", args.iter()
+                        .map(|cell| MathNode::new("mtd", vec![to_presentation(cell).0]))
+                        .collect()), ATOM),
+        "piecewise" => (MathNode::new("mrow", vec![
+                            MathNode::mo("{"),
+                            MathNode::new("mtable", args.iter().map(|piece| to_presentation(piece).0).collect()),
+                        ]), ATOM),
+        "piece" => (MathNode::new("mtr", vec![
+                        MathNode::new("mtd", vec![to_presentation_or_empty(args, 0)]),
+                        MathNode::new("mtd", vec![MathNode::mrow(vec![MathNode::mtext("if "), to_presentation_or_empty(args, 1)])]),
+                    ]), ATOM),
+        "otherwise" => (MathNode::new("mtr", vec![
+                        MathNode::new("mtd", vec![to_presentation_or_empty(args, 0)]),
+                        MathNode::new("mtd", vec![MathNode::mtext("otherwise")]),
+                    ]), ATOM),
+        "selector" => {
+            let indices: Vec<MathNode> = args.iter().skip(1).map(|index| to_presentation(index).0).collect();
+            (MathNode::new("msub", vec![arg(0), MathNode::mrow(comma_separated(indices))]), ATOM)
+        },
+        "sum" | "product" | "int" => {
+            let operator = MathNode::mo(match name {"sum" => "∑", "product" => "∏", _ => "∫"});
+            let (lower, upper) = limits(qualifiers, args);
+            // sums and products show the variable with the lower limit (e.g., `i=1`)
+            let lower = match (lower, bvars.first(), qualifier(qualifiers, "lowlimit")) {
+                (Some(lower), Some(bvar), Some(_)) if name != "int" =>
+                    Some( MathNode::new("mrow", vec![to_presentation(bvar).0, MathNode::mo("="), lower]) ),
+                (lower, _, _) => lower,
+            };
+            let (under, over, under_over) = if name == "int" {("msub", "msup", "msubsup")} else {("munder", "mover", "munderover")};
+            let operator = match (lower, upper) {
+                (Some(lower), Some(upper)) => MathNode::new(under_over, vec![operator, lower, upper]),
+                (Some(lower), None) => MathNode::new(under, vec![operator, lower]),
+                (None, Some(upper)) => MathNode::new(over, vec![operator, upper]),
+                (None, None) => operator,
+            };
+            let mut row = vec![operator, body(args, 7)];
+            if name == "int" {
+                for bvar in bvars {
+                    row.push(MathNode::mo("ⅆ"));
+                    row.push(to_presentation(bvar).0);
+                }
+            }
+            (MathNode::new("mrow", row), 6)
+        },
+        "limit" => {
+            let lower = match (bvars.first(), qualifier(qualifiers, "lowlimit")) {
+                (Some(bvar), Some(low)) => Some( MathNode::new("mrow", vec![to_presentation(bvar).0, MathNode::mo("→"), to_presentation(low).0]) ),
+                _ => qualifier(qualifiers, "condition").map(|condition| to_presentation(condition).0),
+            };
+            let lim = MathNode::mi("lim");
+            let lim = match lower {
+                Some(lower) => MathNode::new("munder", vec![lim, lower]),
+                None => lim,
+            };
+            (MathNode::new("mrow", vec![lim, body(args, 7)]), 6)
+        },
+        "diff" | "partialdiff" => {
+            let d = if name == "diff" {"ⅆ"} else {"∂"};
+            if bvars.is_empty() {
+                // the derivative of a function: f′
+                return (MathNode::new("msup", vec![arg(0), MathNode::mo("′")]), 9);
+            }
+            let degree = qualifier(qualifiers, "degree").map(|degree| to_presentation(degree).0);
+            let numerator = match &degree {
+                Some(degree) => MathNode::new("msup", vec![MathNode::mo(d), degree.clone()]),
+                None => MathNode::mo(d),
+            };
+            let mut denominator = vec![];
+            for bvar in bvars {
+                denominator.push(MathNode::mo(d));
+                denominator.push(match (&degree, bvars.len()) {
+                    (Some(degree), 1) => MathNode::new("msup", vec![to_presentation(bvar).0, degree.clone()]),
+                    _ => to_presentation(bvar).0,
+                });
+            }
+            let fraction = MathNode::new("mfrac", vec![numerator, MathNode::new("mrow", denominator)]);
+            (MathNode::new("mrow", vec![fraction, body(args, ATOM)]), 7)
+        },
+        "forall" | "exists" => {
+            let mut row = vec![MathNode::mo(if name == "forall" {"∀"} else {"∃"})];
+            row.append(&mut comma_list(bvars));
+            if let Some(condition) = qualifier(qualifiers, "condition") {
+                row.push(MathNode::mo(","));
+                row.push(to_presentation(condition).0);
+            }
+            row.push(MathNode::mo(":"));
+            row.push(body(args, 1));
+            (MathNode::new("mrow", row), 1)
+        },
+        "lambda" => {
+            let mut row = comma_list(bvars);
+            row.push(MathNode::mo("↦"));
+            row.push(body(args, 1));
+            (MathNode::new("mrow", row), 1)
+        },
+        _ => {
+            // something we don't know about (e.g., a csymbol from a CAS) -- keep its meaning in an intent
+            let nodes = args.iter().map(|arg| to_presentation(arg).0).collect();
+            let (nodes, intent) = intent_args(&intent_name(name), nodes);
+            let call = MathNode::new("mrow", vec![MathNode::mi(name), MathNode::mo("\u{2061}"), fenced("(", comma_separated(nodes), ")")]);
+            (call.attr("intent", &intent), ATOM)
+        },
+    };
+}
+
+fn to_presentation_or_empty(args: &[Content], i: usize) -> MathNode {
+    return args.get(i).map_or_else(|| MathNode::new("mrow", vec![]), |arg| to_presentation(arg).0);
+}
+
+fn comma_separated(nodes: Vec<MathNode>) -> Vec<MathNode> {
+    let mut result = vec![];
+    for (i, node) in nodes.into_iter().enumerate() {
+        if i > 0 {
+            result.push(MathNode::mo(","));
+        }
+        result.push(node);
+    }
+    return result;
+}
+
+/// Intent names are NCNames: replace anything else with '-'
+fn intent_name(name: &str) -> String {
+    let name: String = name.chars().map(|ch| if ch.is_alphanumeric() || ch == '-' || ch == '_' || ch == '.' {ch} else {'-'}).collect();
+    return if name.starts_with(|ch: char| ch.is_alphabetic() || ch == '_') {name} else {format!("_{}", name)};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The presentation MathML for `content_mathml` without the `<math>` wrapper
+    fn mathml(content_mathml: &str) -> String {
+        let mathml = content_mathml_to_mathml(content_mathml).unwrap();
+        return mathml.trim_start_matches("<math display=\"block\">").trim_end_matches("</math>").to_string();
+    }
+
+    /// The presentation MathML for `openmath` (without the `OMOBJ`) without the `<math>` wrapper
+    fn openmath(openmath: &str) -> String {
+        let mathml = openmath_to_mathml(&format!("<OMOBJ>{}</OMOBJ>", openmath)).unwrap();
+        return mathml.trim_start_matches("<math display=\"block\">").trim_end_matches("</math>").to_string();
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(mathml("<apply><times/><apply><plus/><ci>a</ci><ci>b</ci></apply><ci>c</ci></apply>"),
+                   "<mrow><mrow><mo>(</mo><mrow><mi>a</mi><mo>+</mo><mi>b</mi></mrow><mo>)</mo></mrow><mo>\u{2062}</mo><mi>c</mi></mrow>");
+        assert_eq!(mathml("<apply><minus/><ci>a</ci><apply><minus/><ci>b</ci><ci>c</ci></apply></apply>"),
+                   "<mrow><mi>a</mi><mo>−</mo><mrow><mo>(</mo><mrow><mi>b</mi><mo>−</mo><mi>c</mi></mrow><mo>)</mo></mrow></mrow>");
+        assert_eq!(mathml("<apply><plus/><ci>a</ci><apply><minus/><ci>b</ci></apply></apply>"),
+                   "<mrow><mi>a</mi><mo>−</mo><mi>b</mi></mrow>");
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(mathml("<cn type=\"rational\">1<sep/>2</cn>"), "<mfrac><mn>1</mn><mn>2</mn></mfrac>");
+        assert_eq!(mathml("<apply><times/><cn>2</cn><cn>3</cn></apply>"), "<mrow><mn>2</mn><mo>×</mo><mn>3</mn></mrow>");
+    }
+
+    #[test]
+    fn intents() {
+        assert_eq!(mathml("<apply><abs/><ci>x</ci></apply>"),
+                   "<mrow intent=\"absolute-value($a1)\"><mo>|</mo><mi arg=\"a1\">x</mi><mo>|</mo></mrow>");
+        assert_eq!(mathml("<interval closure=\"open-closed\"><cn>0</cn><cn>1</cn></interval>"),
+                   "<mrow intent=\"open-closed-interval($a1,$a2)\"><mo>(</mo><mn arg=\"a1\">0</mn><mo>,</mo><mn arg=\"a2\">1</mn><mo>]</mo></mrow>");
+        assert_eq!(mathml("<apply><csymbol>BesselJ</csymbol><cn>0</cn><ci>x</ci></apply>"),
+                   "<mrow intent=\"BesselJ($a1,$a2)\"><mi>BesselJ</mi><mo>\u{2061}</mo>\
+                    <mrow><mo>(</mo><mn arg=\"a1\">0</mn><mo>,</mo><mi arg=\"a2\">x</mi><mo>)</mo></mrow></mrow>");
+        // intents in the input are kept
+        assert_eq!(mathml("<ci intent=\"velocity\">v</ci>"), "<mi intent=\"velocity\">v</mi>");
+    }
+
+    #[test]
+    fn sums_and_functions() {
+        assert_eq!(mathml("<apply><sum/><bvar><ci>i</ci></bvar><lowlimit><cn>1</cn></lowlimit><uplimit><ci>n</ci></uplimit>\
+                           <ci>i</ci></apply>"),
+                   "<mrow><munderover><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover><mi>i</mi></mrow>");
+        assert_eq!(mathml("<apply><sin/><ci>x</ci></apply>"), "<mrow><mi>sin</mi><mo>\u{2061}</mo><mi>x</mi></mrow>");
+        assert_eq!(mathml("<apply><log/><logbase><cn>2</cn></logbase><apply><plus/><ci>x</ci><cn>1</cn></apply></apply>"),
+                   "<mrow><msub><mi>log</mi><mn>2</mn></msub><mo>\u{2061}</mo>\
+                    <mrow><mo>(</mo><mrow><mi>x</mi><mo>+</mo><mn>1</mn></mrow><mo>)</mo></mrow></mrow>");
+    }
+
+    #[test]
+    fn semantics() {
+        assert_eq!(mathml("<math><semantics><mi>x</mi><annotation-xml encoding=\"MathML-Content\"><ci>y</ci></annotation-xml>\
+                           </semantics></math>"),
+                   "<mi>y</mi>");
+    }
+
+    #[test]
+    fn openmath_symbols() {
+        assert_eq!(openmath("<OMA><OMS cd=\"arith1\" name=\"plus\"/><OMI>1</OMI><OMV name=\"x\"/></OMA>"),
+                   "<mrow><mn>1</mn><mo>+</mo><mi>x</mi></mrow>");
+        assert_eq!(openmath("<OMA><OMS cd=\"arith1\" name=\"root\"/><OMV name=\"x\"/><OMI>3</OMI></OMA>"),
+                   "<mroot><mi>x</mi><mn>3</mn></mroot>");
+        assert_eq!(openmath("<OMS cd=\"nums1\" name=\"pi\"/>"), "<mi>π</mi>");
+    }
+
+    #[test]
+    fn openmath_binding() {
+        assert_eq!(openmath("<OMA><OMS cd=\"calculus1\" name=\"defint\"/>\
+                                <OMA><OMS cd=\"interval1\" name=\"interval\"/><OMI>0</OMI><OMI>1</OMI></OMA>\
+                                <OMBIND><OMS cd=\"fns1\" name=\"lambda\"/><OMBVAR><OMV name=\"x\"/></OMBVAR><OMV name=\"x\"/></OMBIND>\
+                             </OMA>"),
+                   "<mrow><msubsup><mo>∫</mo><mn>0</mn><mn>1</mn></msubsup><mi>x</mi><mo>ⅆ</mo><mi>x</mi></mrow>");
+    }
+
+    #[test]
+    fn errors() {
+        let message = |result: Result<String>| result.unwrap_err().to_string();
+        assert_eq!(message(content_mathml_to_mathml("<apply><plus/><mi>x</mi></apply>")),
+                   "Presentation MathML (<mi>) can't be mixed with Content MathML");
+        assert_eq!(message(openmath_to_mathml("<OMOBJ><OME><OMS cd=\"moreerrors\" name=\"x\"/></OME></OMOBJ>")),
+                   "The OpenMath contains an error (<OME>)");
+        let e = content_mathml_to_mathml("<apply><plus/>").unwrap_err();
+        assert!(e.downcast_ref::<ParseError>().expect("a ParseError").message.starts_with("The Content MathML isn't valid XML"));
+    }
+}
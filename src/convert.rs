@@ -0,0 +1,153 @@
+//! Figure out what format the input is in and convert it to MathML.
+use anyhow::{Result, Context};
+use std::fmt;
+use crate::tex::tex_to_mathml;
+use crate::asciimath::asciimath_to_mathml;
+use crate::unicode_math::unicode_math_to_mathml;
+use crate::omml::omml_to_mathml;
+use crate::content_mathml::{content_mathml_to_mathml, openmath_to_mathml};
+use crate::detect::detect;
+use crate::errors::Stage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathFormat {
+    TeX,
+    ASCIIMath,
+    MathML,
+    /// "Plain" Unicode math such as `x² + √y`
+    Unicode,
+    /// Office Math Markup Language (used by Word and PowerPoint)
+    OfficeMath,
+    /// Content MathML (e.g., `<apply><plus/>...</apply>`) -- converted to presentation MathML
+    ContentMathML,
+    /// OpenMath (XML encoding)
+    OpenMath,
+}
+
+impl MathFormat {
+    /// The name used by MathJax for the format
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            MathFormat::TeX => "TeX",
+            MathFormat::ASCIIMath => "ASCIIMath",
+            MathFormat::MathML => "MathML",
+            MathFormat::Unicode => "Unicode",
+            MathFormat::OfficeMath => "OMML",
+            MathFormat::ContentMathML => "Content MathML",
+            MathFormat::OpenMath => "OpenMath",
+        };
+    }
+}
+
+/// An error in the input, along with where (as char offsets into the string being parsed) the error was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl ParseError {
+    pub fn new(message: &str, start: usize, end: usize) -> ParseError {
+        return ParseError { message: message.to_string(), start, end };
+    }
+
+    /// An error for XML that couldn't be parsed: `message` is followed by what `roxmltree` reported.
+    /// `skip` is the number of chars at the start of `xml` that aren't part of the input (e.g., a wrapper element).
+    pub fn from_xml_error(message: &str, e: &roxmltree::Error, xml: &str, skip: usize) -> ParseError {
+        // the row and column at the end of the message are in `xml`, not in the input (the `ParseError` has the position)
+        let description = e.to_string();
+        let description = description.rsplit_once(" at ").map_or(description.as_str(), |(description, _)| description);
+        let (row, col) = (e.pos().row as usize, e.pos().col as usize);
+        let preceding_lines: usize = xml.split('\n').take(row.saturating_sub(1)).map(|line| line.chars().count() + 1).sum();
+        let position = (preceding_lines + col.saturating_sub(1)).saturating_sub(skip);
+        return ParseError::new(&format!("{}: {}", message, description), position, position + 1);
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{} (at character {})", self.message, self.start + 1);
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Figure out the format of `math_str` and return it along with the part of the string that should be converted.
+/// Delimiters override auto-detection: `$...$` for TeX and `` `...` `` for ASCIIMath.
+/// See `detect::detect` for details (including an explanation of why the format was chosen).
+pub fn detect_format(math_str: &str) -> (MathFormat, &str) {
+    let detection = detect(math_str);
+    return (detection.format, detection.math);
+}
+
+/// Convert `math_str` (which is in `math_format`) to MathML.
+/// TeX, ASCIIMath, Office Math, Content MathML, OpenMath, and Unicode math are converted in Rust. If TeX conversion fails and the "mathjax" feature is on,
+/// MathJax is tried when running in a browser.
+pub fn string_to_mathml(math_str: &str, math_format: MathFormat) -> Result<String> {
+    match math_format {
+        MathFormat::MathML => return Ok( math_str.to_string() ),
+        MathFormat::TeX => {
+            let result = tex_to_mathml(math_str);
+            #[cfg(all(target_arch = "wasm32", feature = "mathjax"))]
+            if let Err(e) = &result {
+                warn!("TeX conversion failed ({}) -- trying MathJax", e);
+                let mathml = mathjax::convert_to_mathml(math_str, math_format.as_str());
+                // MathJax reports its errors by returning an 'merror' -- the Rust converter's error is more useful
+                if !mathml.contains("<merror") {
+                    return Ok(mathml);
+                }
+            }
+            return result;
+        },
+        MathFormat::ASCIIMath => return asciimath_to_mathml(math_str),
+        MathFormat::Unicode => return unicode_math_to_mathml(math_str),
+        MathFormat::OfficeMath => return omml_to_mathml(math_str),
+        MathFormat::ContentMathML => return content_mathml_to_mathml(math_str),
+        MathFormat::OpenMath => return openmath_to_mathml(math_str),
+    }
+}
+
+/// Convert the user's input string to MathML (not canonicalized), auto-detecting the format.
+/// The resulting MathML is always marked as display (block) math.
+/// The position in any `ParseError` is relative to `input` (not to the part inside the delimiters).
+/// Errors have the `Stage` that failed attached as context.
+pub fn input_to_mathml(input: &str) -> Result<String> {
+    return input_to_mathml_as(input, None);
+}
+
+/// Like `input_to_mathml`, but if `math_format` is given, it is used instead of the detected format.
+pub fn input_to_mathml_as(input: &str, math_format: Option<MathFormat>) -> Result<String> {
+    let (detected_format, math_str) = detect_format(input);
+    if math_str.trim().is_empty() {
+        return Err( anyhow::anyhow!("The input is empty").context(Stage::Detection) );
+    }
+    let math_format = math_format.unwrap_or(detected_format);
+    debug!("{}: '{}'", math_format.as_str(), math_str);
+    let mut math = match string_to_mathml(math_str, math_format) {
+        Ok(math) => math,
+        Err(mut e) => {
+            if let Some(parse_error) = e.downcast_mut::<ParseError>() {
+                let offset = input[..math_str.as_ptr() as usize - input.as_ptr() as usize].chars().count();
+                parse_error.start += offset;
+                parse_error.end += offset;
+            }
+            return Err(e).context(Stage::Conversion);
+        },
+    };
+    if !math.contains("display=\"block\"") && !math.contains("display='block'") {
+        math = math.replace("<math ", "<math display='block' ").replace("<math>", "<math display='block'>");
+    }
+    return Ok(math);
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "mathjax"))]
+mod mathjax {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_name = "ConvertToMathML")]
+        pub fn convert_to_mathml(mathml: &str, math_format: &str) -> String;
+    }
+}
@@ -0,0 +1,260 @@
+//! Figure out what format some (undelimited) math is in.
+//!
+//! Each format is given a score based on the evidence for it found in the input (e.g., `\alpha` is evidence for TeX,
+//! `sqrt(x)` for ASCIIMath, and `x²` for Unicode math). The format with the highest score wins.
+//! ASCIIMath wins ties because it accepts the most input.
+use regex::Regex;
+use phf::{phf_map, phf_set};
+use crate::convert::MathFormat;
+use crate::asciimath::is_asciimath_symbol;
+use crate::unicode_math::is_unicode_math_notation;
+
+/// The result of detecting the format of some input
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatDetection<'a> {
+    pub format: MathFormat,
+    /// The part of the input that should be converted (i.e., without any delimiters)
+    pub math: &'a str,
+    /// The score for each format (empty if the format was given by delimiters)
+    pub scores: Vec<(MathFormat, i32)>,
+    /// A sentence explaining why `format` was chosen
+    pub explanation: String,
+}
+
+/// The formats that auto-detection chooses between, in tie-breaking order
+pub static DETECTED_FORMATS: &[MathFormat] = &[
+    MathFormat::ASCIIMath, MathFormat::TeX, MathFormat::MathML, MathFormat::Unicode, MathFormat::OfficeMath,
+    MathFormat::ContentMathML, MathFormat::OpenMath,
+];
+
+/// Delimiters that determine the format: (open, close, format).
+/// An opening delimiter comes before any other opening delimiter that is a prefix of it.
+pub static DELIMITERS: &[(&str, &str, MathFormat)] = &[
+    ("$$", "$$", MathFormat::TeX),
+    ("\\(", "\\)", MathFormat::TeX),
+    ("\\[", "\\]", MathFormat::TeX),
+    ("$", "$", MathFormat::TeX),
+    ("`", "`", MathFormat::ASCIIMath),
+];
+
+/// The languages for Markdown code fences (e.g., ```` ```math ````) that hold math
+pub static FENCE_LANGUAGES: phf::Map<&'static str, MathFormat> = phf_map! {
+    "math" => MathFormat::TeX,
+    "latex" => MathFormat::TeX,
+    "tex" => MathFormat::TeX,
+    "asciimath" => MathFormat::ASCIIMath,
+    "mathml" => MathFormat::MathML,
+};
+
+/// LaTeX environments that are (display) math on their own -- the whole environment is given to the TeX converter
+pub static DISPLAY_ENVIRONMENTS: phf::Set<&'static str> = phf_set! {
+    "equation", "equation*", "displaymath", "align", "align*", "gather", "gather*",
+    "multline", "multline*", "flalign", "flalign*", "alignat", "alignat*", "eqnarray", "eqnarray*",
+};
+
+/// The evidence for one format
+struct Evidence {
+    format: MathFormat,
+    score: i32,
+    /// What was found (e.g., "\alpha") -- only the first few are kept
+    found: Vec<String>,
+}
+
+impl Evidence {
+    fn add(&mut self, score: i32, found: &str) {
+        self.score += score;
+        if score > 0 && self.found.len() < 3 && !self.found.iter().any(|f| f == found) {
+            self.found.push(found.to_string());
+        }
+    }
+}
+
+/// Figure out the format of `math_str` and return it along with the part of the string that should be converted.
+/// Delimiters (see `DELIMITERS`, `FENCE_LANGUAGES`, and `DISPLAY_ENVIRONMENTS`) override auto-detection.
+pub fn detect(math_str: &str) -> FormatDetection<'_> {
+    let trimmed = math_str.trim();
+    if let Some( (format, math, delimiters) ) = strip_delimiters(trimmed) {
+        return FormatDetection {
+            format,
+            math,
+            scores: vec![],
+            explanation: format!("{} because it is delimited by {}.", format.as_str(), delimiters),
+        };
+    }
+
+    let evidence = score(trimmed);
+    let best = evidence.iter()
+        .fold(&evidence[0], |best, e| if e.score > best.score {e} else {best});
+    let explanation = if best.found.is_empty() {
+        format!("Assumed {} because nothing specific to any format was found.", best.format.as_str())
+    } else {
+        format!("Auto-detected {} because the input contains {}.", best.format.as_str(), best.found.join(", "))
+    };
+    return FormatDetection {
+        format: best.format,
+        math: trimmed,
+        scores: evidence.iter().map(|e| (e.format, e.score)).collect(),
+        explanation,
+    };
+}
+
+/// If `math_str` is delimited, return the format, the math inside the delimiters, and a description of the delimiters
+fn strip_delimiters(math_str: &str) -> Option<(MathFormat, &str, String)> {
+    if let Some(rest) = math_str.strip_prefix("```") {
+        let (language, body) = rest.split_once('\n')?;
+        let format = *FENCE_LANGUAGES.get(language.trim())?;
+        let body = body.strip_suffix("```")?;
+        return Some( (format, body.trim(), format!("```{}", language.trim())) );
+    }
+
+    if let Some(rest) = math_str.strip_prefix("\\begin{") {
+        let name = &rest[..rest.find('}')?];
+        if DISPLAY_ENVIRONMENTS.contains(name) && math_str.ends_with(&format!("\\end{{{}}}", name)) {
+            return Some( (MathFormat::TeX, math_str, format!("\\begin{{{name}}}...\\end{{{name}}}", name=name)) );
+        }
+        return None;
+    }
+
+    for &(open, close, format) in DELIMITERS {
+        if math_str.len() > open.len() + close.len() && math_str.starts_with(open) && math_str.ends_with(close) {
+            let math = &math_str[open.len()..math_str.len() - close.len()];
+            return Some( (format, math, format!("{}...{}", open, close)) );
+        }
+    }
+    return None;
+}
+
+/// Score `math_str` against each of the formats (in the order of `DETECTED_FORMATS`)
+fn score(math_str: &str) -> Vec<Evidence> {
+    lazy_static! {
+        static ref TEX_COMMAND: Regex = Regex::new(r"\\([a-zA-Z]+|[{}|,;!])").unwrap();
+        static ref TEX_SCRIPT: Regex = Regex::new(r"[_^]\{").unwrap();
+        static ref TEX_ARGS: Regex = Regex::new(r"\}\{").unwrap();
+        static ref ASCIIMATH_SCRIPT: Regex = Regex::new(r"[_^]\(").unwrap();
+        static ref ASCIIMATH_FRACTION: Regex = Regex::new(r"\)/\(").unwrap();
+        static ref ASCIIMATH_WORD: Regex = Regex::new(r"[a-zA-Z]{2,}|[-+*/<>=!~:|@^_.]{2,}").unwrap();
+        static ref MATHML_TAG: Regex = Regex::new(r"^<(m:)?(math|mrow|mi|mn|mo|mfrac|msqrt|mroot|msub|msup|msubsup|mtable|mtext)[\s>/]").unwrap();
+        static ref OMML_TAG: Regex = Regex::new(r"^<m:(oMathPara|oMath)[\s>]").unwrap();
+        static ref OPENMATH_TAG: Regex = Regex::new(r"^<(OMOBJ|OMA|OMBIND)[\s>]").unwrap();
+        static ref CONTENT_TAG: Regex = Regex::new(r"<(apply|bind|ci|cn|csymbol)[\s>]").unwrap();
+        static ref PRESENTATION_TOKEN: Regex = Regex::new(r"<(m:)?(mi|mn|mo)[\s>]").unwrap();
+    };
+
+    let mut evidence: Vec<Evidence> = DETECTED_FORMATS.iter()
+        .map(|&format| Evidence { format, score: 0, found: vec![] })
+        .collect();
+    let [ascii_math, tex, mathml, unicode, office_math, content_mathml, openmath] = &mut evidence[..] else { unreachable!() };
+
+    if math_str.starts_with('<') && math_str.ends_with('>') {
+        if let Some(caps) = OMML_TAG.captures(math_str) {
+            office_math.add(100, &format!("an <m:{}> tag", caps.get(1).unwrap().as_str()));
+        } else if let Some(caps) = OPENMATH_TAG.captures(math_str) {
+            openmath.add(100, &format!("an <{}> tag", caps.get(1).unwrap().as_str()));
+        } else {
+            // Content MathML beats presentation MathML unless both are present (e.g., in <semantics>)
+            if let Some(caps) = CONTENT_TAG.captures(math_str) {
+                let score = if PRESENTATION_TOKEN.is_match(math_str) {50} else {150};
+                content_mathml.add(score, &format!("a Content MathML <{}> tag", caps.get(1).unwrap().as_str()));
+            }
+            match MATHML_TAG.captures(math_str) {
+                Some(caps) => mathml.add(100, &format!("a <{}> tag", caps.get(2).unwrap().as_str())),
+                None => mathml.add(10, "what looks like a tag"),
+            }
+        }
+    }
+
+    for caps in TEX_COMMAND.captures_iter(math_str) {
+        let command = caps.get(0).unwrap().as_str();
+        tex.add(3, &format!("the TeX command {}", command));
+        ascii_math.add(-2, command);     // ASCIIMath has no '\' commands
+    }
+    for m in TEX_SCRIPT.find_iter(math_str) {
+        tex.add(2, &format!("'{}' (a TeX script)", m.as_str()));
+    }
+    for _ in TEX_ARGS.find_iter(math_str) {
+        tex.add(2, "'}{' (TeX arguments)");
+    }
+
+    for m in ASCIIMATH_SCRIPT.find_iter(math_str) {
+        ascii_math.add(1, &format!("'{}' (an ASCIIMath script)", m.as_str()));
+    }
+    for _ in ASCIIMATH_FRACTION.find_iter(math_str) {
+        ascii_math.add(2, "')/(' (an ASCIIMath fraction)");
+    }
+    for m in ASCIIMATH_WORD.find_iter(math_str) {
+        let word = m.as_str();
+        let preceded_by_backslash = m.start() > 0 && math_str[..m.start()].ends_with('\\');
+        if !preceded_by_backslash && is_asciimath_symbol(word) {
+            ascii_math.add(2, &format!("the ASCIIMath symbol '{}'", word));
+        }
+    }
+    if math_str.contains('"') {
+        ascii_math.add(1, "quoted text");
+    }
+
+    for ch in math_str.chars() {
+        if is_unicode_math_notation(ch) {
+            unicode.add(3, &format!("'{}'", ch));
+        } else if !ch.is_ascii() && !ch.is_whitespace() {
+            unicode.add(1, &format!("'{}'", ch));
+        }
+    }
+    return evidence;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(detection: &FormatDetection, format: MathFormat) -> i32 {
+        return detection.scores.iter().find(|(f, _)| *f == format).unwrap().1;
+    }
+
+    #[test]
+    fn tex() {
+        let detection = detect(r"\frac{1}{2}");
+        assert_eq!(detection.format, MathFormat::TeX);
+        assert!(score(&detection, MathFormat::TeX) > score(&detection, MathFormat::ASCIIMath));
+        assert!(detection.explanation.contains(r"\frac"));
+    }
+
+    #[test]
+    fn asciimath() {
+        let detection = detect("sqrt(x)/2");
+        assert_eq!(detection.format, MathFormat::ASCIIMath);
+        assert!(detection.explanation.contains("sqrt"));
+    }
+
+    #[test]
+    fn unicode() {
+        assert_eq!(detect("x²+y²").format, MathFormat::Unicode);
+        assert_eq!(detect("½ + √2").format, MathFormat::Unicode);
+    }
+
+    #[test]
+    fn xml_formats() {
+        assert_eq!(detect("<math><mi>x</mi></math>").format, MathFormat::MathML);
+        assert_eq!(detect("<m:oMath><m:r><m:t>x</m:t></m:r></m:oMath>").format, MathFormat::OfficeMath);
+        assert_eq!(detect("<OMOBJ><OMV name='x'/></OMOBJ>").format, MathFormat::OpenMath);
+        // Content MathML is also (weak) evidence for MathML
+        let detection = detect("<apply><plus/><ci>x</ci><cn>1</cn></apply>");
+        assert_eq!(detection.format, MathFormat::ContentMathML);
+        assert!(score(&detection, MathFormat::MathML) > 0);
+    }
+
+    #[test]
+    fn strongest_evidence_wins() {
+        // 'sqrt' is ASCIIMath, but a TeX command is stronger evidence
+        assert_eq!(detect(r"\alpha + sqrt(x)").format, MathFormat::TeX);
+    }
+
+    #[test]
+    fn ties_go_to_asciimath() {
+        for input in ["x", "x+1", "a/b"] {
+            let detection = detect(input);
+            assert!(detection.scores.iter().all(|&(_, score)| score == 0), "{}", input);
+            assert_eq!(detection.format, MathFormat::ASCIIMath);
+            assert!(detection.explanation.starts_with("Assumed ASCIIMath"));
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Split the user's input into pieces of math and the text in between them.
+//! Math is delimited by `<math>...</math>`, Office Math's `<m:oMath>...</m:oMath>`, OpenMath's `<OMOBJ>...</OMOBJ>`, or one of the delimiters in `detect` (e.g., `$...$`, `\(...\)`,
+//! `` `...` ``, a `\begin{equation}...\end{equation}` environment, or a ```` ```math ```` code fence).
+use anyhow::Result;
+use crate::convert::{MathFormat, ParseError, input_to_mathml_as};
+use crate::detect::{DELIMITERS, FENCE_LANGUAGES, DISPLAY_ENVIRONMENTS};
+
+/// A delimited piece of math in the input
+#[derive(Debug, Clone, PartialEq)]
+pub struct MathSource {
+    /// The math, including the delimiters (these determine the format)
+    pub text: String,
+    /// The char offset of `text` in the input
+    pub start: usize,
+}
+
+impl MathSource {
+    /// Convert the math to (uncanonicalized) MathML.
+    /// The position in any `ParseError` is relative to the entire input, not to `text`.
+    pub fn to_mathml(&self) -> Result<String> {
+        return self.to_mathml_as(None);
+    }
+
+    /// Like `to_mathml`, but if `math_format` is given, it is used instead of the detected format.
+    pub fn to_mathml_as(&self, math_format: Option<MathFormat>) -> Result<String> {
+        return input_to_mathml_as(&self.text, math_format).map_err(|mut e| {
+            if let Some(parse_error) = e.downcast_mut::<ParseError>() {
+                parse_error.start += self.start;
+                parse_error.end += self.start;
+            }
+            e
+        });
+    }
+}
+
+/// Part of the input
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Math(MathSource),
+}
+
+/// Split `input` into text and math segments. Empty text segments are not included.
+/// An unterminated delimiter is treated as text. A `\$` is a literal `$`.
+pub fn split_input(input: &str) -> Vec<Segment> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut segments = vec![];
+    let mut text = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' && chars.get(i+1) == Some(&'$') {
+            text.push('$');
+            i += 2;
+            continue;
+        }
+        match find_math_end(&chars, i) {
+            None => {
+                // skip over (non-math) code fences so that backticks in them don't look like ASCIIMath delimiters
+                let end = if starts_with(&chars, i, "```") {
+                    find(&chars, i + 3, "```").map_or(chars.len(), |end| end + 3)
+                } else {
+                    i + 1
+                };
+                text.extend(&chars[i..end]);
+                i = end;
+            },
+            Some(end) => {
+                if !text.is_empty() {
+                    segments.push( Segment::Text(std::mem::take(&mut text)) );
+                }
+                segments.push( Segment::Math( MathSource { text: chars[i..end].iter().collect(), start: i } ) );
+                i = end;
+            },
+        }
+    }
+    if !text.is_empty() {
+        segments.push( Segment::Text(text) );
+    }
+    return segments;
+}
+
+/// All the math in `input`. If there isn't any delimited math, the whole input (trimmed) is treated as math (if not empty).
+pub fn math_expressions(input: &str) -> Vec<MathSource> {
+    let math: Vec<MathSource> = split_input(input).into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Math(math) => Some(math),
+            Segment::Text(_) => None,
+        })
+        .collect();
+    if !math.is_empty() {
+        return math;
+    }
+
+    let trimmed = input.trim_start();
+    if trimmed.trim_end().is_empty() {
+        return vec![];
+    }
+    let start = input.chars().count() - trimmed.chars().count();
+    return vec![ MathSource { text: trimmed.trim_end().to_string(), start } ];
+}
+
+/// If a delimited piece of math starts at `start`, return the (exclusive) end of it
+fn find_math_end(chars: &[char], start: usize) -> Option<usize> {
+    if starts_with(chars, start, "```") {
+        // a code fence -- the language is the rest of the line
+        let line_end = (start..chars.len()).find(|&i| chars[i] == '\n')?;
+        let language: String = chars[start+3..line_end].iter().collect();
+        if !FENCE_LANGUAGES.contains_key(language.trim()) {
+            return None;
+        }
+        return find(chars, line_end, "```").map(|i| i + 3);
+    }
+
+    if starts_with(chars, start, "\\begin{") {
+        let name_start = start + "\\begin{".len();
+        let name_end = find(chars, name_start, "}")?;
+        let name: String = chars[name_start..name_end].iter().collect();
+        if !DISPLAY_ENVIRONMENTS.contains(name.as_str()) {
+            return None;
+        }
+        let end = format!("\\end{{{}}}", name);
+        return find(chars, name_end, &end).map(|i| i + end.chars().count());
+    }
+
+    for element in ["math", "m:oMathPara", "m:oMath", "OMOBJ"].iter() {
+        let start_tag = format!("<{}", element);
+        if starts_with(chars, start, &start_tag) {
+            let after_name = chars.get(start + start_tag.len());
+            if !after_name.is_some_and(|&ch| ch == '>' || ch.is_whitespace()) {
+                continue;       // e.g., "<m:oMathPara" when looking for "<m:oMath"
+            }
+            let end_tag = format!("</{}>", element);
+            return find(chars, start, &end_tag).map(|i| i + end_tag.len());
+        }
+    }
+
+    for &(open, close, _) in DELIMITERS {
+        if starts_with(chars, start, open) {
+            let math_start = start + open.chars().count();
+            match find(chars, math_start, close) {
+                Some(i) if i > math_start => return Some(i + close.chars().count()),
+                _ => (),    // maybe a shorter delimiter matches (e.g., '$' instead of '$$')
+            }
+        }
+    }
+    return None;
+}
+
+/// The position of the first `target` at or after `start`
+fn find(chars: &[char], start: usize, target: &str) -> Option<usize> {
+    return (start..chars.len()).find(|&i| starts_with(chars, i, target));
+}
+
+fn starts_with(chars: &[char], start: usize, prefix: &str) -> bool {
+    return prefix.chars().enumerate().all(|(i, ch)| chars.get(start + i) == Some(&ch));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn math(text: &str, start: usize) -> Segment {
+        return Segment::Math( MathSource { text: text.to_string(), start } );
+    }
+
+    fn text(text: &str) -> Segment {
+        return Segment::Text(text.to_string());
+    }
+
+    /// The text of each math expression in `input`
+    fn expressions(input: &str) -> Vec<String> {
+        return math_expressions(input).into_iter().map(|math| math.text).collect();
+    }
+
+    #[test]
+    fn text_and_math() {
+        assert_eq!(split_input("Solve $x^2=4$ for `x`."),
+                   vec![text("Solve "), math("$x^2=4$", 6), text(" for "), math("`x`", 18), text(".")]);
+    }
+
+    #[test]
+    fn unterminated_delimiter_is_text() {
+        assert_eq!(split_input("costs $5"), vec![text("costs $5")]);
+    }
+
+    #[test]
+    fn several_expressions() {
+        assert_eq!(expressions("$a$ and $b$"), vec!["$a$", "$b$"]);
+    }
+
+    #[test]
+    fn undelimited_input_is_one_expression() {
+        let math = math_expressions("  x^2 + 1 \n");
+        assert_eq!(math, vec![ MathSource { text: "x^2 + 1".to_string(), start: 2 } ]);
+        assert!(math_expressions(" \n ").is_empty());
+    }
+
+    #[test]
+    fn escaped_dollar() {
+        assert_eq!(split_input(r"costs \$5 or $x$"), vec![text("costs $5 or "), math("$x$", 13)]);
+    }
+
+    #[test]
+    fn tex_delimiters() {
+        assert_eq!(expressions(r"$$a$$ \(b\) \[c\] $d$"), vec!["$$a$$", r"\(b\)", r"\[c\]", "$d$"]);
+    }
+
+    #[test]
+    fn environments() {
+        assert_eq!(split_input("Given\n\\begin{align} a &= b \\\\ c &= d \\end{align}\nso"),
+                   vec![text("Given\n"), math("\\begin{align} a &= b \\\\ c &= d \\end{align}", 6), text("\nso")]);
+        // environments that aren't math on their own aren't delimiters
+        assert_eq!(split_input(r"\begin{itemize}x\end{itemize}"), vec![text(r"\begin{itemize}x\end{itemize}")]);
+    }
+
+    #[test]
+    fn code_fences() {
+        assert_eq!(split_input("a\n```math\nx^2\n```\nb"), vec![text("a\n"), math("```math\nx^2\n```", 2), text("\nb")]);
+        // backticks in other code aren't ASCIIMath delimiters
+        assert_eq!(split_input("```rust\nlet s = `x`;\n```"), vec![text("```rust\nlet s = `x`;\n```")]);
+    }
+
+    #[test]
+    fn xml_elements() {
+        assert_eq!(expressions("See <math><mi>x</mi></math> and <m:oMathPara><m:oMath/></m:oMathPara>"),
+                   vec!["<math><mi>x</mi></math>", "<m:oMathPara><m:oMath/></m:oMathPara>"]);
+    }
+
+    #[test]
+    fn positions_are_in_chars() {
+        assert_eq!(split_input("π ≈ $3.14$"), vec![text("π ≈ "), math("$3.14$", 4)]);
+    }
+}
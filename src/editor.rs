@@ -0,0 +1,305 @@
+//! Structural editing of (canonicalized) MathML.
+//!
+//! The node being navigated (found by its id) can be replaced, deleted, wrapped (e.g., in a fraction),
+//! or have something inserted next to it. What is inserted can be written in any of the input formats.
+//! New nodes are given ids (`edit-1`, ...) so that navigation can move to them after MathCAT canonicalizes
+//! the result (MathCAT keeps the ids it is given).
+use anyhow::{Result, bail};
+use crate::convert::input_to_mathml;
+use crate::mathml::{MathNode, parse_mathml};
+
+/// What goes in an empty slot (e.g., the denominator of a new fraction) until it is replaced
+pub const PLACEHOLDER: &str = "□";
+
+/// Elements whose children are a list (an inferred mrow): children can be added and removed
+static LIST_ELEMENTS: &[&str] = &["math", "mrow", "msqrt", "mstyle", "merror", "mpadded", "mphantom", "menclose", "mtd"];
+
+/// Elements whose children are rows or cells (only rows and cells can go in them)
+static TABLE_ELEMENTS: &[&str] = &["mtable", "mtr", "mlabeledtr"];
+
+/// A template that the node is put into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wrapper {
+    /// The node is the numerator
+    Fraction,
+    SquareRoot,
+    /// The node is the radicand
+    Root,
+    /// The node is the base
+    Superscript,
+    /// The node is the base
+    Subscript,
+    Parentheses,
+    AbsoluteValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    /// Replace the node with the (converted) input
+    Replace(String),
+    InsertBefore(String),
+    InsertAfter(String),
+    /// Delete the node (if it fills a slot such as a numerator, it is replaced by a placeholder)
+    Delete,
+    Wrap(Wrapper),
+}
+
+/// The result of an edit
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditResult {
+    pub mathml: String,
+    /// Where navigation should go: the new node (or the first empty slot in it) or, after a delete, a neighbor
+    pub nav_id: String,
+}
+
+/// Make `edit` to the node with id `id` in `mathml`. An empty `id` means the whole expression.
+pub fn edit_mathml(mathml: &str, id: &str, edit: &Edit) -> Result<EditResult> {
+    let mut math = parse_mathml(mathml)?;
+    let mut ids = IdMaker::new(&math);
+    let path = if id.is_empty() || math.get_attr("id") == Some(id) {
+        vec![]
+    } else {
+        match find_path(&math, id) {
+            Some(path) => path,
+            None => bail!("Couldn't find the node with id '{}'", id),
+        }
+    };
+
+    let nav_id = match edit {
+        Edit::Replace(input) => {
+            let mut node = input_to_node(input)?;
+            let nav_id = ids.set_id(&mut node);
+            if path.is_empty() {
+                math.children = vec![node];
+            } else {
+                *node_at(&mut math, &path) = node;
+            }
+            nav_id
+        },
+        Edit::InsertBefore(input) | Edit::InsertAfter(input) => {
+            let mut node = input_to_node(input)?;
+            let nav_id = ids.set_id(&mut node);
+            insert(&mut math, &path, node, matches!(edit, Edit::InsertAfter(_)))?;
+            nav_id
+        },
+        Edit::Delete => delete(&mut math, &path, &mut ids)?,
+        Edit::Wrap(wrapper) => {
+            if path.is_empty() {
+                // wrap everything -- the math element itself stays where it is
+                let children = std::mem::take(&mut math.children);
+                math.children = vec![MathNode::mrow(children)];
+                wrap(&mut math.children[0], *wrapper, &mut ids)
+            } else {
+                wrap(node_at(&mut math, &path), *wrapper, &mut ids)
+            }
+        },
+    };
+    return Ok( EditResult { mathml: math.to_mathml(), nav_id } );
+}
+
+/// Makes ids for new nodes that don't clash with the ones in use
+struct IdMaker {
+    next: usize,
+}
+
+impl IdMaker {
+    fn new(math: &MathNode) -> IdMaker {
+        fn max_edit_id(node: &MathNode) -> usize {
+            let n = node.get_attr("id")
+                .and_then(|id| id.strip_prefix("edit-"))
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0);
+            return node.children.iter().map(max_edit_id).fold(n, usize::max);
+        }
+        return IdMaker { next: max_edit_id(math) + 1 };
+    }
+
+    fn make_id(&mut self) -> String {
+        self.next += 1;
+        return format!("edit-{}", self.next - 1);
+    }
+
+    /// Give `node` a new id (replacing any it has) and return it
+    fn set_id(&mut self, node: &mut MathNode) -> String {
+        let id = self.make_id();
+        node.set_attr("id", &id);
+        return id;
+    }
+
+    fn placeholder(&mut self) -> MathNode {
+        return MathNode::mi(PLACEHOLDER).attr("id", &self.make_id());
+    }
+}
+
+/// Convert `input` (in any input format) to a node -- an mrow if it is more than one node
+fn input_to_node(input: &str) -> Result<MathNode> {
+    let input = input.trim();
+    if input.is_empty() {
+        bail!("Nothing was entered");
+    }
+    let math = parse_mathml(&input_to_mathml(input)?)?;
+    if math.children.is_empty() {
+        bail!("'{}' doesn't have any math in it", input);
+    }
+    return Ok( MathNode::mrow(math.children) );
+}
+
+/// The indices of the children to follow from `node` to get to the node with id `id`
+fn find_path(node: &MathNode, id: &str) -> Option<Vec<usize>> {
+    for (i, child) in node.children.iter().enumerate() {
+        if child.get_attr("id") == Some(id) {
+            return Some( vec![i] );
+        }
+        if let Some(mut path) = find_path(child, id) {
+            path.insert(0, i);
+            return Some(path);
+        }
+    }
+    return None;
+}
+
+fn node_at<'a>(node: &'a mut MathNode, path: &[usize]) -> &'a mut MathNode {
+    return path.iter().fold(node, |node, &i| &mut node.children[i]);
+}
+
+/// Insert `new_node` before or after the node at `path`
+fn insert(math: &mut MathNode, path: &[usize], new_node: MathNode, after: bool) -> Result<()> {
+    let (parent, i) = match path.split_last() {
+        None => {
+            // next to everything
+            if after {
+                math.children.push(new_node);
+            } else {
+                math.children.insert(0, new_node);
+            }
+            return Ok( () );
+        },
+        Some( (&i, parent_path) ) => (node_at(math, parent_path), i),
+    };
+    if LIST_ELEMENTS.contains(&parent.name.as_str()) {
+        parent.children.insert(if after {i + 1} else {i}, new_node);
+    } else if TABLE_ELEMENTS.contains(&parent.name.as_str()) {
+        bail!("Only a row or a cell can go next to a row or a cell -- move into a cell first");
+    } else {
+        // the node fills a slot (e.g., a numerator), so the slot becomes an mrow
+        let node = std::mem::take(&mut parent.children[i]);
+        parent.children[i] = MathNode::new("mrow", if after {vec![node, new_node]} else {vec![new_node, node]});
+    }
+    return Ok( () );
+}
+
+/// Delete the node at `path` and return the id to navigate to
+fn delete(math: &mut MathNode, path: &[usize], ids: &mut IdMaker) -> Result<String> {
+    let (&i, parent_path) = match path.split_last() {
+        None => bail!("The whole expression can't be deleted"),
+        Some(split) => split,
+    };
+    let parent = node_at(math, parent_path);
+    if LIST_ELEMENTS.contains(&parent.name.as_str()) || TABLE_ELEMENTS.contains(&parent.name.as_str()) {
+        if parent.children.len() == 1 {
+            if TABLE_ELEMENTS.contains(&parent.name.as_str()) {
+                bail!("The only {} in a table can't be deleted", if parent.is("mtable") {"row"} else {"cell"});
+            }
+            // leave something behind so that there is still somewhere to navigate to
+            let placeholder = ids.placeholder();
+            let nav_id = placeholder.get_attr("id").unwrap().to_string();
+            parent.children = vec![placeholder];
+            return Ok(nav_id);
+        }
+        parent.children.remove(i);
+        let neighbor = if i > 0 {&mut parent.children[i-1]} else {&mut parent.children[0]};
+        return Ok( match neighbor.get_attr("id") {
+            Some(id) => id.to_string(),
+            None => ids.set_id(neighbor),
+        } );
+    }
+    // the node fills a slot (e.g., a numerator)
+    let placeholder = ids.placeholder();
+    let nav_id = placeholder.get_attr("id").unwrap().to_string();
+    parent.children[i] = placeholder;
+    return Ok(nav_id);
+}
+
+/// Put `node` in `wrapper` and return the id to navigate to: the empty slot if there is one, otherwise the new node
+fn wrap(node: &mut MathNode, wrapper: Wrapper, ids: &mut IdMaker) -> String {
+    let inner = std::mem::take(node);
+    let (mut wrapped, slot) = match wrapper {
+        Wrapper::Fraction => with_slot("mfrac", inner, ids),
+        Wrapper::Root => with_slot("mroot", inner, ids),
+        Wrapper::Superscript => with_slot("msup", inner, ids),
+        Wrapper::Subscript => with_slot("msub", inner, ids),
+        Wrapper::SquareRoot => (MathNode::new("msqrt", vec![inner]), None),
+        Wrapper::Parentheses => (MathNode::new("mrow", vec![MathNode::mo("("), inner, MathNode::mo(")")]), None),
+        Wrapper::AbsoluteValue => (MathNode::new("mrow", vec![MathNode::mo("|"), inner, MathNode::mo("|")]), None),
+    };
+    let id = ids.set_id(&mut wrapped);
+    *node = wrapped;
+    return slot.unwrap_or(id);
+}
+
+/// `name` with `first` as its first child and a placeholder as the second -- the placeholder's id is returned
+fn with_slot(name: &str, first: MathNode, ids: &mut IdMaker) -> (MathNode, Option<String>) {
+    let placeholder = ids.placeholder();
+    let slot = placeholder.get_attr("id").map(|id| id.to_string());
+    return (MathNode::new(name, vec![first, placeholder]), slot);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(mathml: &str, id: &str, edit: Edit) -> (String, String) {
+        let result = edit_mathml(mathml, id, &edit).unwrap();
+        return (result.mathml, result.nav_id);
+    }
+
+    fn error(mathml: &str, id: &str, edit: Edit) -> String {
+        return edit_mathml(mathml, id, &edit).unwrap_err().to_string();
+    }
+
+    const TABLE: &str = "<math><mtable id=\"t\"><mtr id=\"r\"><mtd id=\"c\"><mi id=\"x\">x</mi></mtd></mtr></mtable></math>";
+    const FRACTION: &str = "<math><mfrac id=\"f\"><mi id=\"a\">a</mi><mi id=\"b\">b</mi></mfrac></math>";
+
+    #[test]
+    fn delete() {
+        assert_eq!(edit("<math><mrow id=\"r\"><mi id=\"a\">a</mi><mo id=\"p\">+</mo><mi id=\"b\">b</mi></mrow></math>", "b", Edit::Delete),
+                   ("<math><mrow id=\"r\"><mi id=\"a\">a</mi><mo id=\"p\">+</mo></mrow></math>".to_string(), "p".to_string()));
+        // a slot or the only thing in a cell is replaced by a placeholder
+        assert_eq!(edit(FRACTION, "b", Edit::Delete),
+                   ("<math><mfrac id=\"f\"><mi id=\"a\">a</mi><mi id=\"edit-1\">□</mi></mfrac></math>".to_string(), "edit-1".to_string()));
+        assert_eq!(edit(TABLE, "x", Edit::Delete),
+                   ("<math><mtable id=\"t\"><mtr id=\"r\"><mtd id=\"c\"><mi id=\"edit-1\">□</mi></mtd></mtr></mtable></math>".to_string(),
+                    "edit-1".to_string()));
+    }
+
+    #[test]
+    fn delete_only_cell() {
+        assert_eq!(error(TABLE, "c", Edit::Delete), "The only cell in a table can't be deleted");
+        assert_eq!(error(TABLE, "r", Edit::Delete), "The only row in a table can't be deleted");
+        assert_eq!(error(TABLE, "", Edit::Delete), "The whole expression can't be deleted");
+    }
+
+    #[test]
+    fn wrap_root() {
+        assert_eq!(edit("<math id=\"m\"><mi id=\"x\">x</mi></math>", "", Edit::Wrap(Wrapper::Fraction)),
+                   ("<math id=\"m\"><mfrac id=\"edit-2\"><mi id=\"x\">x</mi><mi id=\"edit-1\">□</mi></mfrac></math>".to_string(),
+                    "edit-1".to_string()));
+        assert_eq!(edit("<math id=\"m\"><mi id=\"x\">x</mi></math>", "m", Edit::Wrap(Wrapper::SquareRoot)),
+                   ("<math id=\"m\"><msqrt id=\"edit-1\"><mi id=\"x\">x</mi></msqrt></math>".to_string(), "edit-1".to_string()));
+    }
+
+    #[test]
+    fn insert_into_slot() {
+        assert_eq!(edit(FRACTION, "a", Edit::InsertAfter("y".to_string())),
+                   ("<math><mfrac id=\"f\"><mrow><mi id=\"a\">a</mi><mi id=\"edit-1\">y</mi></mrow><mi id=\"b\">b</mi></mfrac></math>".to_string(),
+                    "edit-1".to_string()));
+        assert_eq!(error(TABLE, "c", Edit::InsertBefore("y".to_string())),
+                   "Only a row or a cell can go next to a row or a cell -- move into a cell first");
+    }
+
+    #[test]
+    fn ids_do_not_clash() {
+        assert_eq!(edit("<math><mi id=\"edit-7\">x</mi></math>", "edit-7", Edit::Wrap(Wrapper::Subscript)).1, "edit-8");
+        assert_eq!(error(FRACTION, "z", Edit::Delete), "Couldn't find the node with id 'z'");
+    }
+}
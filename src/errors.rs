@@ -0,0 +1,83 @@
+//! Errors from the "input -> MathML -> speech + braille" pipeline.
+//!
+//! The pipeline functions return `anyhow` errors with the `Stage` that failed attached as context, so that
+//! `PipelineError::new` can recover what went wrong (and where in the input, for a `ParseError`).
+use std::fmt;
+use libmathcat::errors_to_string;
+use crate::convert::ParseError;
+use crate::document::MathSource;
+
+/// The part of the pipeline that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Detection,
+    Conversion,
+    Canonicalization,
+    Speech,
+    Braille,
+}
+
+impl Stage {
+    pub fn description(&self) -> &'static str {
+        return match self {
+            Stage::Detection => "Couldn't find any math in the input",
+            Stage::Conversion => "Couldn't convert the input to MathML",
+            Stage::Canonicalization => "MathCAT couldn't use the MathML",
+            Stage::Speech => "Couldn't generate speech",
+            Stage::Braille => "Couldn't generate braille",
+        };
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.description());
+    }
+}
+
+/// An error from the pipeline in a form that is ready to show to the user
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineError {
+    pub stage: Stage,
+    /// The message from the error that started the problem
+    pub message: String,
+    /// Where (char offsets into the input) the error is, if known
+    pub span: Option<(usize, usize)>,
+    /// The whole chain of error messages
+    pub details: String,
+}
+
+impl PipelineError {
+    /// If `e` doesn't have a `Stage` attached, it is assumed to be a conversion error
+    pub fn new(e: &anyhow::Error) -> PipelineError {
+        return PipelineError {
+            stage: e.downcast_ref::<Stage>().copied().unwrap_or(Stage::Conversion),
+            message: e.root_cause().to_string(),
+            span: e.downcast_ref::<ParseError>().map(|parse_error| (parse_error.start, parse_error.end)),
+            details: errors_to_string(e),
+        };
+    }
+
+    /// Split the text of `math` into the part before the error, the error, and the part after it.
+    /// `None` is returned if the error has no position in `math`.
+    /// If the error is at the end of `math`, the error part is empty.
+    pub fn split_source(&self, math: &MathSource) -> Option<(String, String, String)> {
+        let (start, end) = self.span?;
+        let chars: Vec<char> = math.text.chars().collect();
+        let start = start.checked_sub(math.start)?.min(chars.len());
+        let end = end.saturating_sub(math.start).clamp(start, chars.len());
+        return Some( (
+            chars[..start].iter().collect(),
+            chars[start..end].iter().collect(),
+            chars[end..].iter().collect(),
+        ) );
+    }
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}: {}", self.stage, self.message);
+    }
+}
+
+impl std::error::Error for PipelineError {}
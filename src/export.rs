@@ -0,0 +1,243 @@
+//! Export the results for an expression (e.g., to attach to a bug report or to use in lesson materials).
+//!
+//! The speech is generated twice -- as plain text and as SSML -- so both are available whatever the TTS setting is.
+//! The braille is available as Unicode braille and as a BRF (Braille Ready Format) file: ASCII braille
+//! broken into lines of at most `BRF_LINE_LENGTH` cells and pages of `BRF_PAGE_LENGTH` lines.
+//! The JSON bundle has everything, along with the preferences that were used.
+use anyhow::Result;
+use libmathcat::get_version;
+use crate::pipeline::{speech, braille, ascii_braille};
+use crate::preferences::PREFERENCES;
+use crate::settings::Settings;
+
+/// The number of cells in a line of a BRF file
+pub const BRF_LINE_LENGTH: usize = 40;
+/// The number of lines in a page of a BRF file
+pub const BRF_PAGE_LENGTH: usize = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// The speech as plain text
+    Text,
+    Ssml,
+    /// Unicode braille
+    Braille,
+    /// ASCII braille (Braille Ready Format)
+    Brf,
+    /// The canonicalized MathML (with ids)
+    MathML,
+    /// Everything, along with the preferences used
+    Json,
+}
+
+impl ExportFormat {
+    pub fn description(&self) -> &'static str {
+        return match self {
+            ExportFormat::Text => "Speech (text)",
+            ExportFormat::Ssml => "Speech (SSML)",
+            ExportFormat::Braille => "Braille (Unicode)",
+            ExportFormat::Brf => "Braille (BRF)",
+            ExportFormat::MathML => "MathML with ids",
+            ExportFormat::Json => "Everything (JSON)",
+        };
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        return match self {
+            ExportFormat::Text => "txt",
+            ExportFormat::Ssml => "ssml",
+            ExportFormat::Braille => "txt",
+            ExportFormat::Brf => "brf",
+            ExportFormat::MathML => "mml",
+            ExportFormat::Json => "json",
+        };
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        return match self {
+            ExportFormat::Text | ExportFormat::Braille | ExportFormat::Brf => "text/plain;charset=utf-8",
+            ExportFormat::Ssml => "application/ssml+xml",
+            ExportFormat::MathML => "application/mathml+xml",
+            ExportFormat::Json => "application/json",
+        };
+    }
+
+    /// A name for the format (used as the value in menus)
+    pub fn name(&self) -> &'static str {
+        return match self {
+            ExportFormat::Text => "text",
+            ExportFormat::Ssml => "ssml",
+            ExportFormat::Braille => "braille",
+            ExportFormat::Brf => "brf",
+            ExportFormat::MathML => "mathml",
+            ExportFormat::Json => "json",
+        };
+    }
+
+    /// The format called `name` (see `ExportFormat::name`)
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        return EXPORT_FORMATS.iter().copied().find(|format| format.name() == name);
+    }
+}
+
+pub static EXPORT_FORMATS: &[ExportFormat] = &[
+    ExportFormat::Text, ExportFormat::Ssml, ExportFormat::Braille, ExportFormat::Brf, ExportFormat::MathML, ExportFormat::Json,
+];
+
+/// The results for an expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportData {
+    /// What the user typed (or an empty string if that isn't known)
+    pub input: String,
+    /// The canonicalized MathML (with ids)
+    pub mathml: String,
+    pub speech: String,
+    pub ssml: String,
+    /// Unicode braille
+    pub braille: String,
+    pub settings: Settings,
+}
+
+/// Generate the results for the MathML most recently given to MathCAT (`mathml` is that MathML after canonicalization).
+/// This changes MathCAT's preferences -- the caller should restore them afterwards (see `Settings::apply`).
+pub fn export_data(input: &str, mathml: &str, settings: &Settings) -> Result<ExportData> {
+    let text_settings = Settings { tts: "None".to_string(), ..settings.clone() };
+    let ssml_settings = Settings { tts: "SSML".to_string(), ..settings.clone() };
+    return Ok( ExportData {
+        input: input.to_string(),
+        mathml: mathml.to_string(),
+        speech: speech(&text_settings)?,
+        ssml: speech(&ssml_settings)?,
+        braille: braille(settings, "")?,
+        settings: settings.clone(),
+    } );
+}
+
+impl ExportData {
+    /// The contents of the file for `format`
+    pub fn to_format(&self, format: ExportFormat) -> String {
+        return match format {
+            ExportFormat::Text => self.speech.clone(),
+            ExportFormat::Ssml => format!("<speak version=\"1.1\" xmlns=\"http://www.w3.org/2001/10/synthesis\" xml:lang=\"{}\">{}</speak>\n",
+                                          self.settings.language, self.ssml),
+            ExportFormat::Braille => self.braille.clone(),
+            ExportFormat::Brf => brf(&self.braille),
+            ExportFormat::MathML => self.mathml.clone(),
+            ExportFormat::Json => self.to_json(),
+        };
+    }
+
+    /// Everything as a JSON object. The preferences are the values MathCAT was given (by their MathCAT names).
+    fn to_json(&self) -> String {
+        let preferences: Vec<String> = PREFERENCES.iter()
+            .map(|preference| format!("    {}: {}", json_string(preference.name), json_string(&self.settings.preference(preference.name))))
+            .collect();
+        let fields = [
+            ("generator", format!("MathCAT Demo {} (MathCAT v{})", env!("CARGO_PKG_VERSION"), get_version())),
+            ("input", self.input.clone()),
+            ("mathml", self.mathml.clone()),
+            ("speech", self.speech.clone()),
+            ("ssml", self.ssml.clone()),
+            ("braille", self.braille.clone()),
+            ("ascii_braille", ascii_braille(&self.braille)),
+        ];
+        let mut json = String::from("{\n");
+        for (name, value) in fields.iter() {
+            json += &format!("  {}: {},\n", json_string(name), json_string(value));
+        }
+        json += &format!("  \"preferences\": {{\n{}\n  }}\n}}\n", preferences.join(",\n"));
+        return json;
+    }
+}
+
+/// `text` as a (quoted) JSON string
+fn json_string(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => result.push(ch),
+        }
+    }
+    result.push('"');
+    return result;
+}
+
+/// `braille` (Unicode braille) as the contents of a BRF file.
+/// Lines are broken at spaces when possible; lines end with CR LF and pages are separated by a form feed.
+pub fn brf(braille: &str) -> String {
+    let mut lines: Vec<String> = vec![];
+    for paragraph in ascii_braille(braille).lines() {
+        let mut line = String::new();
+        for word in paragraph.split(' ').filter(|word| !word.is_empty()) {
+            let word: Vec<char> = word.chars().collect();
+            // a word too long for a line is split across lines
+            for chunk in word.chunks(BRF_LINE_LENGTH) {
+                let chunk: String = chunk.iter().collect();
+                let length = line.chars().count();
+                if length > 0 && length + 1 + chunk.chars().count() > BRF_LINE_LENGTH {
+                    lines.push(std::mem::take(&mut line));
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line += &chunk;
+            }
+        }
+        lines.push(line);
+    }
+
+    let pages: Vec<String> = lines.chunks(BRF_PAGE_LENGTH)
+        .map(|page| page.iter().map(|line| format!("{}\r\n", line)).collect())
+        .collect();
+    return pages.join("\x0C");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::unicode_braille_char;
+
+    /// `ascii` (ASCII braille) as Unicode braille
+    fn braille(ascii: &str) -> String {
+        return ascii.chars().map(|ch| if ch == '\n' {ch} else {unicode_braille_char(ch)}).collect();
+    }
+
+    #[test]
+    fn brf_lines() {
+        let word = "A".repeat(15);
+        assert_eq!(brf(&braille(&format!("{0} {0} {0}", word))), format!("{0} {0}\r\n{0}\r\n", word));
+        // a word that is longer than a line is split
+        assert_eq!(brf(&braille(&"B".repeat(45))), format!("{}\r\n{}\r\n", "B".repeat(40), "B".repeat(5)));
+        // line breaks in the braille are kept and dots 7 and 8 are dropped
+        assert_eq!(brf("\u{2801}\u{2803}\n\u{28C1}"), "AB\r\nA\r\n");
+    }
+
+    #[test]
+    fn brf_pages() {
+        let lines = |n: usize| vec!["A"; n].join("\n");
+        assert_eq!(brf(&braille(&lines(BRF_PAGE_LENGTH))), "A\r\n".repeat(BRF_PAGE_LENGTH));
+        assert_eq!(brf(&braille(&lines(BRF_PAGE_LENGTH + 1))), "A\r\n".repeat(BRF_PAGE_LENGTH) + "\x0CA\r\n");
+    }
+
+    #[test]
+    fn json_strings() {
+        assert_eq!(json_string("a \"b\" \\ c"), r#""a \"b\" \\ c""#);
+        assert_eq!(json_string("1\n2\t3\r\u{1}"), r#""1\n2\t3\r\u0001""#);
+        assert_eq!(json_string("x² ⠁"), "\"x² ⠁\"");
+    }
+
+    #[test]
+    fn format_names() {
+        for &format in EXPORT_FORMATS {
+            assert_eq!(ExportFormat::from_name(format.name()), Some(format));
+        }
+        assert_eq!(ExportFormat::from_name("pdf"), None);
+    }
+}
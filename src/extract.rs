@@ -0,0 +1,315 @@
+//! Find the math in HTML, XHTML, and EPUB files.
+//!
+//! The `<math>` elements are found by scanning the text (HTML isn't necessarily well-formed XML, so it isn't parsed).
+//! An EPUB is a zip file: its content documents are read in reading (spine) order.
+use anyhow::{Result, Context, bail};
+use regex::Regex;
+use std::io::{Cursor, Read};
+use crate::errors::Stage;
+use crate::permalink::percent_decode;
+
+/// The number of chars of text kept before and after the math
+const CONTEXT_LENGTH: usize = 60;
+
+/// Where some math was found
+#[derive(Debug, Clone, PartialEq)]
+pub struct MathContext {
+    /// The file the math is in (for an EPUB, the file inside of it)
+    pub file: String,
+    /// The end of the text before the math (the markup is removed)
+    pub before: String,
+    /// The start of the text after the math (the markup is removed)
+    pub after: String,
+}
+
+/// A `<math>` element found in a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedMath {
+    /// The `<math>` element, with any namespace prefix (e.g., `m:`) removed
+    pub mathml: String,
+    pub context: MathContext,
+}
+
+/// Find the math in the file named `file_name` with the contents `contents`.
+/// The file type is determined by the extension: ".epub" files are unzipped, anything else is treated as (X)HTML.
+/// Errors have `Stage::Detection` attached.
+pub fn extract_math_from_file(file_name: &str, contents: &[u8]) -> Result<Vec<ExtractedMath>> {
+    let math = if file_name.to_ascii_lowercase().ends_with(".epub") {
+        extract_math_from_epub(contents)
+            .with_context(|| format!("Couldn't read the EPUB file '{}'", file_name))
+            .context(Stage::Detection)?
+    } else {
+        extract_math_from_html(&String::from_utf8_lossy(contents), file_name)
+    };
+    if math.is_empty() {
+        return Err( anyhow::anyhow!("No <math> elements found in '{}'", file_name).context(Stage::Detection) );
+    }
+    return Ok(math);
+}
+
+/// Find all the `<math>` elements in `html`. `file_name` is recorded in the context of each one.
+pub fn extract_math_from_html(html: &str, file_name: &str) -> Vec<ExtractedMath> {
+    lazy_static! {
+        static ref MATH_START: Regex = Regex::new(r"<([a-zA-Z][\w.-]*:)?math[\s>/]").unwrap();
+    };
+
+    // find the (byte) ranges first so that the context can stop at the neighboring math
+    let mut ranges: Vec<(usize, usize, &str)> = vec![];
+    let mut search_from = 0;
+    while let Some(caps) = MATH_START.captures(&html[search_from..]) {
+        let start = search_from + caps.get(0).unwrap().start();
+        let prefix = caps.get(1).map_or("", |prefix| prefix.as_str());
+        let end = match find_math_element_end(html, start, prefix) {
+            Some(end) => end,
+            None => break,      // unterminated -- nothing more can be found
+        };
+        ranges.push( (start, end, prefix) );
+        search_from = end;
+    }
+
+    return ranges.iter().enumerate().map(|(i, &(start, end, prefix))| {
+        let previous_end = if i == 0 {0} else {ranges[i-1].1};
+        let next_start = ranges.get(i+1).map_or(html.len(), |range| range.0);
+        let before = html_to_text(&html[previous_end..start]);
+        let after = html_to_text(&html[end..next_start]);
+        ExtractedMath {
+            mathml: remove_prefix(&html[start..end], prefix),
+            context: MathContext {
+                file: file_name.to_string(),
+                before: last_chars(&before, CONTEXT_LENGTH),
+                after: first_chars(&after, CONTEXT_LENGTH),
+            },
+        }
+    }).collect();
+}
+
+/// Find all the `<math>` elements in the content documents of an EPUB (in reading order)
+pub fn extract_math_from_epub(contents: &[u8]) -> Result<Vec<ExtractedMath>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(contents)).context("Not a zip file")?;
+    let content_files = match spine_files(&mut archive) {
+        Ok(files) => files,
+        Err(e) => {
+            // not a proper EPUB, but the HTML files can still be looked at
+            warn!("{} -- using all the HTML files in the EPUB", e);
+            archive.file_names()
+                .filter(|name| is_html_file_name(name))
+                .map(|name| name.to_string())
+                .collect()
+        },
+    };
+
+    let mut math = vec![];
+    for file_name in content_files {
+        match read_zip_file(&mut archive, &file_name) {
+            Ok(html) => math.append(&mut extract_math_from_html(&html, &file_name)),
+            Err(e) => warn!("{}", e),
+        }
+    }
+    return Ok(math);
+}
+
+/// The content documents listed in the spine of the package document, in order
+fn spine_files(archive: &mut zip::ZipArchive<Cursor<&[u8]>>) -> Result<Vec<String>> {
+    lazy_static! {
+        static ref ROOTFILE: Regex = Regex::new(r#"<(?:\w+:)?rootfile\s[^>]*full-path\s*=\s*["']([^"']+)["']"#).unwrap();
+        static ref ITEM: Regex = Regex::new(r"<(?:\w+:)?item\s[^>]*>").unwrap();
+        static ref ITEMREF: Regex = Regex::new(r"<(?:\w+:)?itemref\s[^>]*>").unwrap();
+    };
+
+    let container = read_zip_file(archive, "META-INF/container.xml")?;
+    let package_path = match ROOTFILE.captures(&container) {
+        Some(caps) => caps.get(1).unwrap().as_str().to_string(),
+        None => bail!("No package document given in 'META-INF/container.xml'"),
+    };
+    let package = read_zip_file(archive, &package_path)?;
+    let package_dir = package_path.rfind('/').map_or("", |i| &package_path[..i+1]);
+
+    let manifest: Vec<(String, String)> = ITEM.find_iter(&package)
+        .filter_map(|item| Some( (attribute(item.as_str(), "id")?, attribute(item.as_str(), "href")?) ))
+        .collect();
+    let files: Vec<String> = ITEMREF.find_iter(&package)
+        .filter_map(|itemref| attribute(itemref.as_str(), "idref"))
+        .filter_map(|idref| manifest.iter().find(|(id, _)| *id == idref))
+        .map(|(_, href)| package_dir.to_string() + &percent_decode(href))
+        .collect();
+    if files.is_empty() {
+        bail!("The spine in '{}' is empty", package_path);
+    }
+    return Ok(files);
+}
+
+fn read_zip_file(archive: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<String> {
+    let mut file = archive.by_name(name).with_context(|| format!("'{}' is missing", name))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).with_context(|| format!("Couldn't read '{}'", name))?;
+    return Ok(contents);
+}
+
+fn is_html_file_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    return name.ends_with(".xhtml") || name.ends_with(".html") || name.ends_with(".htm");
+}
+
+/// The value of the attribute `name` in the start tag `tag`
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    lazy_static! {
+        static ref ATTRIBUTE: Regex = Regex::new(r#"\s([\w:.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    };
+    let caps = ATTRIBUTE.captures_iter(tag).find(|caps| &caps[1] == name)?;
+    return caps.get(2).or_else(|| caps.get(3)).map(|value| value.as_str().to_string());
+}
+
+/// The (byte) position just past the end of the `math` element that starts at `start`
+fn find_math_element_end(html: &str, start: usize, prefix: &str) -> Option<usize> {
+    let start_tag_end = start + html[start..].find('>')? + 1;
+    if html[..start_tag_end].ends_with("/>") {
+        return Some(start_tag_end);
+    }
+    // `math` elements can't be nested, so the first end tag is the matching one
+    let end_tag = format!("</{}math", prefix);
+    let end_tag_start = start_tag_end + html[start_tag_end..].find(&end_tag)?;
+    return Some( end_tag_start + html[end_tag_start..].find('>')? + 1 );
+}
+
+/// Remove the namespace prefix from the elements in `mathml` (MathCAT doesn't understand prefixes)
+fn remove_prefix(mathml: &str, prefix: &str) -> String {
+    if prefix.is_empty() {
+        return mathml.to_string();
+    }
+    return mathml.replace(&format!("<{}", prefix), "<")
+                 .replace(&format!("</{}", prefix), "</")
+                 .replace(&format!("xmlns:{}=", &prefix[..prefix.len()-1]), "xmlns=");
+}
+
+/// The text in `html`, with the markup (and anything in a `head`, `script`, or `style` element) removed,
+/// a few common entities replaced, and the whitespace collapsed
+fn html_to_text(html: &str) -> String {
+    lazy_static! {
+        static ref HIDDEN: Regex = Regex::new(r"(?is)<(head|script|style)[\s>].*?</(head|script|style)\s*>").unwrap();
+        static ref MARKUP: Regex = Regex::new(r"(?s)<!--.*?-->|<[^>]*>").unwrap();
+    };
+    let text = HIDDEN.replace_all(html, " ");
+    let text = MARKUP.replace_all(&text, " ");
+    let text = text.replace("&nbsp;", " ").replace("&#160;", " ")
+                   .replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'")
+                   .replace("&amp;", "&");
+    return text.split_whitespace().collect::<Vec<&str>>().join(" ");
+}
+
+fn first_chars(text: &str, n: usize) -> String {
+    if text.chars().count() <= n {
+        return text.to_string();
+    }
+    return text.chars().take(n).collect::<String>() + "…";
+}
+
+fn last_chars(text: &str, n: usize) -> String {
+    let n_chars = text.chars().count();
+    if n_chars <= n {
+        return text.to_string();
+    }
+    return "…".to_string() + &text.chars().skip(n_chars - n).collect::<String>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// (mathml, before, after) for each `<math>` element in `html`
+    fn extract(html: &str) -> Vec<(String, String, String)> {
+        return extract_math_from_html(html, "test.html").into_iter()
+            .map(|math| (math.mathml, math.context.before, math.context.after))
+            .collect();
+    }
+
+    fn found(mathml: &str, before: &str, after: &str) -> (String, String, String) {
+        return (mathml.to_string(), before.to_string(), after.to_string());
+    }
+
+    /// An EPUB (zip) file with `files` ((name, contents) pairs) in it
+    fn epub(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (name, contents) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        return zip.finish().unwrap().into_inner();
+    }
+
+    const CONTAINER: &str = "<container><rootfiles><rootfile full-path='OEBPS/content.opf' media-type='application/oebps-package+xml'/>\
+                             </rootfiles></container>";
+    const PACKAGE: &str = "<package><manifest>\
+                               <item id=\"nav\" href=\"nav.xhtml\" properties=\"nav\"/>\
+                               <item href=\"Chapter%202.xhtml\" id=\"ch2\"/>\
+                               <item id=\"ch1\" href=\"ch1.xhtml\"/>\
+                           </manifest><spine><itemref idref=\"ch1\"/><itemref idref=\"ch2\"/></spine></package>";
+
+    #[test]
+    fn html() {
+        assert_eq!(extract("<p>See <m:math xmlns:m=\"http://www.w3.org/1998/Math/MathML\"><m:mi>x</m:mi></m:math> here</p>"),
+                   vec![found("<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mi>x</mi></math>", "See", "here")]);
+        assert_eq!(extract("<p>empty <math/> and <math display='block'><mn>1</mn></math></p>"),
+                   vec![found("<math/>", "empty", "and"), found("<math display='block'><mn>1</mn></math>", "and", "")]);
+        // an unterminated element ends the search
+        assert_eq!(extract("<math><mi>a</mi></math> b <math><mi>c</mi>"), vec![found("<math><mi>a</mi></math>", "", "b c")]);
+        assert!(extract("<p>no <mathematics>math</mathematics> here</p>").is_empty());
+    }
+
+    #[test]
+    fn context() {
+        let html = format!("<head><title>t</title></head><p>{} &amp;</p><math><mi>a</mi></math> and <math><mi>b</mi></math><p>{}</p>",
+                           "x".repeat(100), "y".repeat(100));
+        assert_eq!(extract(&html), vec![
+            found("<math><mi>a</mi></math>", &("…".to_string() + &"x".repeat(58) + " &"), "and"),
+            found("<math><mi>b</mi></math>", "and", &("y".repeat(60) + "…")),
+        ]);
+    }
+
+    #[test]
+    fn spine_order() {
+        let contents = epub(&[
+            ("META-INF/container.xml", CONTAINER),
+            ("OEBPS/content.opf", PACKAGE),
+            ("OEBPS/nav.xhtml", "<nav/>"),
+            ("OEBPS/Chapter 2.xhtml", "<math><mi>b</mi></math>"),
+            ("OEBPS/ch1.xhtml", "<math><mi>a</mi></math>"),
+        ]);
+        let mut archive = zip::ZipArchive::new(Cursor::new(contents.as_slice())).unwrap();
+        assert_eq!(spine_files(&mut archive).unwrap(), vec!["OEBPS/ch1.xhtml", "OEBPS/Chapter 2.xhtml"]);
+
+        let math: Vec<(String, String)> = extract_math_from_file("book.EPUB", &contents).unwrap().into_iter()
+            .map(|math| (math.context.file, math.mathml))
+            .collect();
+        assert_eq!(math, vec![
+            ("OEBPS/ch1.xhtml".to_string(), "<math><mi>a</mi></math>".to_string()),
+            ("OEBPS/Chapter 2.xhtml".to_string(), "<math><mi>b</mi></math>".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn missing_container() {
+        let contents = epub(&[
+            ("a.xhtml", "<math><mi>a</mi></math>"),
+            ("text/b.HTML", "<math><mi>b</mi></math>"),
+            ("style.css", "<math><mi>c</mi></math>"),
+        ]);
+        let mut files: Vec<String> = extract_math_from_epub(&contents).unwrap().into_iter().map(|math| math.context.file).collect();
+        files.sort();
+        assert_eq!(files, vec!["a.xhtml", "text/b.HTML"]);
+    }
+
+    #[test]
+    fn attributes() {
+        assert_eq!(attribute("<item id=\"x\" href='a b.xhtml'>", "href").as_deref(), Some("a b.xhtml"));
+        assert_eq!(attribute("<itemref xml:idref=\"y\" idref=\"x\"/>", "idref").as_deref(), Some("x"));
+        assert_eq!(attribute("<item id=\"x\">", "href"), None);
+    }
+
+    #[test]
+    fn no_math() {
+        let e = extract_math_from_file("page.html", b"<p>text</p>").unwrap_err();
+        assert_eq!(e.root_cause().to_string(), "No <math> elements found in 'page.html'");
+    }
+}
@@ -0,0 +1,331 @@
+//! Built-in examples, grouped by category, that can be chosen from a menu in the GUI.
+//!
+//! Each example has the (English) speech MathCAT is expected to produce for it in each speech style.
+//! That lets the gallery double as a smoke test: `cargo run --example gallery` renders every example and
+//! reports the ones whose speech has changed. `cargo test -- --ignored` does the same check (it needs MathCAT's rules
+//! in `../MathCAT/Rules`, so it isn't run by a plain `cargo test`).
+use anyhow::Result;
+use crate::pipeline::render;
+use crate::settings::Settings;
+use crate::mhchem::contains_mhchem;
+
+/// A built-in example
+pub struct Example {
+    pub category: &'static str,
+    pub name: &'static str,
+    /// What goes in the input area
+    pub input: &'static str,
+    /// (speech style, expected speech) pairs -- see `Example::expected_speech_settings` for the other settings used
+    pub speech: &'static [(&'static str, &'static str)],
+}
+
+/// The examples, in menu order (examples in the same category are next to each other).
+/// The first one is what is shown when the demo starts.
+pub static EXAMPLES: &[Example] = &[
+    // Fractions
+    Example {
+        category: "Fractions",
+        name: "Quadratic formula",
+        input: r"$x = {-b \pm \sqrt{b^2-4ac} \over 2a}$",
+        speech: &[
+            ("ClearSpeak", "x equals, the fraction with numerator negative b plus or minus the square root of b squared minus 4 a c; and denominator 2 a;"),
+            ("SimpleSpeak", "x equals, fraction, negative b plus or minus the square root of b squared minus 4 a c end root; over 2 a, end fraction;"),
+        ],
+    },
+    Example {
+        category: "Fractions",
+        name: "Numeric fraction",
+        input: r"$\frac{3}{4}$",
+        speech: &[
+            ("ClearSpeak", "3 fourths"),
+            ("SimpleSpeak", "3 fourths"),
+        ],
+    },
+    Example {
+        category: "Fractions",
+        name: "Rational function",
+        input: r"$\frac{x+1}{x-1}$",
+        speech: &[
+            ("ClearSpeak", "the fraction with numerator x plus 1; and denominator x minus 1;"),
+            ("SimpleSpeak", "fraction, x plus 1, over x minus 1, end fraction;"),
+        ],
+    },
+
+    // Radicals
+    Example {
+        category: "Radicals",
+        name: "Distance",
+        input: r"$d = \sqrt{x^2+y^2}$",
+        speech: &[
+            ("ClearSpeak", "d equals the square root of x squared plus y squared;"),
+            ("SimpleSpeak", "d equals the square root of x squared plus y squared end root;"),
+        ],
+    },
+    Example {
+        category: "Radicals",
+        name: "Cube root",
+        input: r"$\sqrt[3]{8} = 2$",
+        speech: &[
+            ("ClearSpeak", "the cube root of 8; equals 2"),
+            ("SimpleSpeak", "the cube root of 8; equals 2"),
+        ],
+    },
+
+    // Calculus
+    Example {
+        category: "Calculus",
+        name: "Definite integral",
+        input: r"$\int_0^1 x^2\,dx = \frac{1}{3}$",
+        speech: &[
+            ("ClearSpeak", "the integral from 0 to 1 of, x squared d x; equals 1 third"),
+            ("SimpleSpeak", "the integral from 0 to 1 of, x squared d x; equals 1 third"),
+        ],
+    },
+    Example {
+        category: "Calculus",
+        name: "Limit",
+        input: r"$\lim_{x \to 0} \frac{\sin x}{x} = 1$",
+        speech: &[
+            ("ClearSpeak", "the limit as x approaches 0, of, the fraction with numerator sine x; and denominator x; equals 1"),
+            ("SimpleSpeak", "the limit as x approaches 0, of, fraction, sine x, over x, end fraction; equals 1"),
+        ],
+    },
+    Example {
+        category: "Calculus",
+        name: "Infinite series",
+        input: r"$\sum_{n=1}^{\infty} \frac{1}{n^2} = \frac{\pi^2}{6}$",
+        speech: &[
+            ("ClearSpeak", "the sum from n equals 1 to infinity of, the fraction with numerator 1; and denominator n squared; equals; the fraction with numerator pi squared; and denominator 6;"),
+            ("SimpleSpeak", "the sum from n equals 1 to infinity of, 1 over n squared; equals, pi squared over 6"),
+        ],
+    },
+
+    // Matrices
+    Example {
+        category: "Matrices",
+        name: "2×2 matrix",
+        input: r"$\begin{pmatrix} a & b \\ c & d \end{pmatrix}$",
+        speech: &[
+            ("ClearSpeak", "the 2 by 2 matrix; row 1; a, b; row 2; c, d;"),
+            ("SimpleSpeak", "the 2 by 2 matrix; row 1; a, b; row 2; c, d;"),
+        ],
+    },
+    Example {
+        category: "Matrices",
+        name: "Determinant",
+        input: r"$\begin{vmatrix} 1 & 2 \\ 3 & 4 \end{vmatrix} = -2$",
+        speech: &[
+            ("ClearSpeak", "the 2 by 2 determinant; row 1; 1, 2; row 2; 3, 4; equals negative 2"),
+            ("SimpleSpeak", "the 2 by 2 determinant; row 1; 1, 2; row 2; 3, 4; equals negative 2"),
+        ],
+    },
+
+    // Chemistry
+    Example {
+        category: "Chemistry",
+        name: "Water",
+        input: r"$\ce{H2O}$",
+        speech: &[
+            ("ClearSpeak", "cap h, 2, cap o"),
+            ("SimpleSpeak", "cap h, 2, cap o"),
+        ],
+    },
+    Example {
+        category: "Chemistry",
+        name: "Combustion of hydrogen",
+        input: r"$\ce{2H2 + O2 -> 2H2O}$",
+        speech: &[
+            ("ClearSpeak", "2, cap h, 2; plus cap o, 2; reacts to form; 2, cap h, 2, cap o"),
+            ("SimpleSpeak", "2, cap h, 2; plus cap o, 2; reacts to form; 2, cap h, 2, cap o"),
+        ],
+    },
+    Example {
+        category: "Chemistry",
+        name: "Ions",
+        input: r"$\ce{Na+ + Cl- -> NaCl}$",
+        speech: &[
+            ("ClearSpeak", "cap n a, plus; plus cap c l, minus; reacts to form; cap n a, cap c l"),
+            ("SimpleSpeak", "cap n a, plus; plus cap c l, minus; reacts to form; cap n a, cap c l"),
+        ],
+    },
+    Example {
+        category: "Chemistry",
+        name: "Equilibrium",
+        input: r"$\ce{N2 + 3H2 <=> 2NH3}$",
+        speech: &[
+            ("ClearSpeak", "cap n, 2; plus 3, cap h, 2; is in equilibrium with; 2, cap n, cap h, 3"),
+            ("SimpleSpeak", "cap n, 2; plus 3, cap h, 2; is in equilibrium with; 2, cap n, cap h, 3"),
+        ],
+    },
+    Example {
+        category: "Chemistry",
+        name: "Dissolving salt",
+        input: r"$\ce{NaCl(s) -> Na+(aq) + Cl-(aq)}$",
+        speech: &[
+            ("ClearSpeak", "cap n a, cap c l, solid; reacts to form; cap n a, plus, aqueous; plus cap c l, minus, aqueous"),
+            ("SimpleSpeak", "cap n a, cap c l, solid; reacts to form; cap n a, plus, aqueous; plus cap c l, minus, aqueous"),
+        ],
+    },
+    Example {
+        category: "Chemistry",
+        name: "Hydrate",
+        input: r"$\ce{CuSO4*5H2O}$",
+        speech: &[
+            ("ClearSpeak", "cap c u, cap s, cap o, 4; dot; 5, cap h, 2, cap o"),
+            ("SimpleSpeak", "cap c u, cap s, cap o, 4; dot; 5, cap h, 2, cap o"),
+        ],
+    },
+
+    // Sets
+    Example {
+        category: "Sets",
+        name: "Set-builder notation",
+        input: r"$A \cup B = \{x \mid x \in A \text{ or } x \in B\}$",
+        speech: &[
+            ("ClearSpeak", "cap eigh union cap b equals the set of all x such that x is a member of cap eigh or x is a member of cap b"),
+            ("SimpleSpeak", "cap eigh union cap b equals the set of all x such that x is an element of cap eigh or x is an element of cap b"),
+        ],
+    },
+    Example {
+        category: "Sets",
+        name: "Disjoint sets",
+        input: r"$A \cap B = \emptyset$",
+        speech: &[
+            ("ClearSpeak", "cap eigh intersection cap b equals the empty set"),
+            ("SimpleSpeak", "cap eigh intersection cap b equals the empty set"),
+        ],
+    },
+
+    // Statistics
+    Example {
+        category: "Statistics",
+        name: "Mean",
+        input: r"$\bar{x} = \frac{1}{n}\sum_{i=1}^{n} x_i$",
+        speech: &[
+            ("ClearSpeak", "x bar equals 1 over n, the sum from i equals 1 to n of x sub i"),
+            ("SimpleSpeak", "x bar equals 1 over n, the sum from i equals 1 to n of x sub i"),
+        ],
+    },
+    Example {
+        category: "Statistics",
+        name: "Standard deviation",
+        input: r"$\sigma = \sqrt{\frac{1}{N}\sum_{i=1}^{N}(x_i-\mu)^2}$",
+        speech: &[
+            ("ClearSpeak", "sigma equals the square root of 1 over cap n, the sum from i equals 1 to cap n of, open paren x sub i minus mu, close paren squared;"),
+            ("SimpleSpeak", "sigma equals the square root of 1 over cap n, the sum from i equals 1 to cap n of, open paren x sub i minus mu, close paren squared end root;"),
+        ],
+    },
+
+    // Multi-line alignments
+    Example {
+        category: "Multi-line alignments",
+        name: "System of equations",
+        input: r"\begin{align} x + y &= 5 \\ x - y &= 1 \end{align}",
+        speech: &[
+            ("ClearSpeak", "2 lines; line 1; x plus y, equals 5; line 2; x minus y, equals 1;"),
+            ("SimpleSpeak", "2 lines; line 1; x plus y, equals 5; line 2; x minus y, equals 1;"),
+        ],
+    },
+    Example {
+        category: "Multi-line alignments",
+        name: "Piecewise function",
+        input: r"$|x| = \begin{cases} x & x \ge 0 \\ -x & x < 0 \end{cases}$",
+        speech: &[
+            ("ClearSpeak", "the absolute value of x; equals; 2 cases; case 1; x if x is greater than or equal to 0; case 2; negative x if x is less than 0;"),
+            ("SimpleSpeak", "the absolute value of x; equals; 2 cases; case 1; x if x is greater than or equal to 0; case 2; negative x if x is less than 0;"),
+        ],
+    },
+];
+
+/// The categories, in menu order
+pub fn categories() -> Vec<&'static str> {
+    let mut categories: Vec<&'static str> = vec![];
+    for example in EXAMPLES {
+        if !categories.contains(&example.category) {
+            categories.push(example.category);
+        }
+    }
+    return categories;
+}
+
+impl Example {
+    /// The speech expected for `speech_style` (None if none was recorded)
+    pub fn expected_speech(&self, speech_style: &str) -> Option<&'static str> {
+        return self.speech.iter()
+            .find(|(style, _)| *style == speech_style)
+            .map(|(_, speech)| *speech);
+    }
+
+    /// The settings the expected speech was recorded with: the defaults, except that there is no TTS markup
+    /// and chemistry is on for mhchem input (as it is in the GUI)
+    pub fn expected_speech_settings(&self, speech_style: &str) -> Settings {
+        return Settings {
+            tts: "None".to_string(),
+            speech_style: speech_style.to_string(),
+            chemistry: contains_mhchem(self.input),
+            ..Settings::default()
+        };
+    }
+}
+
+/// A difference between the speech an example is expected to have and what MathCAT says
+pub struct SpeechMismatch {
+    pub speech_style: &'static str,
+    pub expected: &'static str,
+    pub actual: String,
+}
+
+/// Render `example` in each of the speech styles it has expected speech for and return the ones that don't match.
+/// MathCAT must have been initialized (see `pipeline::init`).
+pub fn check_example(example: &Example) -> Result<Vec<SpeechMismatch>> {
+    let mut mismatches = vec![];
+    for &(speech_style, expected) in example.speech {
+        let rendering = render(example.input, &example.expected_speech_settings(speech_style))?;
+        if !same_speech(&rendering.speech, expected) {
+            mismatches.push( SpeechMismatch { speech_style, expected, actual: rendering.speech } );
+        }
+    }
+    return Ok(mismatches);
+}
+
+/// True if `speech1` and `speech2` are the same except for case, punctuation (pauses), and whitespace
+pub fn same_speech(speech1: &str, speech2: &str) -> bool {
+    fn words(speech: &str) -> Vec<String> {
+        return speech.split(|ch: char| ch.is_whitespace() || ch == ',' || ch == ';' || ch == '.')
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect();
+    }
+    return words(speech1) == words(speech2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::init;
+
+    /// MathCAT's rules, from the MathCAT checkout next to this one (where Cargo.toml looks for MathCAT)
+    const RULES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../MathCAT/Rules");
+
+    #[test]
+    #[ignore = "needs ../MathCAT/Rules"]
+    fn examples_have_the_expected_speech() {
+        assert!(std::path::Path::new(RULES_DIR).is_dir(), "'{}' doesn't exist", RULES_DIR);
+        init(RULES_DIR).unwrap();
+        let mut failures = vec![];
+        for example in EXAMPLES {
+            match check_example(example) {
+                Ok(mismatches) => failures.extend(mismatches.into_iter().map(|mismatch|
+                    format!("{} ({}):\n  expected: {}\n  actual:   {}", example.name, mismatch.speech_style, mismatch.expected, mismatch.actual))),
+                Err(e) => failures.push(format!("{}: {}", example.name, e)),
+            }
+        }
+        assert!(failures.is_empty(), "{} of {} examples failed:\n{}", failures.len(), EXAMPLES.len(), failures.join("\n"));
+    }
+
+    #[test]
+    fn speech_comparison_ignores_pauses_and_case() {
+        assert!(same_speech("x equals, fraction; 1 over 2", "X equals fraction 1 over 2."));
+        assert!(!same_speech("x equals 1 half", "x equals one half"));
+    }
+}
@@ -0,0 +1,50 @@
+//! The non-UI part of the MathCAT demo.
+//!
+//! This holds the "input string -> MathML -> speech + braille" pipeline so that it can be used
+//! without a browser. The yew GUI in `main.rs` is a thin layer on top of this.
+//! TeX, ASCIIMath, Office Math (OMML), Content MathML, OpenMath, and Unicode math are converted to MathML in Rust; the input format is auto-detected (see `detect`).
+#![allow(clippy::needless_return)]
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+
+pub mod settings;
+pub mod preferences;
+pub mod convert;
+pub mod mathml;
+pub mod tex;
+pub mod mhchem;
+pub mod asciimath;
+pub mod unicode_math;
+pub mod omml;
+pub mod content_mathml;
+pub mod detect;
+pub mod document;
+pub mod errors;
+pub mod permalink;
+pub mod source_view;
+pub mod ssml;
+pub mod extract;
+pub mod editor;
+pub mod compare;
+pub mod export;
+pub mod gallery;
+pub mod pipeline;
+
+pub use settings::Settings;
+pub use preferences::{Preference, PreferenceKind, PreferenceGroup, PREFERENCES};
+pub use convert::{MathFormat, ParseError, detect_format, string_to_mathml, input_to_mathml, input_to_mathml_as};
+pub use detect::{FormatDetection, detect};
+pub use errors::{Stage, PipelineError};
+pub use permalink::{make_permalink, read_permalink};
+pub use source_view::mathml_source_html;
+pub use ssml::{SsmlInspection, inspect_ssml};
+pub use extract::{ExtractedMath, MathContext, extract_math_from_file};
+pub use gallery::{Example, EXAMPLES};
+pub use export::{ExportFormat, ExportData, EXPORT_FORMATS, export_data};
+pub use editor::{Edit, Wrapper, EditResult, edit_mathml};
+pub use document::{MathSource, Segment, split_input, math_expressions};
+pub use pipeline::*;
@@ -1,15 +1,20 @@
 
 use yew::prelude::*;
-use yew::web_sys::Element;
-// use wasm_bindgen::JsCast;
+use yew::web_sys::{Element, HtmlElement, HtmlInputElement};
+use wasm_bindgen::JsCast;
 // use web_sys::{HtmlInputElement};
-use regex::Regex;
-#[macro_use]
-extern crate lazy_static;
 
 use wasm_bindgen::prelude::*;
 use cfg_if::cfg_if;
+use std::cell::RefCell;
+use std::time::Duration;
+use yew::services::{TimeoutService, timeout::TimeoutTask};
 use libmathcat::*;
+use math_cat_demo::*;
+use math_cat_demo::mathml::escape_xml;
+use math_cat_demo::mhchem::contains_mhchem;
+use math_cat_demo::preferences::{PREFERENCE_GROUPS, find_preference};
+use math_cat_demo::compare::{ComparisonChoices, Comparison, DiffPart, compare};
 
 
 cfg_if! {
@@ -32,39 +37,163 @@ enum Msg {
     NewMathML,
     NavMode(&'static str),
     NavVerbosity(&'static str),
-    Language(&'static str),
+    Language(String),
     SpeechStyle(&'static str),
     SpeechVerbosity(&'static str),
     SayCaps(&'static str),
+    Chemistry,
+    Preference(&'static str, String),
+    CompareChoice(&'static str, String),
+    Compare,
+    Pitch(String),
+    ResetClearSpeak,
+    Overview,
+    ShowOverview,
+    ExportFormat(String),
+    Export,
+    CopyExport,
     BrailleCode(&'static str),
     BrailleDisplayAs(&'static str),
     TTS(&'static str),
     Dots(&'static str),
     Navigate(KeyboardEvent),
+    NextExpression,
+    PreviousExpression,
+    ReadDocument,
+    InputFormat(Option<MathFormat>),
+    CopyLink,
+    OpenFile,
+    FileLoaded(String, FileContents),
+    SelectExpression(usize),
+    SourceNavigate(MouseEvent),
+    Example(usize),
+    EditMode,
+    EditInputKey(KeyboardEvent),
+    LiveMode,
+    InputChanged,
+    LiveUpdate,
+}
+
+/// MathCAT only knows about one expression at a time, so the state for each expression in the input is kept here
+struct Expression {
+    /// The canonicalized MathML (empty if there was an error, unless it is the last good MathML in live mode)
+    mathml: String,
+    source: MathSource,
+    /// Set if the expression couldn't be converted
+    error: Option<PipelineError>,
+    /// Why the input format was chosen
+    format_explanation: String,
+    /// Where the expression was found if it came from a file
+    context: Option<MathContext>,
+    nav_id: String,
+    nav_offset: usize,
+}
+
+/// The edits that need some math to be entered
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditInput {
+    Replace,
+    InsertBefore,
+    InsertAfter,
+}
+
+impl EditInput {
+    fn label(self) -> &'static str {
+        return match self {
+            EditInput::Replace => "Replace with: ",
+            EditInput::InsertBefore => "Insert before: ",
+            EditInput::InsertAfter => "Insert after: ",
+        };
+    }
+
+    fn to_edit(self, input: String) -> Edit {
+        return match self {
+            EditInput::Replace => Edit::Replace(input),
+            EditInput::InsertBefore => Edit::InsertBefore(input),
+            EditInput::InsertAfter => Edit::InsertAfter(input),
+        };
+    }
+}
+
+/// The contents of an opened file (only the size is shown when a `Msg` is logged)
+struct FileContents(Vec<u8>);
+
+impl std::fmt::Debug for FileContents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "FileContents({} bytes)", self.0.len());
+    }
+}
+
+/// What a key does in edit mode
+enum EditKey {
+    Edit(Edit),
+    NeedsInput(EditInput),
+}
+
+/// The edit mode keys (shown to the user -- keep in sync with `edit_key`)
+static EDIT_KEYS_HELP: &str = "Keys: r replace, a insert after, b insert before, Delete delete, \
+                               / fraction, s square root, n root, ^ superscript, _ subscript, ( parentheses, | absolute value";
+
+/// The edit for a key pressed in edit mode (None if the key isn't an edit key)
+fn edit_key(ev: &KeyboardEvent) -> Option<EditKey> {
+    if ev.ctrl_key() || ev.alt_key() || ev.meta_key() {
+        return None;
+    }
+    return match ev.key().as_str() {
+        "r" => Some(EditKey::NeedsInput(EditInput::Replace)),
+        "a" => Some(EditKey::NeedsInput(EditInput::InsertAfter)),
+        "b" => Some(EditKey::NeedsInput(EditInput::InsertBefore)),
+        "Delete" => Some(EditKey::Edit(Edit::Delete)),
+        "/" => Some(EditKey::Edit(Edit::Wrap(Wrapper::Fraction))),
+        "s" => Some(EditKey::Edit(Edit::Wrap(Wrapper::SquareRoot))),
+        "n" => Some(EditKey::Edit(Edit::Wrap(Wrapper::Root))),
+        "^" => Some(EditKey::Edit(Edit::Wrap(Wrapper::Superscript))),
+        "_" => Some(EditKey::Edit(Edit::Wrap(Wrapper::Subscript))),
+        "(" => Some(EditKey::Edit(Edit::Wrap(Wrapper::Parentheses))),
+        "|" => Some(EditKey::Edit(Edit::Wrap(Wrapper::AbsoluteValue))),
+        _ => None,
+    };
 }
 
 struct Model {
     // `ComponentLink` is like a reference to a component.
     // It can be used to send messages to the component
     link: ComponentLink<Self>,
+    initial_input: String,
+    render_initial_input: bool,     // true if the initial input came from a permalink
+    link_status: String,
+    export_format: ExportFormat,
+    export_status: String,
     math_string: String,
-    nav_mode: String,
-    nav_verbosity: String,
     display: Html,
-    language: String,
-    speech_style: String,
-    verbosity: String,
-    say_caps: bool,
+    expressions: Vec<Expression>,
+    current: usize,             // index into `expressions`
+    file_name: Option<String>,  // set if the expressions came from a file
+    input_format: Option<MathFormat>,   // None means auto-detect
+    input_error: Option<PipelineError>, // set if there is no math in the input
+    speech_error: Option<PipelineError>,
+    braille_error: Option<PipelineError>,
+    settings: Settings,
+    languages: Vec<(String, String)>,   // (code, name) for the languages with speech rules
+    comparison_choices: ComparisonChoices,
+    comparisons: Vec<Comparison>,   // the results of the last comparison (for the current expression)
     speech: String,
+    overview: String,           // the overview of the expression (if it is shown with the speech)
     speak: bool,
     nav_id: String,
     nav_offset: usize,
-    braille_code: String,
-    braille_display_as: String,
-    braille_dots78: String,
     braille: String,
     braille_node_ref: NodeRef,
-    tts: String,
+    source_node_ref: NodeRef,
+    source_mathml: String,      // the MathML shown in the source pane
+    ssml_node_ref: NodeRef,
+    ssml_inspection: Option<SsmlInspection>,    // set when the speech is SSML
+    ssml_shown: String,         // the SSML shown in the SSML inspector
+    live_update_task: Option<TimeoutTask>,  // pending live update (dropping it cancels it)
+    editing: bool,              // the keyboard edits the displayed math (as well as navigating it)
+    edit_input: Option<EditInput>,  // set while waiting for the math to use in an edit
+    edit_input_ref: NodeRef,
+    focus_edit_input: bool,     // the edit input just appeared
 
     update_speech: bool,
     update_braille: bool,
@@ -72,52 +201,773 @@ struct Model {
 
 impl Model {
     fn save_state(&self) {
-        let mut cookie = String::with_capacity(1024);
-        cookie += &format!("nav_mode={};", self.nav_mode);
-        cookie += &format!("nav_verbosity={};", self.nav_verbosity);
-        cookie += &format!("language={};", self.language);
-        cookie += &format!("speech_style={};", self.speech_style);
-        cookie += &format!("verbosity={};", self.verbosity);
-        cookie += &format!("say_caps={};", self.say_caps);
-        cookie += &format!("braille_code={};", self.braille_code);
-        cookie += &format!("braille_display_as={};", self.braille_display_as);
-        cookie += &format!("braille_dots78={};", self.braille_dots78);
-        cookie += &format!("tts={};", self.tts);
+        set_cookie(&self.settings.to_cookie_string());
     }
 
     fn init_state_from_cookies(&mut self) {
         let cookies = set_cookie("");
-        for cookie in cookies.split(';') {
-            let mut key_value = cookie.split('=');
-            let key = key_value.next();
-            let value = key_value.next();
-            match (key, value) {
-                (Some(key), Some(value)) => set_state(self, key.trim(), value.trim()),
-                _ => (),
+        self.settings.set_from_cookie_string(&cookies);
+    }
+
+    /// Convert `math` (found at `context` if it came from a file) to an `Expression` along with what to display for it.
+    /// The math is converted to MathML, then ids are added and the MathML is canonicalized.
+    fn convert_expression(&self, math: MathSource, context: Option<MathContext>) -> (Expression, Element) {
+        let mut expression = Expression {
+            mathml: String::default(),
+            format_explanation: format_explanation(&math.text, self.input_format),
+            source: math,
+            error: None,
+            context,
+            nav_id: String::default(),
+            nav_offset: 0,
+        };
+        let mathjax_html = match expression.source.to_mathml_as(self.input_format).and_then(canonicalize_mathml) {
+            Ok(math) => {
+                debug!("MathML with ids: \n{}", &math);
+                expression.mathml = math.clone();
+                mathml_to_chtml(math)
+            },
+            Err(e) => {
+                error!("{}", errors_to_string(&e));
+                let error = PipelineError::new(&e);
+                let html = error_to_html(&error, &expression.source);
+                expression.error = Some(error);
+                html
+            },
+        };
+        return (expression, mathjax_html);
+    }
+
+    /// Replace the displayed expressions with `math` (along with where it was found, if it came from a file).
+    fn set_expressions(&mut self, math: Vec<(MathSource, Option<MathContext>)>) {
+        let node = match &self.display {
+            Html::VRef(node) => node.clone(),
+            _ => return,
+        };
+        let document = yew::utils::document();
+        node.set_text_content(Some(""));
+        self.expressions.clear();
+        self.apply_canonicalization_preferences();
+        for (i, (math, context)) in math.into_iter().enumerate() {
+            let (expression, mathjax_html) = self.convert_expression(math, context);
+            let div = document.create_element("div").unwrap();
+            div.set_id(&expression_id(i));
+            div.set_class_name("expression");
+            if let Err(e) = div.append_child(&mathjax_html).and_then(|_| node.append_child(&div)) {
+                panic!("append_child returned error '{:?}'", e);
+            };
+            self.expressions.push(expression);
+        }
+        self.nav_id = "".to_string();
+        self.nav_offset = 0;
+        self.current = 0;
+        self.math_string = String::default();
+        self.set_input_error();
+        self.select_expression(0);
+    }
+
+    /// Used for live updates: like `set_expressions`, but only the expressions whose source changed are converted.
+    /// If a changed expression can't be converted, its last good rendering is kept (along with the error) so that
+    /// the display doesn't flip to an error while the user is in the middle of typing something.
+    fn update_expressions(&mut self, math: Vec<MathSource>) {
+        let node = match &self.display {
+            Html::VRef(node) => node.clone(),
+            _ => return,
+        };
+        let document = yew::utils::document();
+        self.apply_canonicalization_preferences();
+        if let Some(expression) = self.expressions.get_mut(self.current) {
+            expression.nav_id = self.nav_id.clone();
+            expression.nav_offset = self.nav_offset;
+        }
+        let current_mathml = self.expressions.get(self.current).map(|expression| expression.mathml.clone());
+        let n_old_expressions = self.expressions.len();
+        let mut old_expressions = std::mem::take(&mut self.expressions).into_iter();
+        for (i, math) in math.into_iter().enumerate() {
+            let previous = old_expressions.next();
+            if previous.as_ref().is_some_and(|previous| previous.source.text == math.text) {
+                let mut previous = previous.unwrap();
+                previous.source = math;     // the text is the same, but it may have moved
+                self.expressions.push(previous);
+                continue;
+            }
+
+            let (mut expression, mathjax_html) = self.convert_expression(math, None);
+            let div = match document.get_element_by_id(&expression_id(i)) {
+                Some(div) => div,
+                None => {
+                    let div = document.create_element("div").unwrap();
+                    div.set_id(&expression_id(i));
+                    div.set_class_name("expression");
+                    node.append_child(&div).unwrap();
+                    div
+                },
+            };
+            match previous {
+                Some(previous) if expression.mathml.is_empty() && !previous.mathml.is_empty() => {
+                    // keep showing (and speaking) the last good rendering
+                    expression.mathml = previous.mathml;
+                    expression.nav_id = previous.nav_id;
+                    expression.nav_offset = previous.nav_offset;
+                },
+                _ => {
+                    div.set_text_content(Some(""));
+                    div.append_child(&mathjax_html).unwrap();
+                },
             }
+            self.expressions.push(expression);
+        }
+        for i in self.expressions.len()..n_old_expressions {
+            if let Some(div) = document.get_element_by_id(&expression_id(i)) {
+                div.remove();
+            }
+        }
+
+        self.set_input_error();
+        self.current = self.current.min(self.expressions.len().saturating_sub(1));
+        if self.expressions.get(self.current).map(|expression| expression.mathml.clone()) == current_mathml {
+            // converting the other expressions changed the MathML that MathCAT has
+            self.restore_mathcat_state();
+            self.update_expression_classes();
+        } else {
+            self.nav_id = "".to_string();
+            self.nav_offset = 0;
+            self.select_expression(self.current);
+        }
+    }
+
+    /// Make `edit` at the navigation position in the current expression, then display, speak, and braille the result.
+    /// The navigation moves to what was changed.
+    fn apply_edit(&mut self, edit: Edit) {
+        if self.math_string.is_empty() {
+            return;
         }
+        let result = edit_mathml(&self.math_string, &self.nav_id, &edit)
+            .and_then(|result| Ok( (canonicalize_mathml(result.mathml)?, result.nav_id) ));
+        let (mathml, nav_id) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                error!("{}", errors_to_string(&e));
+                // speak the problem -- the user might not be able to see it
+                self.speech = format!("Can't make that edit: {}", PipelineError::new(&e).message);
+                self.speak = true;
+                self.restore_mathcat_state();
+                return;
+            },
+        };
 
-        fn set_state(model: &mut Model, name: &str, value: &str) {
+        self.math_string = mathml.clone();
+        if let Some(expression) = self.expressions.get_mut(self.current) {
+            expression.mathml = mathml.clone();
+            expression.error = None;
+        }
+        if let Some(div) = yew::utils::document().get_element_by_id(&expression_id(self.current)) {
+            div.set_text_content(Some(""));
+            div.append_child(&mathml_to_chtml(mathml)).unwrap();
+        }
+        self.update_expression_classes();
+        match navigate_to(&nav_id) {
+            Ok(nav) => self.apply_navigation(nav),
+            Err(e) => {
+                error!("{}", errors_to_string(&e));
+                self.nav_id = String::default();
+                self.nav_offset = 0;
+                self.update_speech = true;
+                self.update_braille = true;
+            },
+        }
+    }
+
+    /// Convert the expressions again (e.g., because a setting that affects conversion changed)
+    fn redo_expressions(&mut self) {
+        if self.file_name.is_some() {
+            // redo the math from the file, not what is in the input area
+            let math = self.expressions.iter().map(|e| (e.source.clone(), e.context.clone())).collect();
+            self.set_expressions(math);
+        } else {
+            self.link.send_message(Msg::NewMathML);
+        }
+    }
+
+    /// Turn on chemistry if `input` uses mhchem's `\ce{...}`.
+    /// It isn't turned off automatically because chemistry can also be given in other ways (e.g., as MathML).
+    fn detect_chemistry(&mut self, input: &str) {
+        if !self.settings.chemistry && contains_mhchem(input) {
+            info!("Found \\ce{{...}} -- turning on chemistry");
+            self.settings.chemistry = true;
+        }
+    }
+
+    /// Set `input_error` if there is no math
+    fn set_input_error(&mut self) {
+        self.input_error = None;
+        if self.expressions.is_empty() {
+            let e = anyhow::anyhow!("No math found -- use $...$ for TeX, `...` for ASCIIMath, or enter MathML")
+                        .context(Stage::Detection);
+            self.input_error = Some(PipelineError::new(&e));
+        }
+    }
+
+    /// Make expression `i` the one that is spoken, brailled, and navigated.
+    /// The navigation position of the current expression is remembered so that it is restored when returning to it.
+    fn select_expression(&mut self, i: usize) {
+        if let Some(expression) = self.expressions.get_mut(self.current) {
+            expression.nav_id = self.nav_id.clone();
+            expression.nav_offset = self.nav_offset;
+        }
+        if i >= self.expressions.len() {
+            return;
+        }
+
+        self.current = i;
+        self.comparisons.clear();
+        let expression = &self.expressions[i];
+        self.math_string = expression.mathml.clone();
+        self.nav_id = expression.nav_id.clone();
+        self.nav_offset = expression.nav_offset;
+        self.speech_error = None;
+        self.braille_error = None;
+        if self.math_string.is_empty() {
+            self.speech = expression.error.as_ref().map(|e| e.to_string()).unwrap_or_default();
+            self.braille = String::default();
+        } else {
+            self.restore_mathcat_state();
+        }
+        highlight_nav_element(&self.nav_id, self.nav_offset);
+        self.update_expression_classes();
+        self.update_speech = true;
+        self.update_braille = true;
+    }
+
+    /// Mark the current expression and any that are showing an old rendering (see `update_expressions`)
+    fn update_expression_classes(&self) {
+        let document = yew::utils::document();
+        for (i, expression) in self.expressions.iter().enumerate() {
+            if let Some(element) = document.get_element_by_id(&expression_id(i)) {
+                let is_stale = expression.error.is_some() && !expression.mathml.is_empty();
+                let class_name = if is_stale {"expression stale"} else {"expression"};
+                if i == self.current {
+                    element.set_class_name(&(class_name.to_string() + " current"));
+                    element.set_attribute("aria-current", "true").unwrap();
+                } else {
+                    element.set_class_name(class_name);
+                    element.remove_attribute("aria-current").unwrap();
+                }
+            }
+        }
+    }
+
+    /// The current expression's results in the chosen export format (None if there is nothing to export)
+    fn export_contents(&mut self) -> Option<String> {
+        if self.math_string.is_empty() {
+            self.export_status = "There is no math to export".to_string();
+            return None;
+        }
+        let input = self.expressions.get(self.current).map_or("", |expression| expression.source.text.as_str());
+        let result = export_data(input, &self.math_string, &self.settings);
+        // put back the user's preferences (the export changes the TTS setting)
+        if let Err(e) = self.settings.apply() {
+            error!("{}", errors_to_string(&e));
+        }
+        return match result {
+            Ok(data) => Some( data.to_format(self.export_format) ),
+            Err(e) => {
+                error!("{}", errors_to_string(&e));
+                self.export_status = format!("Export failed: {}", PipelineError::new(&e));
+                None
+            },
+        };
+    }
+
+    /// Use the result of moving the navigation (by a key or by clicking on the source)
+    fn apply_navigation(&mut self, nav: NavigationResult) {
+        self.speech = nav.speech;
+        self.nav_id = nav.nav_id;
+        self.nav_offset = nav.nav_offset;
+        highlight_nav_element(&self.nav_id, self.nav_offset);
+        self.settings.nav_mode = nav.nav_mode;
+        self.speak = true;
+        self.update_braille = true;
+    }
+
+    fn apply_canonicalization_preferences(&self) {
+        if let Err(e) = self.settings.apply_canonicalization_preferences() {
+            error!("{}", errors_to_string(&e));
+        }
+    }
+
+    /// Give MathCAT the current expression (and navigation position) again -- something else may have been given to it
+    fn restore_mathcat_state(&self) {
+        if self.math_string.is_empty() {
+            return;
+        }
+        let result = canonicalize_mathml(self.math_string.clone()).and_then(|_|
+            if self.nav_id.is_empty() {Ok( String::default() )} else {set_navigation_node(self.nav_id.clone(), self.nav_offset)}
+        );
+        if let Err(e) = result {
+            error!("{}", errors_to_string(&e));
+        }
+    }
+
+    /// The format that was used for the current expression (and why) along with buttons to override auto-detection
+    fn view_input_format(&self) -> Html {
+        let explanation = match self.expressions.get(self.current) {
+            Some(expression) => expression.format_explanation.clone(),
+            None => String::default(),
+        };
+        let format_button = |label: &'static str, input_format: Option<MathFormat>| html! {
+            <>
+                <input type="radio" id={format!("Format{}", label.replace(' ', ""))} name="input_format"
+                    checked = {self.input_format == input_format}
+                    onclick=self.link.callback(move |_| Msg::InputFormat(input_format))/>
+                <label for={format!("Format{}", label.replace(' ', ""))}>{label}</label>
+            </>
+        };
+        return html! {
+            <div id="input-format">
+                <span aria-live="polite" id="format-explanation">{explanation}</span>
+                <div role="radiogroup" aria-label="Input format">
+                    {"Input Format: "}
+                    { format_button("Auto", None) }
+                    { format_button("TeX", Some(MathFormat::TeX)) }
+                    { format_button("ASCIIMath", Some(MathFormat::ASCIIMath)) }
+                    { format_button("MathML", Some(MathFormat::MathML)) }
+                    { format_button("Unicode", Some(MathFormat::Unicode)) }
+                    { format_button("OMML", Some(MathFormat::OfficeMath)) }
+                    { format_button("Content MathML", Some(MathFormat::ContentMathML)) }
+                    { format_button("OpenMath", Some(MathFormat::OpenMath)) }
+                </div>
+            </div>
+        };
+    }
+
+    /// The edit mode checkbox and, when editing, the edit keys and the field for entering math
+    fn view_edit_panel(&self) -> Html {
+        let edit_input = match self.edit_input {
+            None => html! {},
+            Some(edit_input) => html! {
+                <div id="edit-input-area">
+                    <label for="edit-input">{edit_input.label()}</label>
+                    <input type="text" id="edit-input" ref={self.edit_input_ref.clone()}
+                        placeholder="TeX, ASCIIMath, MathML, ... (Enter to finish, Esc to cancel)"
+                        onkeydown=self.link.callback(Msg::EditInputKey) />
+                </div>
+            },
+        };
+        return html! {
+            <div id="edit-panel">
+                <input type="checkbox" id="edit-mode" checked={self.editing}
+                    title="Edit the displayed math at the navigation position"
+                    onclick=self.link.callback(|_| Msg::EditMode) />
+                <label for="edit-mode">{"Edit mode"}</label>
+                { if self.editing {
+                    html! { <span id="edit-keys">{EDIT_KEYS_HELP}</span> }
+                } else {
+                    html! {}
+                } }
+                { edit_input }
+            </div>
+        };
+    }
+
+    /// Sliders for the rate, pause length, and pitch of the speech.
+    /// The rate and pauses are MathCAT preferences (they change the SSML); the pitch is given to the speech engine.
+    fn view_speech_rate_controls(&self) -> Html {
+        let view_slider = |id: &str, label: &str, min: f64, max: f64, step: &str, value: String, on_change: Callback<ChangeData>| html! {
+            <span class="speech-slider">
+                <label for={id.to_string()}>{label}</label>
+                <input type="range" id={id.to_string()} min={min.to_string()} max={max.to_string()} step={step.to_string()}
+                    value={value.clone()} onchange=on_change />
+                <output for={id.to_string()}>{format!("{}%", value)}</output>
+            </span>
+        };
+        let view_preference_slider = |name: &'static str, id: &str, label: &str, step: &str| {
+            let (min, max) = match find_preference(name).map(|preference| preference.kind) {
+                Some(PreferenceKind::Number(min, max)) => (min, max),
+                _ => (0.0, 100.0),
+            };
+            let on_change = self.link.batch_callback(move |change| match change {
+                ChangeData::Value(value) => Some(Msg::Preference(name, value)),
+                _ => None,
+            });
+            view_slider(id, label, min, max, step, self.settings.preference(name), on_change)
+        };
+        let on_pitch_change = self.link.batch_callback(|change| match change {
+            ChangeData::Value(value) => Some(Msg::Pitch(value)),
+            _ => None,
+        });
+        return html! {
+            <div id="speech-rate-controls">
+                { view_preference_slider("MathRate", "math-rate", "Rate", "10") }
+                { view_preference_slider("PauseFactor", "pause-factor", "Pauses", "10") }
+                { view_slider("pitch", "Pitch", -50.0, 50.0, "5", self.settings.pitch.clone(), on_pitch_change) }
+            </div>
+        };
+    }
+
+    /// The speech's SSML pretty-printed, along with any problems with it
+    fn view_ssml_inspector(&self) -> Html {
+        let bookmarks = self.settings.preference("Bookmark") == "true";
+        let view_issues = match &self.ssml_inspection {
+            None => html! {
+                <p>{"The speech isn't SSML -- choose \"SSML\" for \"Speech markup\" in \"All MathCAT Preferences\"."}</p>
+            },
+            Some(inspection) if inspection.issues.is_empty() => html! {
+                <p class="ssml-valid">{"No problems found (SSML 1.1)."}</p>
+            },
+            Some(inspection) => html! {
+                <ul id="ssml-issues">
+                    { for inspection.issues.iter().map(|issue| html! { <li>{issue.to_string()}</li> }) }
+                </ul>
+            },
+        };
+        return html! {
+            <details id="ssml-inspector-details">
+                <summary>{"SSML Inspector"}</summary>
+                <input type="checkbox" id="ssml-bookmarks" checked={bookmarks}
+                    onclick=self.link.callback(move |_| Msg::Preference("Bookmark", (!bookmarks).to_string())) />
+                <label for="ssml-bookmarks">{"Include bookmarks (<mark> elements)"}</label>
+                { view_issues }
+                <pre id="ssml-inspector" ref={self.ssml_node_ref.clone()}></pre>
+            </details>
+        };
+    }
+
+    /// Save or copy the results for the current expression in one of the export formats
+    fn view_export_controls(&self) -> Html {
+        let no_math = self.math_string.is_empty();
+        return html! {
+            <div id="export-controls">
+                <label for="export-format">{"Export: "}</label>
+                <select id="export-format"
+                    onchange=self.link.batch_callback(|change| match change {
+                        ChangeData::Select(select) => Some(Msg::ExportFormat(select.value())),
+                        _ => None,
+                    })>
+                    { for EXPORT_FORMATS.iter().map(|format| html! {
+                        <option value={format.name()} selected={*format == self.export_format}>{format.description()}</option>
+                    }) }
+                </select>
+                <input type="button" value="Download" id="export-download-button" disabled={no_math}
+                    onclick=self.link.callback(|_| Msg::Export) />
+                <input type="button" value="Copy" id="export-copy-button" disabled={no_math}
+                    onclick=self.link.callback(|_| Msg::CopyExport) />
+                <span aria-live="polite" id="export-status">{&self.export_status}</span>
+            </div>
+        };
+    }
+
+    /// Choices of languages, speech styles, and braille codes to compare, and a grid with the results
+    fn view_comparison_panel(&self) -> Html {
+        let view_choice = |kind: &'static str, value: &str, label: &str, is_checked: bool| {
+            let id = format!("compare-{}-{}", kind, value);
             let value = value.to_string();
-            match name {
-                "nav_mode" => model.nav_mode = value,
-                "nav_verbosity" => model.nav_verbosity = value,
-                "language" => model.language = value,
-                "speech_style" => model.speech_style = value,
-                "verbosity" => model.verbosity = value,
-                "say_caps" => model.say_caps = value=="true",
-                "braille_code" => model.braille_code = value,
-                "braille_display_as" => model.braille_display_as = value,
-                "braille_dots78" => model.braille_dots78 = value,
-                "tts" => model.tts = value,
-                _ => (),
+            html! {
+                <span class="compare-choice">
+                    <input type="checkbox" id={id.clone()} checked={is_checked}
+                        onclick=self.link.callback(move |_| Msg::CompareChoice(kind, value.clone())) />
+                    <label for={id}>{label}</label>
+                </span>
             }
+        };
+        let choices = &self.comparison_choices;
+        let speech_styles = ["ClearSpeak", "SimpleSpeak"];
+        let braille_codes = match find_preference("BrailleCode").map(|preference| preference.kind) {
+            Some(PreferenceKind::Choice(codes)) => codes,
+            _ => &[],
+        };
+        let view_parts = |parts: &Vec<DiffPart>| html! {
+            { for parts.iter().map(|part| if part.changed {
+                html! { <mark class="diff">{&part.text}</mark> }
+            } else {
+                html! { {&part.text} }
+            }) }
+        };
+        let view_comparison = |comparison: &Comparison| html! {
+            <tr>
+                <th scope="row">{&comparison.label}</th>
+                { match &comparison.error {
+                    Some(error) => html! { <td colspan="2" class="compare-error">{error}</td> },
+                    None => html! {
+                        <>
+                            <td>{view_parts(&comparison.speech)}</td>
+                            <td class="compare-braille">{view_parts(&comparison.braille)}</td>
+                        </>
+                    },
+                } }
+            </tr>
+        };
+        return html! {
+            <details id="comparison-panel">
+                <summary>{"Compare Speech and Braille"}</summary>
+                <p>{"Every combination of the checked values is compared (the current setting is used if none are checked).
+                     Differences from the first row are highlighted."}</p>
+                <fieldset>
+                    <legend>{"Languages"}</legend>
+                    { for self.languages.iter().map(|(code, name)|
+                        view_choice("language", code, name, choices.languages.contains(code))) }
+                </fieldset>
+                <fieldset>
+                    <legend>{"Speech styles"}</legend>
+                    { for speech_styles.iter().map(|&style|
+                        view_choice("speech_style", style, style, choices.speech_styles.iter().any(|s| s == style))) }
+                </fieldset>
+                <fieldset>
+                    <legend>{"Braille codes"}</legend>
+                    { for braille_codes.iter().map(|&code|
+                        view_choice("braille_code", code, code, choices.braille_codes.iter().any(|c| c == code))) }
+                </fieldset>
+                <input type="button" value="Compare" id="compare-button"
+                    disabled={self.math_string.is_empty()}
+                    onclick=self.link.callback(|_| Msg::Compare) />
+                { if self.comparisons.is_empty() {
+                    html! {}
+                } else {
+                    html! {
+                        <table id="comparison-grid">
+                            <tr><th scope="col">{"Preferences"}</th><th scope="col">{"Speech"}</th><th scope="col">{"Braille"}</th></tr>
+                            { for self.comparisons.iter().map(view_comparison) }
+                        </table>
+                    }
+                } }
+            </details>
+        };
+    }
+
+    /// ClearSpeak's options for how particular notations are spoken (only shown when ClearSpeak is the speech style)
+    fn view_clear_speak_panel(&self) -> Html {
+        if self.settings.speech_style != "ClearSpeak" {
+            return html! {};
         }
+        return html! {
+            <details id="clearspeak-panel">
+                <summary>{"ClearSpeak Options"}</summary>
+                <table role="presentation">
+                    { for PREFERENCES.iter()
+                        .filter(|preference| preference.is_clear_speak())
+                        .map(|preference| self.view_preference_row(preference)) }
+                </table>
+                <input type="button" value="Reset to Auto" onclick=self.link.callback(|_| Msg::ResetClearSpeak) />
+            </details>
+        };
     }
+
+    /// A table row with the label and control for `preference`
+    fn view_preference_row(&self, preference: &'static Preference) -> Html {
+        let name = preference.name;
+        let value = self.settings.preference(name);
+        let id = format!("pref-{}", name);
+        // the markup can't be changed while speech is off (see `Settings::set_preference`)
+        let is_disabled = name == "TTS" && self.settings.tts == "Off";
+        let on_select = self.link.batch_callback(move |change| match change {
+            ChangeData::Select(select) => Some(Msg::Preference(name, select.value())),
+            _ => None,
+        });
+        let on_change = self.link.batch_callback(move |change| match change {
+            ChangeData::Value(value) => Some(Msg::Preference(name, value)),
+            _ => None,
+        });
+        let control = match preference.kind {
+            PreferenceKind::Choice(choices) => html! {
+                <select id={id.clone()} onchange=on_select disabled={is_disabled}
+                        title={if is_disabled {"Speech is off (turn it on with the TTS buttons)"} else {""}}>
+                    { for choices.iter().map(|&choice| html! {
+                        <option value={choice} selected={choice == value}>
+                            {if choice.is_empty() {"(nothing)"} else {choice}}
+                        </option>
+                    }) }
+                </select>
+            },
+            PreferenceKind::Language => html! {
+                <select id={id.clone()} onchange=on_select>
+                    { for self.languages.iter().map(|(code, name)| html! {
+                        <option value={code.clone()} selected={*code == value}>{name}</option>
+                    }) }
+                </select>
+            },
+            PreferenceKind::Bool => {
+                let is_checked = value == "true";
+                html! {
+                    <input type="checkbox" id={id.clone()} checked={is_checked}
+                        onclick=self.link.callback(move |_| Msg::Preference(name, (!is_checked).to_string())) />
+                }
+            },
+            PreferenceKind::Number(min, max) => html! {
+                <input type="number" id={id.clone()} min={min.to_string()} max={max.to_string()} value={value}
+                    onchange=on_change />
+            },
+            PreferenceKind::Text => html! {
+                <input type="text" id={id.clone()} value={value} onchange=on_change />
+            },
+        };
+        return html! {
+            <tr>
+                <td><label for={id}>{preference.label}</label></td>
+                <td>{control}</td>
+            </tr>
+        };
+    }
+
+    /// Every MathCAT preference (see `preferences::PREFERENCES`), grouped by when MathCAT uses it.
+    /// ClearSpeak's options are in their own panel.
+    fn view_preferences_panel(&self) -> Html {
+        let view_group = |group: &PreferenceGroup| html! {
+            <fieldset>
+                <legend>{group.description()}</legend>
+                <table role="presentation">
+                    { for PREFERENCES.iter()
+                        .filter(|preference| preference.group == *group && !preference.is_clear_speak())
+                        .map(|preference| self.view_preference_row(preference)) }
+                </table>
+            </fieldset>
+        };
+        return html! {
+            <details id="preferences-panel">
+                <summary>{"All MathCAT Preferences"}</summary>
+                { for PREFERENCE_GROUPS.iter().map(view_group) }
+            </details>
+        };
+    }
+
+    /// The menu of built-in examples (grouped by category); choosing one replaces the input
+    fn view_examples_menu(&self) -> Html {
+        let view_category = |category: &str| {
+            html! {
+                <optgroup label={category.to_string()}>
+                    { for EXAMPLES.iter().enumerate()
+                        .filter(|(_, example)| example.category == category)
+                        .map(|(i, example)| html! { <option value={i.to_string()}>{example.name}</option> }) }
+                </optgroup>
+            }
+        };
+        return html! {
+            <div id="examples-menu">
+                <label for="examples">{"Examples: "}</label>
+                <span class="select"><select id="examples"
+                    onchange=self.link.batch_callback(|change| match change {
+                        ChangeData::Select(select) => select.value().parse().ok().map(Msg::Example),
+                        _ => None,
+                    })>
+                    <option value="" selected=true disabled=true>{"Choose an example..."}</option>
+                    { for gallery::categories().into_iter().map(view_category) }
+                </select></span>
+            </div>
+        };
+    }
+
+    /// Any errors for the current expression, with the part of the source that caused them and the full error chain
+    fn view_error_panel(&self) -> Html {
+        let expression = self.expressions.get(self.current);
+        let errors: Vec<&PipelineError> = [self.input_error.as_ref(), expression.and_then(|e| e.error.as_ref()),
+                                           self.speech_error.as_ref(), self.braille_error.as_ref()]
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        if errors.is_empty() {
+            return html! {};
+        }
+        let is_stale = expression.is_some_and(|expression| expression.error.is_some() && !expression.mathml.is_empty());
+        let view_error = |error: &PipelineError| {
+            let source = match expression.and_then(|expression| error.split_source(&expression.source)) {
+                None => html! {},
+                Some( (before, bad, after) ) => html! {
+                    <p>{"At: "}<code>{before}<mark>{if bad.is_empty() {" ".to_string()} else {bad}}</mark>{after}</code></p>
+                },
+            };
+            html! {
+                <div class="pipeline-error">
+                    <h3>{error.stage.description()}</h3>
+                    <p>{&error.message}</p>
+                    {source}
+                    <details>
+                        <summary>{"Details"}</summary>
+                        <pre>{&error.details}</pre>
+                    </details>
+                </div>
+            }
+        };
+        return html! {
+            <div id="error-panel" role="alert">
+                { for errors.into_iter().map(view_error) }
+                { if is_stale {
+                    html! { <p>{"The math shown (and spoken) is the last version that could be converted."}</p> }
+                } else {
+                    html! {}
+                } }
+            </div>
+        };
+    }
+
+    /// The math found in the opened file, with the text around it (only shown if the expressions came from a file).
+    /// Choosing one makes it the current expression.
+    fn view_file_math(&self) -> Html {
+        let file_name = match &self.file_name {
+            Some(file_name) => file_name,
+            None => return html! {},
+        };
+        let view_math = |(i, expression): (usize, &Expression)| {
+            let context = match &expression.context {
+                Some(context) => context,
+                None => return html! {},
+            };
+            html! {
+                <li class={if i == self.current {"current"} else {""}}>
+                    <input type="button" value={format!("Go to {}", i + 1)}
+                        aria-label={format!("Go to math {} in {}", i + 1, context.file)}
+                        onclick=self.link.callback(move |_| Msg::SelectExpression(i)) />
+                    {" "}{&context.before}{" "}<mark>{"[math]"}</mark>{" "}{&context.after}
+                    <span class="math-file">{format!(" ({})", context.file)}</span>
+                </li>
+            }
+        };
+        return html! {
+            <details id="file-math" open=true>
+                <summary>{format!("{} math elements found in '{}'", self.expressions.len(), file_name)}</summary>
+                <ol>
+                    { for self.expressions.iter().enumerate().map(view_math) }
+                </ol>
+            </details>
+        };
+    }
+
+    /// Buttons to move between expressions (only shown if there is more than one)
+    fn view_expression_buttons(&self) -> Html {
+        if self.expressions.len() < 2 {
+            return html! {};
+        }
+        return html! {
+            <div id="expression-nav">
+                <input type="button" value="Previous Expression (PageUp)"
+                    disabled = {self.current == 0}
+                    onclick=self.link.callback(|_| Msg::PreviousExpression) />
+                <span aria-live="polite">{format!(" Expression {} of {} ", self.current + 1, self.expressions.len())}</span>
+                <input type="button" value="Next Expression (PageDown)"
+                    disabled = {self.current + 1 == self.expressions.len()}
+                    onclick=self.link.callback(|_| Msg::NextExpression) />
+            </div>
+        };
+    }
+}
+
+/// Explain why the format of `math_str` was chosen
+fn format_explanation(math_str: &str, input_format: Option<MathFormat>) -> String {
+    let detection = detect(math_str);
+    return match input_format {
+        Some(format) if format != detection.format =>
+            format!("Treated as {} (chosen below). {}", format.as_str(), detection.explanation),
+        _ => detection.explanation,
+    };
+}
+
+/// The id of the element that holds the displayed expression `i`
+fn expression_id(i: usize) -> String {
+    return format!("expression-{}", i);
 }
-static INPUT_MESSAGE: &'static str = "Auto-detect format: override using $...$ for TeX, `...` for ASCIIMath, <math>...</math> for MathML\n";
-static START_FORMULA: &'static str = r"$x = {-b \pm \sqrt{b^2-4ac} \over 2a}$";
-// static START_FORMULA: &'static str = r"$x = {t \over 2a}$";
+
+static INPUT_MESSAGE: &'static str = "Auto-detect format: override using $...$ for TeX, `...` for ASCIIMath, <math>...</math> for MathML (several can be given)\n";
+/// How long (in ms) to wait after the user stops typing before updating in live mode
+const LIVE_UPDATE_DELAY: u64 = 500;
 
 /// get text for level 1 header
 fn get_header() -> String {
@@ -125,60 +975,83 @@ fn get_header() -> String {
 }
 
 
+/// The braille as it should be shown: for ASCIIBraille, chars with dots 7 or 8 are bolded
+fn braille_to_html(braille: &str, display_as: &str) -> String {
+    if display_as != "ASCIIBraille" {
+        return braille.to_string();
+    }
+    let mut result = String::with_capacity(braille.len());
+    for ch in braille.chars() {
+        let (ascii_ch, has_dots78) = ascii_braille_char(ch);
+        let ascii_ch = escape_xml(&ascii_ch.to_string());
+        if has_dots78 {
+            result.push_str(&format!("<span style='font-weight:bold'>{}</span>", ascii_ch));
+        } else {
+            result.push_str(&ascii_ch);
+        }
+    }
+    return result;
+}
+
 fn update_speech_and_braille(component: &mut Model) {
-    if component.math_string.is_empty() {
-        return;
-    }
-
-    if component.update_speech {
-        set_preference("Verbosity".to_string(), component.verbosity.clone()).unwrap();
-        set_preference("SpeechOverrides_CapitalLetters".to_string(),
-             (if component.say_caps {"cap"} else {""}).to_string()).unwrap();
-        set_preference("Language".to_string(), component.language.clone()).unwrap();
-        set_preference("SpeechStyle".to_string(), component.speech_style.clone()).unwrap();
-        let tts = if component.tts == "Off" {"None".to_string()} else {component.tts.clone()};
-        set_preference("TTS".to_string(), tts).unwrap();
-        set_preference("Bookmark".to_string(), "true".to_string()).unwrap();
-        let speech = match get_spoken_text() {
+    // with no math, there is nothing to update, but there might be something (e.g., a document reading) to speak
+    let has_math = !component.math_string.is_empty();
+    if has_math && component.update_speech {
+        component.speech_error = None;
+        let speech = match speech(&component.settings) {
             Ok(text) => text,
-            Err(e) => errors_to_string(&e),
+            Err(e) => {
+                error!("{}", errors_to_string(&e));
+                let error = PipelineError::new(&e);
+                let speech = error.to_string();
+                component.speech_error = Some(error);
+                speech
+            },
         };
 
+        component.overview = if component.settings.show_overview {
+            overview(&component.settings, "").unwrap_or_else(|e| {
+                error!("{}", errors_to_string(&e));
+                String::default()
+            })
+        } else {
+            String::default()
+        };
         component.speech = speech;
         component.speak = true;  
         component.update_speech = false;  
     }
 
-    if component.speak && component.tts != "Off" {
-        speak_text(&component.speech, &component.language);
+    // the speech is new (it might be from navigation or be an overview)
+    if component.speak {
+        component.ssml_inspection = if component.settings.tts == "SSML" && component.speech_error.is_none() {
+            Some( inspect_ssml(&component.speech) )
+        } else {
+            None
+        };
+    }
+
+    if component.speak && component.settings.tts != "Off" {
+        // MathCAT puts the rate into SSML, but plain text needs the speech engine to change the rate
+        let settings = &component.settings;
+        let rate = if settings.tts == "SSML" {"100".to_string()} else {settings.preference("MathRate")};
+        // the speech engine is always given SSML, so plain text needs its '<'s and '&'s escaped
+        let speech = if settings.tts == "SSML" {component.speech.clone()} else {escape_xml(&component.speech)};
+        speak_text(&speech, &settings.language, &rate, &settings.pitch);
         component.speak = false;
     }
 
-    if component.update_braille {
-        set_preference("BrailleCode".to_string(), component.braille_code.clone()).unwrap();
-        set_preference("BrailleNavHighlight".to_string(), component.braille_dots78.clone()).unwrap();
-        let mut braille = match get_braille(component.nav_id.clone()) {
+    if has_math && component.update_braille {
+        component.braille_error = None;
+        let braille = match braille(&component.settings, &component.nav_id) {
             Ok(str) => str,
-            Err(e) => errors_to_string(&e),
+            Err(e) => {
+                error!("{}", errors_to_string(&e));
+                component.braille_error = Some(PipelineError::new(&e));
+                String::default()
+            },
         };
-        if component.braille_display_as == "ASCIIBraille" {
-            lazy_static! {
-                static ref UNICODE_TO_ASCII: Vec<char> =
-                    " A1B'K2L@CIF/MSP\"E3H9O6R^DJG>NTQ,*5<-U8V.%[$+X!&;:4\\0Z7(_?W]#Y)=".chars().collect();
-            };
-    
-            let mut result = String::with_capacity(braille.len());
-            for ch in braille.chars() {
-                let i = (ch as usize - 0x2800) &0x3F;     // eliminate dots 7 and 8 if present 
-                let mut ascii_str = UNICODE_TO_ASCII[i].to_string();
-                if ch as usize > 0x283F {
-                    ascii_str = format!("<span style='font-weight:bold'>{}</span>", &ascii_str);
-                }
-                result.push_str(&ascii_str);
-            }
-            braille = result;
-        }
-        component.braille = braille;    
+        component.braille = braille_to_html(&braille, &component.settings.braille_display_as);
         component.update_braille = false;
     }
 }
@@ -188,153 +1061,319 @@ impl Component for Model {
     type Properties = ();
 
     fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let file_loaded = link.callback(|(file_name, contents)| Msg::FileLoaded(file_name, FileContents(contents)));
+        FILE_LOADED.with(|callback| *callback.borrow_mut() = Some(file_loaded));
         let mut initial_state = Self {
             link,
+            initial_input: EXAMPLES[0].input.to_string(),
+            render_initial_input: false,
+            link_status: String::default(),
+            export_format: ExportFormat::Json,
+            export_status: String::default(),
             math_string: String::default(),
-            nav_mode: "Enhanced".to_string(),
-            nav_verbosity: "Verbose".to_string(),
             display: Html::VRef(yew::utils::document().create_element("div").unwrap().into()),
-            language: "en".to_string(),
-            speech_style: "ClearSpeak".to_string(),
+            expressions: vec![],
+            current: 0,
+            file_name: None,
+            input_format: None,
+            input_error: None,
+            speech_error: None,
+            braille_error: None,
+            settings: Settings::default(),
+            languages: vec![],
+            comparison_choices: ComparisonChoices::default(),
+            comparisons: vec![],
             speak: true,
-            verbosity: "Verbose".to_string(),
-            say_caps: false,
             speech: String::default(),
+            overview: String::default(),
             nav_id: String::default(),
             nav_offset: 0,
-            braille_dots78: "EndPoints".to_string(),
-            braille_code: "Nemeth".to_string(),
-            braille_display_as: "Dots".to_string(),
             braille: String::default(),
             braille_node_ref: NodeRef::default(),
-            tts: "SSML".to_string(),
+            source_node_ref: NodeRef::default(),
+            source_mathml: String::default(),
+            ssml_node_ref: NodeRef::default(),
+            ssml_inspection: None,
+            ssml_shown: String::default(),
+            live_update_task: None,
+            editing: false,
+            edit_input: None,
+            edit_input_ref: NodeRef::default(),
+            focus_edit_input: false,
 
             update_speech: true,
             update_braille: true,
         };
         
         initial_state.init_state_from_cookies();
-        if let Err(e) = set_rules_dir("Rules".to_string()) {
-            error!("Didn't find rules dir: {}", e.to_string());
+        // a permalink overrides the saved settings
+        if let Some(input) = read_permalink(&get_url(), &mut initial_state.settings) {
+            initial_state.initial_input = input;
+            initial_state.render_initial_input = true;
+        }
+        // speech and braille settings are given to MathCAT when used; navigation settings need to be set now
+        if let Err(e) = init("Rules").and_then(|_| initial_state.settings.apply_navigation_preferences())
+                                      .and_then(|_| initial_state.settings.apply_canonicalization_preferences()) {
+            error!("{}", errors_to_string(&e));
         };
-        set_preference("CheckRuleFiles".to_string(), "None".to_string()).unwrap();
+        initial_state.languages = available_languages();
+        if initial_state.languages.is_empty() {
+            initial_state.languages.push( ("en".to_string(), language_name("en")) );
+        }
+        if !initial_state.languages.iter().any(|(code, _)| *code == initial_state.settings.language) {
+            warn!("No speech rules for the saved language '{}' -- using English", initial_state.settings.language);
+            initial_state.settings.language = "en".to_string();
+        }
 
         return initial_state;
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
-        lazy_static! {
-            static ref TEX: Regex = Regex::new("(?m)^(?P<start>\\$)(?P<math>.+?)(?P<end>\\$)$").unwrap();
-            static ref ASCIIMATH: Regex = Regex::new("(?m)^(?P<start>`)(?P<math>.+?)(?P<end>`)$").unwrap();
-            static ref MATHML: Regex = Regex::new("(?m)^(?P<start><)(?P<math>.+?)(?P<end>>)$").unwrap();
-        };
-
         debug!("======= In update: msg: {:?}", msg);
         self.update_braille = false;    // turn on when appropriate
         self.update_speech = false;     // turn on when appropriate
+        self.link_status = String::default();
+        self.export_status = String::default();
         match msg {
             Msg::NewMathML => {
-                // Get the MathML input string, and clear any previous output
-                if let Html::VRef(node) = &self.display {
-                    let math_str = get_text_of_element("mathml-input");
-                    let math_str = math_str.replace(INPUT_MESSAGE, "").replace("\n", " ").trim().to_string();
-                    let mut mathml;
-                    if let Some(caps) = TEX.captures(&math_str) {
-                        debug!("TeX: '{}'", &math_str);
-                        mathml = Some(string_to_mathml(&caps["math"], "TeX"));
-                    } else if let Some(caps) = ASCIIMATH.captures(&math_str) {
-                        mathml = Some(string_to_mathml(&caps["math"], "ASCIIMath"));
-                    } else if MATHML.is_match(&math_str) {
-                        // Don't need to convert
-                        mathml = Some( math_str );
-                    } else {
-                        // auto-detect -- look for {}'s as a sign it is TeX, otherwise ASCIIMath (which accepts a lot of TeX)
-                        mathml = Some(
-                            string_to_mathml(
-                            &math_str,
-                            if math_str.contains("}") {"TeX"} else {"ASCIIMath"}
-                            )
-                        );
-                    };
-                    // this adds ids and canonicalizes the MathML
-                    if let Some(mut math) = mathml {
-                        if !math.contains("display=\"block\"") && !math.contains("display='block'") {
-                            math = math.replace("<math ", "<math display='block' ");
-                        }
-                        // MathJax bug https://github.com/mathjax/MathJax/issues/2805:  newline at end causes MathJaX to hang(!)
-                        match set_mathml(math) {
-                            Ok(m) => {
-                                let math = m.trim_end().to_string();
-                                debug!("MathML with ids: \n{}", &math);
-                                mathml = Some(math);
-                            },
-                            Err(e) => {
-                                error!("{}", e);
-                                mathml = None;
-                            },
+                let input = get_text_of_element("mathml-input").replace(INPUT_MESSAGE, "");
+                self.file_name = None;
+                self.detect_chemistry(&input);
+                self.set_expressions(math_expressions(&input).into_iter().map(|math| (math, None)).collect());
+            },
+            Msg::Example(i) => {
+                let input = EXAMPLES[i].input;
+                set_text_of_element("mathml-input", &(INPUT_MESSAGE.to_string() + input));
+                self.file_name = None;
+                self.detect_chemistry(input);
+                self.set_expressions(math_expressions(input).into_iter().map(|math| (math, None)).collect());
+            },
+            Msg::EditMode => {
+                self.editing = !self.editing;
+                self.edit_input = None;
+            },
+            Msg::EditInputKey(ev) => {
+                match ev.key().as_str() {
+                    "Enter" => {
+                        ev.prevent_default();
+                        if let Some(edit_input) = self.edit_input.take() {
+                            self.apply_edit(edit_input.to_edit(get_text_of_element("edit-input")));
                         }
+                        focus_element("mathml-output");
+                    },
+                    "Escape" => {
+                        self.edit_input = None;
+                        focus_element("mathml-output");
+                    },
+                    _ => return false,
+                }
+            },
+            Msg::LiveMode => {
+                self.settings.live_update = !self.settings.live_update;
+                if self.settings.live_update {
+                    self.link.send_message(Msg::LiveUpdate);
+                }
+            },
+            Msg::InputChanged => {
+                if !self.settings.live_update {
+                    return false;
+                }
+                // wait until the user pauses typing (a new task replaces, and so cancels, any pending one)
+                self.live_update_task = Some( TimeoutService::spawn(Duration::from_millis(LIVE_UPDATE_DELAY),
+                                                                    self.link.callback(|_| Msg::LiveUpdate)) );
+                return false;
+            },
+            Msg::LiveUpdate => {
+                self.live_update_task = None;
+                let input = get_text_of_element("mathml-input").replace(INPUT_MESSAGE, "");
+                if self.file_name.is_some() {
+                    // the file's math was showing -- start over with the input
+                    self.file_name = None;
+                    self.set_expressions(vec![]);
+                }
+                self.detect_chemistry(&input);
+                self.update_expressions(math_expressions(&input));
+            },
+            Msg::OpenFile => {
+                open_math_file();   // this starts the sequence to get the file -- `load_math_file` is called later
+            },
+            Msg::FileLoaded(file_name, contents) => {
+                match extract_math_from_file(&file_name, &contents.0) {
+                    Ok(math) => {
+                        self.file_name = Some(file_name);
+                        self.set_expressions(math.into_iter()
+                            .map(|math| (MathSource { text: math.mathml, start: 0 }, Some(math.context)))
+                            .collect());
+                    },
+                    Err(e) => {
+                        error!("{}", errors_to_string(&e));
+                        self.input_error = Some(PipelineError::new(&e));
+                    },
+                }
+            },
+            Msg::SelectExpression(i) => {
+                self.select_expression(i);
+            },
+            Msg::SourceNavigate(ev) => {
+                // navigate to the element that was clicked on in the source pane
+                let nav_id = ev.target()
+                    .and_then(|target| target.dyn_into::<Element>().ok())
+                    .and_then(|element| element.closest("[data-id]").ok().flatten())
+                    .and_then(|element| element.get_attribute("data-id"));
+                if let Some(nav_id) = nav_id {
+                    match navigate_to(&nav_id) {
+                        Ok(nav) => self.apply_navigation(nav),
+                        Err(e) => error!("{}", errors_to_string(&e.context("Navigation failure!"))),
                     }
-
-                    let mathjax_html = match mathml {
-                        Some(math) => {
-                            self.math_string = math.clone();
-                            mathml_to_chtml(math)
-                        },
-                        None => {
-                            let span = yew::utils::document().create_element("span").unwrap();
-                            span.set_text_content(Some("Unrecognized Math -- use $...$ for TeX, `...` for ASCIIMath, or enter MathML"));
-                            span
-                        }
-                    };
-                    node.set_text_content(Some(""));
-                    let result = node.append_child(&mathjax_html);
-                    if let Err(e) = result {
-                        panic!("append_child returned error '{:?}'", e);
-                    };
-                    self.nav_id = "".to_string();
-                    self.nav_offset = 0;
-                    self.update_braille = true;
-                    self.update_speech = true;
-                };
+                }
+            },
+            Msg::NextExpression => {
+                if self.current + 1 < self.expressions.len() {
+                    self.select_expression(self.current + 1);
+                }
+            },
+            Msg::PreviousExpression => {
+                if self.current > 0 {
+                    self.select_expression(self.current - 1);
+                }
+            },
+            Msg::ReadDocument => {
+                // read the input (text with embedded math) as one continuous document
+                let input = get_text_of_element("mathml-input").replace(INPUT_MESSAGE, "");
+                match read_document(&input, &self.settings) {
+                    Ok(reading) => {
+                        self.speech = reading.speech;
+                        self.braille = braille_to_html(&reading.braille, &self.settings.braille_display_as);
+                        self.speak = true;
+                    },
+                    Err(e) => {
+                        error!("{}", errors_to_string(&e));
+                        self.speech = errors_to_string(&e);
+                    },
+                }
+                // reading the document changed the MathML MathCAT knows about
+                self.restore_mathcat_state();
+            },
+            Msg::CopyLink => {
+                let input = get_text_of_element("mathml-input").replace(INPUT_MESSAGE, "");
+                copy_to_clipboard(&make_permalink(&get_url(), &input, &self.settings));
+                self.link_status = "Link copied to the clipboard".to_string();
+            },
+            Msg::InputFormat(input_format) => {
+                self.input_format = input_format;
+                self.redo_expressions();
             },
             Msg::NavMode(text) => {
-                self.nav_mode = text.to_string();
+                self.settings.nav_mode = text.to_string();
                 set_preference("NavMode".to_string(), text.to_string()).unwrap();
             },
             Msg::NavVerbosity(text) => {
-                self.nav_verbosity = text.to_string();
+                self.settings.nav_verbosity = text.to_string();
                 set_preference("NavVerbosity".to_string(), text.to_string()).unwrap();
             },
             Msg::Language(text) => {
-                self.language = text.to_string();
+                self.settings.language = text;
                 self.update_speech = true;
             },
             Msg::SpeechStyle(text) => {
-                self.speech_style = text.to_string();
+                self.settings.speech_style = text.to_string();
                 self.update_speech = true;
             },
             Msg::SpeechVerbosity(text) => {
-                self.verbosity = text.to_string();
+                self.settings.verbosity = text.to_string();
                 self.update_speech = true;
             },
             Msg::SayCaps(_text) => {
-                self.say_caps = !self.say_caps;
+                self.settings.say_caps = !self.settings.say_caps;
                 self.update_speech = true;
             },
+            Msg::Preference(name, value) => {
+                self.settings.set_preference(name, &value);
+                match find_preference(name).map(|preference| preference.group) {
+                    Some(PreferenceGroup::Canonicalization) => self.redo_expressions(),
+                    Some(PreferenceGroup::Speech) => self.update_speech = true,
+                    Some(PreferenceGroup::Braille) => self.update_braille = true,
+                    Some(PreferenceGroup::Navigation) => {
+                        if let Err(e) = self.settings.apply_navigation_preferences() {
+                            error!("{}", errors_to_string(&e));
+                        }
+                    },
+                    None => (),
+                }
+            },
+            Msg::ExportFormat(name) => {
+                if let Some(format) = ExportFormat::from_name(&name) {
+                    self.export_format = format;
+                }
+            },
+            Msg::Export => {
+                if let Some(contents) = self.export_contents() {
+                    let file_name = format!("mathcat-{}.{}", self.current + 1, self.export_format.file_extension());
+                    download_file(&file_name, self.export_format.mime_type(), &contents);
+                    self.export_status = format!("Saved {}", file_name);
+                }
+            },
+            Msg::CopyExport => {
+                if let Some(contents) = self.export_contents() {
+                    copy_to_clipboard(&contents);
+                    self.export_status = format!("{} copied to the clipboard", self.export_format.description());
+                }
+            },
+            Msg::Overview => {
+                if !self.math_string.is_empty() {
+                    match overview(&self.settings, &self.nav_id) {
+                        Ok(text) => {
+                            self.speech = text;
+                            self.speak = true;
+                        },
+                        Err(e) => error!("{}", errors_to_string(&e)),
+                    }
+                }
+            },
+            Msg::ShowOverview => {
+                self.settings.show_overview = !self.settings.show_overview;
+                self.update_speech = true;
+            },
+            Msg::ResetClearSpeak => {
+                for preference in PREFERENCES.iter().filter(|preference| preference.is_clear_speak()) {
+                    self.settings.set_preference(preference.name, preference.default);
+                }
+                self.update_speech = true;
+            },
+            Msg::Pitch(pitch) => {
+                self.settings.pitch = pitch;
+                self.speak = true;
+            },
+            Msg::CompareChoice(kind, value) => {
+                self.comparison_choices.toggle(kind, &value);
+            },
+            Msg::Compare => {
+                if !self.math_string.is_empty() {
+                    self.comparisons = compare(&self.comparison_choices.variants(&self.settings));
+                    // put back the user's preferences (navigation uses them)
+                    if let Err(e) = self.settings.apply() {
+                        error!("{}", errors_to_string(&e));
+                    }
+                }
+            },
+            Msg::Chemistry => {
+                self.settings.chemistry = !self.settings.chemistry;
+                self.redo_expressions();   // MathCAT decides what is chemistry when it is given the MathML
+            },
             Msg::BrailleCode(text) => {
-                self.braille_code = text.to_string();
+                self.settings.braille_code = text.to_string();
                 self.update_braille = true;
             },
             Msg::BrailleDisplayAs(text) => {
-                self.braille_display_as = text.to_string();
+                self.settings.braille_display_as = text.to_string();
                 self.update_braille = true;
             },
             Msg::TTS(text) => {
-                self.tts = text.to_string();
+                self.settings.tts = text.to_string();
                 self.update_speech = true;
             },
             Msg::Dots(text) => {
-                self.braille_dots78 = text.to_string();
+                self.settings.braille_dots78 = text.to_string();
                 self.update_braille = true;
             },
             Msg::Navigate(ev) => {
@@ -355,22 +1394,33 @@ impl Component for Model {
                     get_file();     // this starts the sequence to get the file -- we will get a callback later
                 }
                 
-                if ev.key() == "Escape" {
+                if let Some(edit) = edit_key(&ev).filter(|_| self.editing) {
+                    ev.stop_propagation();
+                    ev.prevent_default();
+                    match edit {
+                        EditKey::Edit(edit) => self.apply_edit(edit),
+                        EditKey::NeedsInput(edit_input) => {
+                            self.edit_input = Some(edit_input);
+                            self.focus_edit_input = true;
+                        },
+                    }
+                } else if ev.key() == "o" && !(ev.ctrl_key() || ev.alt_key() || ev.meta_key()) {
+                    // MathCAT doesn't use letters, so this doesn't take away any navigation command
+                    ev.stop_propagation();
+                    ev.prevent_default();
+                    self.link.send_message(Msg::Overview);
+                } else if ev.key() == "Escape" {
                     remove_focus("mathml-output");
+                } else if ev.key() == "PageDown" || ev.key() == "PageUp" {
+                    // move between expressions (MathCAT doesn't use these keys)
+                    ev.stop_propagation();
+                    ev.prevent_default();
+                    self.link.send_message(if ev.key() == "PageDown" {Msg::NextExpression} else {Msg::PreviousExpression});
                 } else if VALID_NAV_KEYS.contains(&ev.key_code()) {
                     ev.stop_propagation();
                     ev.prevent_default();    
-                    match do_navigate_keypress(ev.key_code() as usize, ev.shift_key(), ev.ctrl_key(), ev.alt_key(), ev.meta_key()) {
-                        Ok(speech) => {
-                            self.speech = speech;
-                            let id_and_offset = get_navigation_mathml_id().unwrap();
-                            self.nav_id = id_and_offset.0;
-                            self.nav_offset = id_and_offset.1;
-                            highlight_nav_element(&self.nav_id, self.nav_offset);
-                            self.nav_mode = get_preference("NavMode".to_string()).unwrap();
-                            self.speak = true;
-                            self.update_braille = true;
-                        },
+                    match navigate(ev.key_code() as usize, ev.shift_key(), ev.ctrl_key(), ev.alt_key(), ev.meta_key()) {
+                        Ok(nav) => self.apply_navigation(nav),
                         Err(e) => {
                             error!("{}", errors_to_string(&e.context("Navigation failure!")));
                             self.speech = "Error in Navigation (key combo not yet implement?) -- see console log for more info".to_string()
@@ -397,46 +1447,65 @@ impl Component for Model {
             <div>
                 <h1>{get_header()}</h1>
                 <h2>{"Math Input Area"}</h2>
+                { self.view_examples_menu() }
                 <textarea id="mathml-input"  rows="5" cols="80" autocorrect="off"
-                    placeholder={INPUT_MESSAGE}>
-                    {INPUT_MESSAGE.to_string() + START_FORMULA}
+                    placeholder={INPUT_MESSAGE}
+                    oninput=self.link.callback(|_| Msg::InputChanged)>
+                    {INPUT_MESSAGE.to_string() + &self.initial_input}
                 </textarea>
                 <br />
-                <div>
+                <div id="input-buttons">
                 <input type="button" value="Generate Speech and Braille" id="render-button"
                     onclick=self.link.callback(|_| Msg::NewMathML) />
+                <input type="checkbox" id="live-update" checked={self.settings.live_update}
+                    title="Update the speech and braille while typing"
+                    onclick=self.link.callback(|_| Msg::LiveMode) />
+                <label for="live-update">{"Live update"}</label>
+                <input type="button" value="Read as Document (text and math)" id="read-document-button"
+                    onclick=self.link.callback(|_| Msg::ReadDocument) />
+                <input type="button" value="Open File (HTML, XHTML, EPUB)" id="open-file-button"
+                    title="Find all the math in a file (a file can also be dropped on the page)"
+                    onclick=self.link.callback(|_| Msg::OpenFile) />
+                <input type="button" value="Copy Link" id="copy-link-button"
+                    title="Copy a link to this input with the current settings"
+                    onclick=self.link.callback(|_| Msg::CopyLink) />
+                <span aria-live="polite" id="link-status">{&self.link_status}</span>
                 </div>
                 <h2>
                     {"Displayed Math (click to navigate, ESC to exit ["}
                     <a href="https://docs.wiris.com/en/mathplayer/navigation_commands" target="_blank" rel="noreferrer">{"nav help"}</a>
                     {"])"}
                 </h2>
+                { self.view_expression_buttons() }
+                { self.view_file_math() }
+                { self.view_input_format() }
+                { self.view_error_panel() }
                 <table role="presentation"><tr> // 2x3 table on left
                         <td>{"Navigation Mode:"}</td>
                         <td><input type="radio" id="Enhanced" name="nav_mode"
-                                checked = {self.nav_mode == "Enhanced"}
+                                checked = {self.settings.nav_mode == "Enhanced"}
                                 onclick=self.link.callback(|_| Msg::NavMode("Enhanced"))/>
                         <label for="Enhanced">{"Enhanced"}</label></td>
                         <td><input type="radio" id="Simple" name="nav_mode" value="Simple"
-                                checked = {self.nav_mode == "Simple"}                           
+                                checked = {self.settings.nav_mode == "Simple"}                           
                                 onclick=self.link.callback(|_| Msg::NavMode("Simple"))/>
                             <label for="Simple">{"Simple"}</label></td>
                         <td><input type="radio" id="Character" name="nav_mode" value="Character"
-                                checked = {self.nav_mode == "Character"}                           
+                                checked = {self.settings.nav_mode == "Character"}                           
                                 onclick=self.link.callback(|_| Msg::NavMode("Character"))/>
                             <label for="Character">{"Character"}</label></td>
                     </tr><tr>
                         <td>{"Navigation Verbosity:"}</td>
                         <td><input type="radio" id="NavTerse" name="nav_verbosity" value="Terse"
-                                checked = {self.nav_verbosity == "Terse"}
+                                checked = {self.settings.nav_verbosity == "Terse"}
                                 onclick=self.link.callback(|_| Msg::NavVerbosity("Terse"))/>
                             <label for="NavTerse">{"Terse"}</label></td>
                         <td><input type="radio" id="NavMedium" name="nav_verbosity" value="Medium"
-                                checked = {self.nav_verbosity == "Medium"}
+                                checked = {self.settings.nav_verbosity == "Medium"}
                                 onclick=self.link.callback(|_| Msg::NavVerbosity("Medium"))/>
                             <label for="NavMedium">{"Medium"}</label></td>
                         <td><input type="radio" id="NavVerbose" name="nav_verbosity" value="Verbose"
-                                checked = {self.nav_verbosity == "Verbose"}
+                                checked = {self.settings.nav_verbosity == "Verbose"}
                                 onclick=self.link.callback(|_| Msg::NavVerbosity("Verbose"))/>
                             <label for="NavVerbose">{"Verbose"}</label></td>
                     </tr></table>
@@ -444,90 +1513,124 @@ impl Component for Model {
                         onkeydown=self.link.callback(|ev| Msg::Navigate(ev))>
                     {self.display.clone()}
                 </div>
+                { self.view_edit_panel() }
                 
+                <details id="mathml-source-details">
+                    <summary>{"Canonical MathML (click on an element to navigate to it)"}</summary>
+                    <pre id="mathml-source" ref={self.source_node_ref.clone()}
+                        onclick=self.link.callback(Msg::SourceNavigate)>
+                    </pre>
+                </details>
+
                 <table id="speech-table" role="presentation">
                     <tr>     // 1x2 outside table
                         <td><h2 id="speech-heading">{"Speech"}</h2></td>
-                        <td colspan="3"><label for="Language">{"Language: "}</label>
-                            <span class="select"><select name="language" id="language">
-                            // FIX: this should search available languages
-                                <option value="en" onclick=self.link.callback(|_| Msg::Language("en"))>{"English"}</option>
-                                <option value="es" onclick=self.link.callback(|_| Msg::Language("es"))>{"Spanish"}</option>
-                                <option value="fi" onclick=self.link.callback(|_| Msg::Language("fi"))>{"Finnish"}</option>
-                                <option value="sv" onclick=self.link.callback(|_| Msg::Language("sv"))>{"Swedish"}</option>
-                                <option value="id" onclick=self.link.callback(|_| Msg::Language("id"))>{"Indonesian"}</option>
-                                <option value="vi" onclick=self.link.callback(|_| Msg::Language("vi"))>{"Vietnamese"}</option>
-                                <option value="zh-tw" onclick=self.link.callback(|_| Msg::Language("zh-tw"))>{"Chinese (TW)"}</option>
+                        <td colspan="3"><label for="language">{"Language: "}</label>
+                            <span class="select"><select name="language" id="language"
+                                onchange=self.link.batch_callback(|change| match change {
+                                    ChangeData::Select(select) => Some(Msg::Language(select.value())),
+                                    _ => None,
+                                })>
+                                { for self.languages.iter().map(|(code, name)| html! {
+                                    <option value={code.clone()} selected={*code == self.settings.language}>{name}</option>
+                                }) }
                             </select></span>
                         </td>
                     </tr><tr>
                         <td>{"Speech Style:"}</td>
                         <td><input type="radio" id="ClearSpeak" name="speech_style"
-                                checked = {self.speech_style == "ClearSpeak"}
+                                checked = {self.settings.speech_style == "ClearSpeak"}
                                 onclick=self.link.callback(|_| Msg::SpeechStyle("ClearSpeak"))/>
                         <label for="ClearSpeak">{"ClearSpeak"}</label></td>
                         <td><input type="radio" id="SimpleSpeak" name="speech_style" value="SimpleSpeak"
-                                checked = {self.speech_style == "SimpleSpeak"}                           
+                                checked = {self.settings.speech_style == "SimpleSpeak"}                           
                                 onclick=self.link.callback(|_| Msg::SpeechStyle("SimpleSpeak"))/>
                             <label for="SimpleSpeak">{"SimpleSpeak"}</label></td>
                         <td/>
                         <td class="next-group">{"TTS:"}</td>
                         <td><input type="radio" id="Off" name="tts"
-                                checked = {self.tts == "Off"}
+                                checked = {self.settings.tts == "Off"}
                                 onclick=self.link.callback(|_| Msg::TTS("Off"))/>
                             <label for="Off">{"Off"}</label></td>
                         <td><input type="radio" id="Plain" name="tts"
-                                checked = {self.tts == "None"}
+                                checked = {self.settings.tts == "None"}
                                 onclick=self.link.callback(|_| Msg::TTS("None"))/>
                             <label for="Plain">{"Plain"}</label></td>
                         <td><input type="radio" id="SSML" name="tts" value="SSML"
-                                checked = {self.tts == "SSML"}                           
+                                checked = {self.settings.tts == "SSML"}                           
                                 onclick=self.link.callback(|_| Msg::TTS("SSML"))/>
                             <label for="SSML">{"SSML"}</label></td>
                     </tr><tr>
                         <td>{"Speech Verbosity:"}</td>
                         <td><input type="radio" id="Terse" name="verbosity" value="Terse"
-                                checked = {self.verbosity == "Terse"}
+                                checked = {self.settings.verbosity == "Terse"}
                                 onclick=self.link.callback(|_| Msg::SpeechVerbosity("Terse"))/>
                             <label for="Terse">{"Terse"}</label></td>
                         <td><input type="radio" id="Medium" name="verbosity" value="Medium"
-                                checked = {self.verbosity == "Medium"}
+                                checked = {self.settings.verbosity == "Medium"}
                                 onclick=self.link.callback(|_| Msg::SpeechVerbosity("Medium"))/>
                             <label for="Medium">{"Medium"}</label></td>
                         <td><input type="radio" id="Verbose" name="verbosity" value="Verbose"
-                                checked = {self.verbosity == "Verbose"}
+                                checked = {self.settings.verbosity == "Verbose"}
                                 onclick=self.link.callback(|_| Msg::SpeechVerbosity("Verbose"))/>
                             <label for="Verbose">{"Verbose"}</label></td>
                         <td><label for="Cap" class="next-group">{"Say \"cap\""}</label></td>
                         <td><input type="checkbox" id="Cap" name="say-cap"
-                                checked = {self.say_caps}
+                                checked = {self.settings.say_caps}
                                 onclick=self.link.callback(|_| Msg::SayCaps("ignored"))/>
                         </td>
+                        <td><label for="Chemistry" class="next-group">{"Chemistry"}</label></td>
+                        <td><input type="checkbox" id="Chemistry" name="chemistry"
+                                title="Speak chemical formulas and reactions as chemistry (turned on by \\ce{...})"
+                                checked = {self.settings.chemistry}
+                                onclick=self.link.callback(|_| Msg::Chemistry)/>
+                        </td>
                     </tr>
                 </table>
+                { if self.settings.show_overview && !self.overview.is_empty() {
+                    html! {
+                        <textarea id="overview" aria-label="Overview" readonly=true rows="2" cols="80" autocorrect="off">
+                            {&self.overview}
+                        </textarea>
+                    }
+                } else {
+                    html! {}
+                } }
                 <textarea role="application" id="speech" aria-labelledby="speech-heading" readonly=true rows="3" cols="80" data-hint="" autocorrect="off">
                     {&self.speech}
                 </textarea>
+                <div id="overview-controls">
+                    <input type="button" value="Overview (O)" id="overview-button"
+                        title="Speak a short summary of the expression (or of the part being navigated)"
+                        disabled={self.math_string.is_empty()}
+                        onclick=self.link.callback(|_| Msg::Overview) />
+                    <input type="checkbox" id="show-overview" checked={self.settings.show_overview}
+                        onclick=self.link.callback(|_| Msg::ShowOverview) />
+                    <label for="show-overview">{"Show the overview with the full speech"}</label>
+                </div>
+                { self.view_clear_speak_panel() }
+                { self.view_speech_rate_controls() }
+                { self.view_ssml_inspector() }
                 <h2 id="braille-heading">{"Braille"}</h2>
                 <table role="presentation"><tr>     // 1x2 outside table
                     <td><table role="presentation"><tr>
                         <td>{"Braille Settings:"}</td>
                         <td><input type="radio" id="Nemeth" name="braille_setting"
-                                checked = {self.braille_code == "Nemeth"}
+                                checked = {self.settings.braille_code == "Nemeth"}
                                 onclick=self.link.callback(|_| Msg::BrailleCode("Nemeth"))/>
                             <label for="Nemeth">{"Nemeth"}</label></td>
                         <td><input type="radio" id="UEB" name="braille_setting" value="UEB"
-                                checked = {self.braille_code == "UEB"}
+                                checked = {self.settings.braille_code == "UEB"}
                                 onclick=self.link.callback(|_| Msg::BrailleCode("UEB"))/>
                             <label for="UEB">{"UEB"}</label></td>
                     </tr><tr>
                         <td>{"View Braille As:"}</td>
                         <td><input type="radio" id="Dots" name="view_braille_as" value="Dots"
-                                checked = {self.braille_display_as == "Dots"}
+                                checked = {self.settings.braille_display_as == "Dots"}
                                 onclick=self.link.callback(|_| Msg::BrailleDisplayAs("Dots"))/>
                             <label for="Dots">{"Dots"}</label></td>
                         <td><input type="radio" id="ASCIIBraille" name="view_braille_as" value="ASCIIBraille"
-                                checked = {self.braille_display_as == "ASCIIBraille"}
+                                checked = {self.settings.braille_display_as == "ASCIIBraille"}
                                 onclick=self.link.callback(|_| Msg::BrailleDisplayAs("ASCIIBraille"))/>
                                 <label for="ASCIIBraille">{"ASCIIBraille"}</label>
                         </td>
@@ -537,19 +1640,19 @@ impl Component for Model {
                         </tr> <tr>
                         <td>{"\u{A0}\u{A0}\u{A0}Navigation Indicator:"}</td>
                         <td><input type="radio" id="DotsOff" name="dots-78"
-                                checked = {self.braille_dots78 == "Off"}
+                                checked = {self.settings.braille_dots78 == "Off"}
                                 onclick=self.link.callback(|_| Msg::Dots("Off"))/>
                             <label for="DotsOff">{"Off"}</label></td>
                         <td><input type="radio" id="DotsFirstChar" name="dots-78"
-                                checked = {self.braille_dots78 == "FirstChar"}
+                                checked = {self.settings.braille_dots78 == "FirstChar"}
                                 onclick=self.link.callback(|_| Msg::Dots("FirstChar"))/>
                             <label for="DotsFirstChar">{"FirstChar"}</label></td>
                         <td><input type="radio" id="DotsEndPoints" name="dots-78" value="EndPoints"
-                                checked = {self.braille_dots78 == "EndPoints"}                           
+                                checked = {self.settings.braille_dots78 == "EndPoints"}                           
                                 onclick=self.link.callback(|_| Msg::Dots("EndPoints"))/>
                             <label for="DotsEndPoints">{"EndPoints"}</label></td>
                         <td><input type="radio" id="DotsAll" name="dots-78" value="All"
-                                checked = {self.braille_dots78 == "All"}                           
+                                checked = {self.settings.braille_dots78 == "All"}                           
                                 onclick=self.link.callback(|_| Msg::Dots("All"))/>
                             <label for="DotsAll">{"All"}</label></td>
                     </tr> </table></td>
@@ -557,6 +1660,9 @@ impl Component for Model {
                 <div role="region" aria-labelledby="braille-heading" id="braille" readonly=true rows="2" cols="80" data-hint="" autocorrect="off"
                     ref={self.braille_node_ref.clone()}>
                 </div>
+                { self.view_export_controls() }
+                { self.view_comparison_panel() }
+                { self.view_preferences_panel() }
                 <p>
                   <a href="https://github.com/NSoiffer/MathCAT/issues" target="_blank" rel="noreferrer">{"Please report bugs here."}</a>
                 </p>
@@ -564,21 +1670,89 @@ impl Component for Model {
         }
     }
 
-    fn rendered(&mut self, _first_render: bool) {
+    fn rendered(&mut self, first_render: bool) {
+        if first_render && self.render_initial_input {
+            self.link.send_message(Msg::NewMathML);
+        }
+
         // this allows for bolding of chars in the braille ASCII display
         let el = self.braille_node_ref.cast::<Element>().unwrap();
         el.set_inner_html(&self.braille);
+
+        // the source is only replaced when the MathML changes so that the pane doesn't lose its scroll position
+        if self.source_mathml != self.math_string {
+            self.source_mathml = self.math_string.clone();
+            let html = if self.math_string.is_empty() {
+                Ok( String::default() )
+            } else {
+                mathml_source_html(&self.math_string)
+            };
+            let el = self.source_node_ref.cast::<Element>().unwrap();
+            match html {
+                Ok(html) => el.set_inner_html(&html),
+                Err(e) => el.set_text_content(Some(&e.to_string())),
+            }
+        }
+        highlight_source_element(&self.nav_id);
+
+        // like the source, the SSML is only replaced when it changes
+        if let Some(el) = self.ssml_node_ref.cast::<Element>() {
+            let ssml = if self.ssml_inspection.is_some() {self.speech.as_str()} else {""};
+            if self.ssml_shown != ssml {
+                self.ssml_shown = ssml.to_string();
+                el.set_inner_html(self.ssml_inspection.as_ref().map_or("", |inspection| inspection.html.as_str()));
+            }
+        }
+
+        if self.focus_edit_input {
+            self.focus_edit_input = false;
+            if let Some(input) = self.edit_input_ref.cast::<HtmlInputElement>() {
+                input.focus().unwrap();
+            }
+        }
+    }
+}
+
+/// Move the keyboard focus to the element with id `id`
+fn focus_element(id: &str) {
+    if let Some(element) = yew::utils::document().get_element_by_id(id) {
+        element.unchecked_into::<HtmlElement>().focus().unwrap();
     }
 }
 
+/// Show the error inline: the math with the bad part (if known) marked. The error panel has the details.
+fn error_to_html(error: &PipelineError, math: &MathSource) -> Element {
+    let document = yew::utils::document();
+    let span = document.create_element("span").unwrap();
+    span.set_class_name("error-source");
+    match error.split_source(math) {
+        None => span.set_text_content(Some(&math.text)),
+        Some( (before, bad, after) ) => {
+            span.set_text_content(Some(&before));
+            let mark = document.create_element("mark").unwrap();
+            // an error at the end of the input has nothing to mark, so mark a space
+            mark.set_text_content(Some(if bad.is_empty() {" "} else {&bad}));
+            span.append_with_node_1(&mark).unwrap();
+            span.append_with_str_1(&after).unwrap();
+        },
+    }
+    return span;
+}
+
 #[wasm_bindgen]
 extern "C" {
-    #[wasm_bindgen(js_name = "ConvertToMathML")]
-    pub fn string_to_mathml(mathml: &str, math_format: &str) -> String;
-
     #[wasm_bindgen(js_name = "ConvertToCHTML")]
     pub fn mathml_to_chtml(mathml: String) -> Element;
 
+    #[wasm_bindgen(js_name = "GetURL")]
+    pub fn get_url() -> String;
+
+    #[wasm_bindgen(js_name = "CopyToClipboard")]
+    pub fn copy_to_clipboard(text: &str);
+
+    #[wasm_bindgen(js_name = "DownloadFile")]
+    pub fn download_file(file_name: &str, mime_type: &str, contents: &str);
+
     #[wasm_bindgen(js_name = "GetTextOfElement")]
     pub fn get_text_of_element(id: &str) -> String;
     // This is needed because .get_element_by_id("mathml-input") fails in the following code when used where this is called
@@ -588,17 +1762,26 @@ extern "C" {
     //         .expect("element with id `mathml-input` not present")   // this fails (???)
     //     .unchecked_into::<HtmlElement>();
 
+    #[wasm_bindgen(js_name = "SetTextOfElement")]
+    pub fn set_text_of_element(id: &str, text: &str);
+
     #[wasm_bindgen(js_name = "SpeakText")]
-    pub fn speak_text(text: &str, lang: &str);
+    pub fn speak_text(text: &str, lang: &str, rate: &str, pitch: &str);
 
     #[wasm_bindgen(js_name = "HighlightNavigationElement")]
     pub fn highlight_nav_element(text: &str, offset: usize);
 
+    #[wasm_bindgen(js_name = "HighlightSourceElement")]
+    pub fn highlight_source_element(id: &str);
+
     #[wasm_bindgen(js_name = "RemoveFocus")]
     pub fn remove_focus(text: &str);
 
     #[wasm_bindgen(js_name = "GetFile")]
     pub fn get_file();
+
+    #[wasm_bindgen(js_name = "OpenMathFile")]
+    pub fn open_math_file();
     
     #[wasm_bindgen(js_name = "SetCookie")]
     pub fn set_cookie(new_cookie: &str) -> String;
@@ -615,6 +1798,30 @@ pub fn load_yaml_file(file_name: &str, contents: &str) {
     libmathcat::shim_filesystem::override_file_for_debugging_rules(&file_path, contents);
 }
 
+/// Called with the name and contents of an opened file
+type FileLoadedCallback = Callback<(String, Vec<u8>)>;
+
+thread_local! {
+    /// Sends the model a `Msg::FileLoaded` with the file given to `load_math_file` (set when the model is created)
+    static FILE_LOADED: RefCell<Option<FileLoadedCallback>> = const { RefCell::new(None) };
+}
+
+/// Called (from JS) when something is pasted into the input area: returns the Office Math (OMML) in `html`
+/// (the HTML version of what was pasted) with Office's HTML formatting removed, or an empty string if there is none.
+#[wasm_bindgen]
+pub fn omml_in_pasted_html(html: &str) -> String {
+    return math_cat_demo::omml::find_omml(html).join("\n");
+}
+
+/// Called (from JS) with a file the user opened or dropped on the page -- the model gets it in a `Msg::FileLoaded`.
+#[wasm_bindgen]
+pub fn load_math_file(file_name: &str, contents: Vec<u8>) {
+    FILE_LOADED.with(|callback| match callback.borrow().as_ref() {
+        Some(callback) => callback.emit( (file_name.to_string(), contents) ),
+        None => error!("'{}' was opened before the page was ready", file_name),
+    });
+}
+
 fn main() {
     init_log();
     yew::start_app::<Model>();
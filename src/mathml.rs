@@ -0,0 +1,151 @@
+//! A simple tree for building MathML.
+//! The converters (TeX, ...) build a tree of `MathNode`s and then turn it into a MathML string.
+//! The editor goes the other way: it parses MathML into a tree, changes it, and turns it back into a string.
+use anyhow::Result;
+use crate::convert::ParseError;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MathNode {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    /// Only used for token elements (mi, mn, mo, mtext, ...)
+    pub text: String,
+    pub children: Vec<MathNode>,
+}
+
+static TOKEN_ELEMENTS: &[&str] = &["mi", "mn", "mo", "mtext", "ms", "mspace", "none", "mprescripts", "malignmark"];
+
+impl MathNode {
+    /// A token element such as `<mi>x</mi>`
+    pub fn token(name: &str, text: &str) -> MathNode {
+        return MathNode { name: name.to_string(), text: text.to_string(), ..Default::default() };
+    }
+
+    pub fn mi(text: &str) -> MathNode {
+        return MathNode::token("mi", text);
+    }
+
+    pub fn mn(text: &str) -> MathNode {
+        return MathNode::token("mn", text);
+    }
+
+    pub fn mo(text: &str) -> MathNode {
+        return MathNode::token("mo", text);
+    }
+
+    pub fn mtext(text: &str) -> MathNode {
+        return MathNode::token("mtext", text);
+    }
+
+    /// An element with children, such as `<mfrac>...</mfrac>`
+    pub fn new(name: &str, children: Vec<MathNode>) -> MathNode {
+        return MathNode { name: name.to_string(), children, ..Default::default() };
+    }
+
+    /// An `mrow` unless there is only a single child, in which case that child is returned
+    pub fn mrow(mut children: Vec<MathNode>) -> MathNode {
+        if children.len() == 1 {
+            return children.pop().unwrap();
+        }
+        return MathNode::new("mrow", children);
+    }
+
+    /// Add an attribute (builder style)
+    pub fn attr(mut self, name: &str, value: &str) -> MathNode {
+        self.set_attr(name, value);
+        return self;
+    }
+
+    pub fn set_attr(&mut self, name: &str, value: &str) {
+        match self.attrs.iter_mut().find(|(n, _)| n == name) {
+            Some(attr) => attr.1 = value.to_string(),
+            None => self.attrs.push( (name.to_string(), value.to_string()) ),
+        }
+    }
+
+    pub fn get_attr(&self, name: &str) -> Option<&str> {
+        return self.attrs.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+    }
+
+    pub fn is(&self, name: &str) -> bool {
+        return self.name == name;
+    }
+
+    pub fn is_token(&self) -> bool {
+        return TOKEN_ELEMENTS.contains(&self.name.as_str());
+    }
+
+    /// Serialize the tree as MathML
+    pub fn to_mathml(&self) -> String {
+        let mut result = String::with_capacity(256);
+        self.write_mathml(&mut result);
+        return result;
+    }
+
+    fn write_mathml(&self, result: &mut String) {
+        result.push('<');
+        result.push_str(&self.name);
+        for (name, value) in &self.attrs {
+            result.push_str(&format!(" {}=\"{}\"", name, escape_xml(value)));
+        }
+        if self.children.is_empty() && self.text.is_empty() {
+            result.push_str("/>");
+            return;
+        }
+        result.push('>');
+        if self.is_token() {
+            result.push_str(&escape_xml(&self.text));
+        } else {
+            for child in &self.children {
+                child.write_mathml(result);
+            }
+        }
+        result.push_str("</");
+        result.push_str(&self.name);
+        result.push('>');
+    }
+}
+
+/// Parse `mathml` into a tree. Namespace prefixes are dropped and whitespace between elements is ignored.
+pub fn parse_mathml(mathml: &str) -> Result<MathNode> {
+    let document = match roxmltree::Document::parse(mathml) {
+        Ok(document) => document,
+        Err(e) => return Err( ParseError::from_xml_error("The MathML isn't valid XML", &e, mathml, 0).into() ),
+    };
+    return Ok( to_math_node(document.root_element()) );
+}
+
+fn to_math_node(node: roxmltree::Node) -> MathNode {
+    let mut math_node = MathNode::new(node.tag_name().name(), vec![]);
+    math_node.attrs = node.attributes().map(|attr| (attr.name().to_string(), attr.value().to_string())).collect();
+    if math_node.is_token() {
+        math_node.text = node.children().filter_map(|child| child.text()).collect::<String>().trim().to_string();
+    } else {
+        math_node.children = node.children().filter(|child| child.is_element()).map(to_math_node).collect();
+    }
+    return math_node;
+}
+
+/// Wrap `children` in a `math` element and serialize it
+pub fn math_to_string(children: Vec<MathNode>, display_block: bool) -> String {
+    let mut math = MathNode::new("math", children);
+    if display_block {
+        math.set_attr("display", "block");
+    }
+    return math.to_mathml();
+}
+
+/// Escape the chars that can't appear in XML text or attribute values
+pub fn escape_xml(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            _ => result.push(ch),
+        }
+    }
+    return result;
+}
@@ -0,0 +1,455 @@
+//! Convert mhchem's `\ce{...}` (and `\pu{...}`) notation to MathML.
+//!
+//! This covers the common parts of mhchem: chemical formulas with counts and charges, stoichiometric coefficients,
+//! states such as `(aq)`, isotopes (`^{14}_{6}C`), hydrates (`CuSO4*5H2O`), bonds, and reaction arrows
+//! with optional text above and below (`->[heat]`).
+//! Elements are upright (mathvariant='normal') so that MathCAT recognizes the expression as chemistry.
+use anyhow::Result;
+use regex::Regex;
+use crate::mathml::MathNode;
+use crate::convert::ParseError;
+use crate::tex::parse_tex_at;
+
+/// Arrows (longest first so that prefixes don't match first)
+static ARROWS: &[(&str, &str)] = &[
+    ("<=>>", "⇌"), ("<<=>", "⇌"), ("<-->", "⇄"), ("<=>", "⇌"), ("<->", "↔"), ("->", "→"), ("<-", "←"),
+];
+
+static STATES: &[&str] = &["aq", "s", "l", "g", "cr", "sln"];
+
+/// True if `input` has any `\ce{...}` (i.e., is chemistry)
+pub fn contains_mhchem(input: &str) -> bool {
+    lazy_static! {
+        static ref CE: Regex = Regex::new(r"\\ce\s*\{").unwrap();
+    };
+    return CE.is_match(input);
+}
+
+/// Parse the contents of `\ce{...}`. `offset` is the position of `text` in the original input (for errors).
+pub fn parse_ce(text: &str, offset: usize) -> Result<Vec<MathNode>> {
+    let mut nodes = vec![];
+    for (start, word) in split_words(text, offset)? {
+        if word == "+" {
+            nodes.push(MathNode::mo("+"));
+        } else if word == "^" {
+            nodes.push(MathNode::mo("↑"));      // gas
+        } else if word == "v" {
+            nodes.push(MathNode::mo("↓"));      // precipitate
+        } else if let Some(&(arrow_text, arrow)) = ARROWS.iter().find(|(arrow_text, _)| word.starts_with(arrow_text)) {
+            nodes.push(parse_arrow(arrow, &word[arrow_text.len()..], start + arrow_text.len())?);
+        } else {
+            nodes.append(&mut parse_formula(word, start)?);
+        }
+    }
+    return Ok(nodes);
+}
+
+/// Parse the contents of `\pu{...}` (a number followed by units)
+pub fn parse_pu(text: &str, offset: usize) -> Result<Vec<MathNode>> {
+    let text = text.trim();
+    let (number, units) = text.split_at(number_length(text));
+    let mut nodes = vec![];
+    if !number.is_empty() {
+        nodes.push(MathNode::mn(number));
+    }
+    let units = units.trim();
+    if !units.is_empty() {
+        if !number.is_empty() {
+            nodes.push(MathNode::new("mspace", vec![]).attr("width", "0.167em"));
+        }
+        for (i, unit) in units.split_whitespace().enumerate() {
+            if i > 0 {
+                nodes.push(MathNode::mo("⋅"));
+            }
+            nodes.push(parse_unit(unit, offset)?);
+        }
+    }
+    return Ok(nodes);
+}
+
+/// The length of the number at the start of `text` (e.g., "-1.5e-3"). It must have at least one digit.
+/// An 'e' is only part of the number when it is an exponent (a digit is before it and a digit or sign after it),
+/// so that units such as "eV" aren't taken as part of the number.
+fn number_length(text: &str) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        if ch.is_ascii_digit() || ch == '.' || (ch == '-' && i == 0) {
+            i += 1;
+        } else if ch == 'e' && i > 0 && chars[i-1].is_ascii_digit() &&
+                  chars.get(i+1).is_some_and(|&next| next.is_ascii_digit() || next == '-' || next == '+') {
+            i += 2;     // the sign (or first digit) of the exponent is part of the number
+        } else {
+            break;
+        }
+    }
+    if !chars[..i].iter().any(|ch| ch.is_ascii_digit()) {
+        return 0;   // e.g., "-" or "." on its own
+    }
+    return chars[..i].iter().map(|ch| ch.len_utf8()).sum();
+}
+
+/// A unit such as "kJ" or "mol^-1"
+fn parse_unit(unit: &str, offset: usize) -> Result<MathNode> {
+    return Ok( match unit.split_once('^') {
+        None => MathNode::mi(unit).attr("mathvariant", "normal"),
+        Some((base, power)) => MathNode::new("msup", vec![
+            MathNode::mi(base).attr("mathvariant", "normal"),
+            MathNode::mrow(parse_tex_at(power.trim_start_matches('{').trim_end_matches('}'), offset)?),
+        ]),
+    });
+}
+
+/// Split `text` at white space, but not inside of `[...]`, `{...}`, or `$...$`.
+/// The words are returned with their position in the original input.
+fn split_words(text: &str, offset: usize) -> Result<Vec<(usize, &str)>> {
+    let mut words = vec![];
+    let mut depth = 0;
+    let mut in_math = false;
+    let mut word_start = None;
+    let mut chars_before = 0;       // char count (not byte count) to report positions
+    let mut word_char_start = 0;
+    for (i, ch) in text.char_indices() {
+        match ch {
+            '[' | '{' if !in_math => depth += 1,
+            ']' | '}' if !in_math => depth -= 1,
+            '$' => in_math = !in_math,
+            _ => (),
+        }
+        if ch.is_whitespace() && depth == 0 && !in_math {
+            if let Some(start) = word_start.take() {
+                words.push( (offset + word_char_start, &text[start..i]) );
+            }
+        } else if word_start.is_none() {
+            word_start = Some(i);
+            word_char_start = chars_before;
+        }
+        chars_before += 1;
+    }
+    if depth != 0 || in_math {
+        return Err( ParseError::new("Unbalanced brackets or '$' in \\ce", offset, offset + chars_before).into() );
+    }
+    if let Some(start) = word_start {
+        words.push( (offset + word_char_start, &text[start..]) );
+    }
+    return Ok(words);
+}
+
+/// An arrow with optional `[above]` and `[below]` text
+fn parse_arrow(arrow: &str, rest: &str, offset: usize) -> Result<MathNode> {
+    let arrow = MathNode::mo(arrow).attr("stretchy", "true");
+    let mut annotations = vec![];
+    let mut rest = rest;
+    let mut offset = offset;
+    while rest.starts_with('[') {
+        let end = match rest.find(']') {
+            None => return Err( ParseError::new("Missing ']' after arrow", offset, offset + rest.chars().count()).into() ),
+            Some(end) => end,
+        };
+        annotations.push(parse_annotation(&rest[1..end], offset + 1)?);
+        offset += rest[..end+1].chars().count();
+        rest = &rest[end+1..];
+    }
+    if !rest.is_empty() {
+        return Err( ParseError::new(&format!("Unexpected '{}' after arrow", rest), offset, offset + rest.chars().count()).into() );
+    }
+    let mut annotations = annotations.into_iter();
+    return Ok( match (annotations.next(), annotations.next()) {
+        (None, _) => arrow,
+        (Some(above), None) => MathNode::new("mover", vec![arrow, above]),
+        (Some(above), Some(below)) => MathNode::new("munderover", vec![arrow, below, above]),
+    });
+}
+
+/// Text above/below an arrow: math (`$...$`), plain words, or a chemical formula
+fn parse_annotation(text: &str, offset: usize) -> Result<MathNode> {
+    if text.starts_with('$') && text.ends_with('$') && text.len() > 1 {
+        return Ok( MathNode::mrow(parse_tex_at(&text[1..text.len()-1], offset + 1)?) );
+    }
+    if text.chars().all(|ch| ch.is_lowercase() || ch.is_whitespace()) {
+        return Ok( MathNode::mtext(text) );
+    }
+    return Ok( MathNode::mrow(parse_ce(text, offset)?) );
+}
+
+fn element(name: &str) -> MathNode {
+    return MathNode::mi(name).attr("mathvariant", "normal");
+}
+
+/// Parse a formula such as "2H2O", "SO4^2-", "Fe^{3+}", "NaCl(aq)", or "CuSO4*5H2O"
+fn parse_formula(word: &str, offset: usize) -> Result<Vec<MathNode>> {
+    let chars: Vec<char> = word.chars().collect();
+    let error = |start: usize, message: &str| -> anyhow::Error {
+        ParseError::new(message, offset + start, offset + chars.len()).into()
+    };
+    let mut nodes: Vec<MathNode> = vec![];
+    let mut i = 0;
+    let mut at_start = true;     // a number at the start is a coefficient, not a count
+    while i < chars.len() {
+        let ch = chars[i];
+        if at_start {
+            at_start = false;
+            if ch.is_ascii_digit() {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '/' || chars[i] == '.') {
+                    i += 1;
+                }
+                let coefficient: String = chars[start..i].iter().collect();
+                nodes.push( match coefficient.split_once('/') {
+                    Some((num, den)) => MathNode::new("mfrac", vec![MathNode::mn(num), MathNode::mn(den)]),
+                    None => MathNode::mn(&coefficient),
+                });
+                continue;
+            }
+            if (ch == '^' || ch == '_') && i + 1 < chars.len() {
+                // isotope: ^{14}_{6}C
+                let mut pre_sub = MathNode::new("none", vec![]);
+                let mut pre_sup = MathNode::new("none", vec![]);
+                while i < chars.len() && (chars[i] == '^' || chars[i] == '_') {
+                    let is_sup = chars[i] == '^';
+                    let (script, next) = read_script(&chars, i + 1);
+                    let script = MathNode::mn(&script);
+                    if is_sup {pre_sup = script} else {pre_sub = script};
+                    i = next;
+                }
+                if i >= chars.len() || !chars[i].is_ascii_uppercase() {
+                    return Err(error(i, "Missing element after isotope numbers"));
+                }
+                let (name, next) = read_element(&chars, i);
+                i = next;
+                nodes.push( MathNode::new("mmultiscripts", vec![
+                    element(&name), MathNode::new("mprescripts", vec![]), pre_sub, pre_sup
+                ]));
+                continue;
+            }
+        }
+
+        if ch.is_ascii_uppercase() {
+            let (name, next) = read_element(&chars, i);
+            nodes.push(element(&name));
+            i = next;
+        } else if ch.is_ascii_digit() {
+            // count
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let count: String = chars[start..i].iter().collect();
+            if nodes.is_empty() {
+                return Err(error(start, "Number without an element"));
+            }
+            attach_script(&mut nodes, MathNode::mn(&count), false);
+        } else if ch == '^' {
+            let (charge, next) = read_script(&chars, i + 1);
+            if charge.is_empty() {
+                nodes.push(MathNode::mo("↑"));
+            } else {
+                attach_script(&mut nodes, charge_node(&charge), true);
+            }
+            i = next;
+        } else if (ch == '+' || ch == '-') && (i + 1 == chars.len() || chars[i+1] == '(') && !nodes.is_empty() {
+            // charge at the end of a formula (e.g., "Na+")
+            attach_script(&mut nodes, charge_node(&ch.to_string()), true);
+            i += 1;
+        } else if ch == '(' {
+            let state_end = chars[i+1..].iter().position(|&ch| ch == ')').map(|end| i + 1 + end);
+            let state: Option<String> = state_end.map(|end| chars[i+1..end].iter().collect());
+            match state {
+                Some(state) if STATES.contains(&state.as_str()) => {
+                    nodes.push( MathNode::new("mrow", vec![MathNode::mo("("), element(&state), MathNode::mo(")")]) );
+                    i = state_end.unwrap() + 1;
+                },
+                _ => {
+                    nodes.push(MathNode::mo("("));
+                    i += 1;
+                },
+            }
+        } else if ch == '*' || ch == '.' || ch == '·' {
+            // hydrate, etc.
+            nodes.push(MathNode::mo("⋅"));
+            at_start = true;
+            i += 1;
+        } else if ch == '{' {
+            let end = chars[i..].iter().position(|&ch| ch == '}').map(|end| i + end)
+                        .ok_or_else(|| error(i, "Missing '}'"))?;
+            let group: String = chars[i+1..end].iter().collect();
+            nodes.append(&mut parse_formula(&group, offset + i + 1)?);
+            i = end + 1;
+        } else if ch == '$' {
+            let end = chars[i+1..].iter().position(|&ch| ch == '$').map(|end| i + 1 + end)
+                        .ok_or_else(|| error(i, "Missing '$'"))?;
+            let math: String = chars[i+1..end].iter().collect();
+            nodes.append(&mut parse_tex_at(&math, offset + i + 1)?);
+            i = end + 1;
+        } else {
+            nodes.push( match ch {
+                '-' => MathNode::mo("−"),        // single bond
+                '#' => MathNode::mo("≡"),        // triple bond
+                'v' if i + 1 == chars.len() => MathNode::mo("↓"),
+                _ if ch.is_alphabetic() => MathNode::mi(&ch.to_string()),
+                _ => MathNode::mo(&ch.to_string()),
+            });
+            i += 1;
+        }
+    }
+    return Ok(nodes);
+}
+
+/// An element is an upper case letter followed by lower case letters
+fn read_element(chars: &[char], start: usize) -> (String, usize) {
+    let mut i = start + 1;
+    while i < chars.len() && chars[i].is_ascii_lowercase() {
+        i += 1;
+    }
+    return (chars[start..i].iter().collect(), i);
+}
+
+/// Read a script that follows '^' or '_': either `{...}` or a run of digits optionally followed by a sign
+fn read_script(chars: &[char], start: usize) -> (String, usize) {
+    if chars.get(start) == Some(&'{') {
+        if let Some(end) = chars[start..].iter().position(|&ch| ch == '}') {
+            return (chars[start+1..start+end].iter().collect(), start + end + 1);
+        }
+    }
+    let mut i = start;
+    while i < chars.len() && chars[i].is_ascii_digit() {
+        i += 1;
+    }
+    if i < chars.len() && (chars[i] == '+' || chars[i] == '-') {
+        i += 1;
+    }
+    return (chars[start..i].iter().collect(), i);
+}
+
+/// A charge such as "2+" or "-"
+fn charge_node(charge: &str) -> MathNode {
+    let charge = charge.replace('-', "−");
+    let split = charge.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(charge.len());
+    let (number, sign) = charge.split_at(split);
+    return match (number.is_empty(), sign.is_empty()) {
+        (true, _) => MathNode::mo(sign),
+        (false, true) => MathNode::mn(number),
+        (false, false) => MathNode::new("mrow", vec![MathNode::mn(number), MathNode::mo(sign)]),
+    };
+}
+
+/// Attach a subscript (count) or superscript (charge) to the last node
+fn attach_script(nodes: &mut Vec<MathNode>, script: MathNode, is_superscript: bool) {
+    let base = match nodes.pop() {
+        None => MathNode::new("mrow", vec![]),
+        Some(base) => base,
+    };
+    let node = if is_superscript {
+        if base.is("msub") {
+            let mut children = base.children;
+            children.push(script);
+            MathNode::new("msubsup", children)
+        } else {
+            MathNode::new("msup", vec![base, script])
+        }
+    } else {
+        MathNode::new("msub", vec![base, script])
+    };
+    nodes.push(node);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::tex_to_mathml;
+
+    /// The MathML for `tex` without the `<math>` wrapper
+    fn mathml(tex: &str) -> String {
+        let mathml = tex_to_mathml(tex).unwrap();
+        return mathml.trim_start_matches("<math display=\"block\">").trim_end_matches("</math>").to_string();
+    }
+
+    #[test]
+    fn formula() {
+        assert_eq!(mathml(r"\ce{H2O}"),
+            "<mrow><msub><mi mathvariant=\"normal\">H</mi><mn>2</mn></msub><mi mathvariant=\"normal\">O</mi></mrow>");
+    }
+
+    #[test]
+    fn reaction() {
+        assert_eq!(mathml(r"\ce{A -> B}"),
+            "<mrow><mi mathvariant=\"normal\">A</mi><mo stretchy=\"true\">→</mo><mi mathvariant=\"normal\">B</mi></mrow>");
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(mathml(r"\pu{eV}"), "<mi mathvariant=\"normal\">eV</mi>");
+        assert_eq!(mathml(r"\pu{3 eV}"),
+            "<mrow><mn>3</mn><mspace width=\"0.167em\"/><mi mathvariant=\"normal\">eV</mi></mrow>");
+        assert_eq!(mathml(r"\pu{1.5e-3 mol}"),
+            "<mrow><mn>1.5e-3</mn><mspace width=\"0.167em\"/><mi mathvariant=\"normal\">mol</mi></mrow>");
+        // a sign on its own isn't a number
+        assert_eq!(mathml(r"\pu{-}"), "<mi mathvariant=\"normal\">-</mi>");
+        assert_eq!(mathml(r"\pu{-5 K}"),
+            "<mrow><mn>-5</mn><mspace width=\"0.167em\"/><mi mathvariant=\"normal\">K</mi></mrow>");
+    }
+
+    #[test]
+    fn charges() {
+        assert_eq!(mathml(r"\ce{SO4^2-}"),
+            "<mrow><mi mathvariant=\"normal\">S</mi>\
+                <msubsup><mi mathvariant=\"normal\">O</mi><mn>4</mn><mrow><mn>2</mn><mo>−</mo></mrow></msubsup></mrow>");
+        assert_eq!(mathml(r"\ce{Na+}"), "<msup><mi mathvariant=\"normal\">Na</mi><mo>+</mo></msup>");
+    }
+
+    #[test]
+    fn isotopes() {
+        assert_eq!(mathml(r"\ce{^{14}_{6}C}"),
+            "<mmultiscripts><mi mathvariant=\"normal\">C</mi><mprescripts/><mn>6</mn><mn>14</mn></mmultiscripts>");
+    }
+
+    #[test]
+    fn hydrates() {
+        assert_eq!(mathml(r"\ce{CuSO4*5H2O}"),
+            "<mrow><mi mathvariant=\"normal\">Cu</mi><mi mathvariant=\"normal\">S</mi>\
+                <msub><mi mathvariant=\"normal\">O</mi><mn>4</mn></msub><mo>⋅</mo><mn>5</mn>\
+                <msub><mi mathvariant=\"normal\">H</mi><mn>2</mn></msub><mi mathvariant=\"normal\">O</mi></mrow>");
+    }
+
+    #[test]
+    fn states() {
+        assert_eq!(mathml(r"\ce{NaCl(aq)}"),
+            "<mrow><mi mathvariant=\"normal\">Na</mi><mi mathvariant=\"normal\">Cl</mi>\
+                <mrow><mo>(</mo><mi mathvariant=\"normal\">aq</mi><mo>)</mo></mrow></mrow>");
+    }
+
+    #[test]
+    fn arrow_text() {
+        assert_eq!(mathml(r"\ce{A ->[heat] B}"),
+            "<mrow><mi mathvariant=\"normal\">A</mi><mover><mo stretchy=\"true\">→</mo><mtext>heat</mtext></mover>\
+                <mi mathvariant=\"normal\">B</mi></mrow>");
+        assert_eq!(mathml(r"\ce{A <=>[above][below] B}"),
+            "<mrow><mi mathvariant=\"normal\">A</mi>\
+                <munderover><mo stretchy=\"true\">⇌</mo><mtext>below</mtext><mtext>above</mtext></munderover>\
+                <mi mathvariant=\"normal\">B</mi></mrow>");
+    }
+
+    #[test]
+    fn unbalanced_brackets() {
+        let e = tex_to_mathml(r"\ce{x [y}").unwrap_err();
+        let e = e.downcast_ref::<ParseError>().expect("a ParseError");
+        assert_eq!( (e.start, e.end, e.message.as_str()), (4, 8, "Unbalanced brackets or '$' in \\ce") );
+    }
+
+    #[test]
+    fn number_lengths() {
+        assert_eq!(number_length("eV"), 0);
+        assert_eq!(number_length("3eV"), 1);
+        assert_eq!(number_length("2e5 J"), 3);
+        assert_eq!(number_length("-1e+3"), 5);
+        assert_eq!(number_length("-"), 0);
+        assert_eq!(number_length("- 5"), 0);
+    }
+
+    #[test]
+    fn detection() {
+        assert!(contains_mhchem(r"$\ce {H2O}$"));
+        assert!(!contains_mhchem(r"\cent"));
+    }
+}
@@ -0,0 +1,391 @@
+//! Convert Office Math (OMML -- what Word and PowerPoint use for equations) to presentation MathML.
+//!
+//! Math copied from Office arrives (as HTML) with the OMML in a conditional comment and with HTML formatting
+//! (`<span>`, `<i>`, ...) mixed in. `find_omml` pulls out the `<m:oMath>` elements and removes the HTML from them.
+//! The converter itself ignores elements that aren't in the OMML namespace and the `...Pr` (property) elements
+//! other than the ones that change the meaning (e.g., the characters used for a delimiter or n-ary operator).
+use anyhow::Result;
+use regex::Regex;
+use roxmltree::Node;
+use crate::convert::ParseError;
+use crate::mathml::{MathNode, math_to_string};
+
+const OMML_NAMESPACE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/math";
+
+/// The start of the element that wraps the OMML when parsing it -- it declares the namespaces in case the OMML doesn't
+const WRAPPER_START: &str = "<omml xmlns:m='http://schemas.openxmlformats.org/officeDocument/2006/math' \
+                             xmlns:w='http://schemas.openxmlformats.org/wordprocessingml/2006/main'>";
+
+/// Convert `omml` (one or more `m:oMath` or `m:oMathPara` elements) to a MathML string (display math)
+pub fn omml_to_mathml(omml: &str) -> Result<String> {
+    return Ok( math_to_string(parse_omml(omml)?, true) );
+}
+
+/// Convert `omml` to a list of MathML nodes (the children of the `math` element).
+/// Several `m:oMath` elements (e.g., the lines of an `m:oMathPara`) become the rows of an `mtable`.
+pub fn parse_omml(omml: &str) -> Result<Vec<MathNode>> {
+    let wrapped = format!("{}{}</omml>", WRAPPER_START, omml.replace("&nbsp;", "&#160;"));
+    let document = match roxmltree::Document::parse(&wrapped) {
+        Ok(document) => document,
+        Err(e) => return Err( ParseError::from_xml_error("The OMML isn't valid XML", &e, &wrapped, WRAPPER_START.chars().count()).into() ),
+    };
+
+    let lines: Vec<MathNode> = document.descendants()
+        .filter(|node| is_omml(node, "oMath"))
+        .map(|o_math| MathNode::mrow(children(o_math)))
+        .collect();
+    return match lines.len() {
+        0 => Err( ParseError::new("No <m:oMath> element found", 0, omml.chars().count()).into() ),
+        1 => Ok(lines),
+        _ => Ok( vec![MathNode::new("mtable", lines.into_iter()
+                        .map(|line| MathNode::new("mtr", vec![MathNode::new("mtd", vec![line])]))
+                        .collect())] ),
+    };
+}
+
+/// Find the OMML in some HTML (e.g., from the clipboard) and return each `m:oMathPara` or `m:oMath` element
+/// with the non-OMML markup removed. The OMML is found even if it is in a comment (as it is when copied from Word).
+pub fn find_omml(html: &str) -> Vec<String> {
+    lazy_static! {
+        static ref OMML_START: Regex = Regex::new(r"<m:oMath(Para)?[\s>]").unwrap();
+        static ref MARKUP: Regex = Regex::new(r"<!--|-->|<!\[[^\]]*\]>|</?([\w.-]+:)?[\w.-]+[^>]*>").unwrap();
+    };
+
+    let mut result = vec![];
+    let mut search_from = 0;
+    while let Some(caps) = OMML_START.captures(&html[search_from..]) {
+        let start = search_from + caps.get(0).unwrap().start();
+        let end_tag = if caps.get(1).is_some() {"</m:oMathPara>"} else {"</m:oMath>"};
+        let end = match html[start..].find(end_tag) {
+            Some(i) => start + i + end_tag.len(),
+            None => break,
+        };
+        let omml = MARKUP.replace_all(&html[start..end], |caps: &regex::Captures| {
+            if caps.get(1).is_some_and(|prefix| prefix.as_str() == "m:") {caps[0].to_string()} else {String::new()}
+        });
+        result.push( omml.replace("&nbsp;", " ") );
+        search_from = end;
+    }
+    return result;
+}
+
+fn is_omml(node: &Node, name: &str) -> bool {
+    return node.is_element() && node.tag_name().namespace() == Some(OMML_NAMESPACE) && node.tag_name().name() == name;
+}
+
+/// The first child of `node` that is the OMML element `name`
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    return node.children().find(|child| is_omml(child, name));
+}
+
+/// The `m:val` attribute of the `property` in the properties element (e.g., `m:dPr`) of `node`.
+/// `None` means the property isn't given; `Some("")` means it is given without a value.
+fn property<'a>(node: Node<'a, '_>, properties: &str, property: &str) -> Option<&'a str> {
+    let property = child(child(node, properties)?, property)?;
+    return Some( property.attributes().find(|attr| attr.name() == "val").map_or("", |attr| attr.value()) );
+}
+
+/// An on/off property is on if it is given without a value or with a value other than "0", "off", or "false"
+fn is_on(value: Option<&str>) -> bool {
+    return matches!(value, Some(value) if value != "0" && value != "off" && value != "false");
+}
+
+/// Convert the children of `node` (skipping properties and anything that isn't OMML, except for its contents)
+fn children(node: Node) -> Vec<MathNode> {
+    let mut result = vec![];
+    for child in node.children().filter(|child| child.is_element()) {
+        if child.tag_name().namespace() != Some(OMML_NAMESPACE) {
+            result.append(&mut children(child));     // e.g., HTML formatting
+        } else if !child.tag_name().name().ends_with("Pr") {
+            result.append(&mut convert(child));
+        }
+    }
+    return result;
+}
+
+/// The contents of the argument `name` (e.g., `m:num`) of `node` as a single node
+fn argument(node: Node, name: &str) -> MathNode {
+    return match child(node, name) {
+        Some(argument) => MathNode::mrow(children(argument)),
+        None => MathNode::new("mrow", vec![]),
+    };
+}
+
+/// Like `argument`, but `None` if the argument is missing or empty
+fn optional_argument(node: Node, name: &str) -> Option<MathNode> {
+    let argument = child(node, name)?;
+    let contents = children(argument);
+    return if contents.is_empty() {None} else {Some( MathNode::mrow(contents) )};
+}
+
+/// Convert an OMML element to MathML
+fn convert(node: Node) -> Vec<MathNode> {
+    let mathml = match node.tag_name().name() {
+        "r" => return convert_run(node, false),
+        "f" => {
+            let (num, den) = (argument(node, "num"), argument(node, "den"));
+            match property(node, "fPr", "type") {
+                Some("lin") => MathNode::mrow(vec![num, MathNode::mo("/"), den]),
+                Some("noBar") => MathNode::new("mfrac", vec![num, den]).attr("linethickness", "0"),
+                Some("skw") => MathNode::new("mfrac", vec![num, den]).attr("bevelled", "true"),
+                _ => MathNode::new("mfrac", vec![num, den]),
+            }
+        },
+        "sSup" => MathNode::new("msup", vec![argument(node, "e"), argument(node, "sup")]),
+        "sSub" => MathNode::new("msub", vec![argument(node, "e"), argument(node, "sub")]),
+        "sSubSup" => MathNode::new("msubsup", vec![argument(node, "e"), argument(node, "sub"), argument(node, "sup")]),
+        "sPre" => MathNode::new("mmultiscripts", vec![
+            argument(node, "e"), MathNode::new("mprescripts", vec![]), argument(node, "sub"), argument(node, "sup")
+        ]),
+        "rad" => match optional_argument(node, "deg") {
+            Some(degree) if !is_on(property(node, "radPr", "degHide")) =>
+                MathNode::new("mroot", vec![argument(node, "e"), degree]),
+            _ => MathNode::new("msqrt", vec![argument(node, "e")]),
+        },
+        "d" => {
+            let open = property(node, "dPr", "begChr").unwrap_or("(");
+            let close = property(node, "dPr", "endChr").unwrap_or(")");
+            let separator = property(node, "dPr", "sepChr").unwrap_or("|");
+            let mut contents = vec![];
+            if !open.is_empty() {
+                contents.push(MathNode::mo(open));
+            }
+            for (i, e) in node.children().filter(|child| is_omml(child, "e")).enumerate() {
+                if i > 0 && !separator.is_empty() {
+                    contents.push(MathNode::mo(separator));
+                }
+                contents.push(MathNode::mrow(children(e)));
+            }
+            if !close.is_empty() {
+                contents.push(MathNode::mo(close));
+            }
+            MathNode::new("mrow", contents)
+        },
+        "nary" => {
+            let operator = property(node, "naryPr", "chr").filter(|chr| !chr.is_empty()).unwrap_or("∫");
+            let is_integral = "∫∬∭∮∯∰".contains(operator);
+            let under_over = match property(node, "naryPr", "limLoc") {
+                Some(location) => location == "undOvr",
+                None => !is_integral,
+            };
+            let lower = optional_argument(node, "sub").filter(|_| !is_on(property(node, "naryPr", "subHide")));
+            let upper = optional_argument(node, "sup").filter(|_| !is_on(property(node, "naryPr", "supHide")));
+            let operator = MathNode::mo(operator);
+            let operator = match (lower, upper) {
+                (None, None) => operator,
+                (Some(lower), None) => MathNode::new(if under_over {"munder"} else {"msub"}, vec![operator, lower]),
+                (None, Some(upper)) => MathNode::new(if under_over {"mover"} else {"msup"}, vec![operator, upper]),
+                (Some(lower), Some(upper)) =>
+                    MathNode::new(if under_over {"munderover"} else {"msubsup"}, vec![operator, lower, upper]),
+            };
+            MathNode::mrow(vec![operator, argument(node, "e")])
+        },
+        "func" => {
+            let name = match child(node, "fName") {
+                Some(name) => MathNode::mrow(function_name(name)),
+                None => MathNode::new("mrow", vec![]),
+            };
+            MathNode::mrow(vec![name, MathNode::mo("\u{2061}"), argument(node, "e")])
+        },
+        "acc" => {
+            let accent = property(node, "accPr", "chr").filter(|chr| !chr.is_empty()).unwrap_or("\u{0302}");
+            MathNode::new("mover", vec![argument(node, "e"), MathNode::mo(&spacing_accent(accent))]).attr("accent", "true")
+        },
+        "bar" => match property(node, "barPr", "pos") {
+            Some("top") => MathNode::new("mover", vec![argument(node, "e"), MathNode::mo("‾")]).attr("accent", "true"),
+            _ => MathNode::new("munder", vec![argument(node, "e"), MathNode::mo("_")]).attr("accentunder", "true"),
+        },
+        "groupChr" => {
+            let chr = property(node, "groupChrPr", "chr").filter(|chr| !chr.is_empty()).unwrap_or("⏟");
+            match property(node, "groupChrPr", "pos") {
+                Some("top") => MathNode::new("mover", vec![argument(node, "e"), MathNode::mo(chr)]),
+                _ => MathNode::new("munder", vec![argument(node, "e"), MathNode::mo(chr)]),
+            }
+        },
+        "limLow" => MathNode::new("munder", vec![argument(node, "e"), argument(node, "lim")]),
+        "limUpp" => MathNode::new("mover", vec![argument(node, "e"), argument(node, "lim")]),
+        "m" => MathNode::new("mtable", node.children()
+                .filter(|row| is_omml(row, "mr"))
+                .map(|row| MathNode::new("mtr", row.children()
+                    .filter(|cell| is_omml(cell, "e"))
+                    .map(|cell| MathNode::new("mtd", vec![MathNode::mrow(children(cell))]))
+                    .collect()))
+                .collect()),
+        "eqArr" => MathNode::new("mtable", node.children()
+                .filter(|row| is_omml(row, "e"))
+                .map(|row| MathNode::new("mtr", vec![MathNode::new("mtd", vec![MathNode::mrow(children(row))])]))
+                .collect()),
+        "borderBox" => MathNode::new("menclose", vec![argument(node, "e")]).attr("notation", "box"),
+        "phant" => match property(node, "phantPr", "show") {
+            Some("0") | Some("off") | Some("false") => MathNode::new("mphantom", vec![argument(node, "e")]),
+            _ => argument(node, "e"),
+        },
+        "box" | "e" | "oMath" | "oMathPara" => MathNode::mrow(children(node)),
+        _ => return children(node),
+    };
+    return vec![mathml];
+}
+
+/// A function name (e.g., "sin") is a single `mi` rather than one per letter
+fn function_name(node: Node) -> Vec<MathNode> {
+    let mut result = vec![];
+    for child in node.children().filter(|child| child.is_element()) {
+        if is_omml(&child, "r") {
+            result.append(&mut convert_run(child, true));
+        } else if child.tag_name().namespace() != Some(OMML_NAMESPACE) {
+            result.append(&mut function_name(child));
+        } else if !child.tag_name().name().ends_with("Pr") {
+            result.append(&mut convert(child));
+        }
+    }
+    return result;
+}
+
+/// Convert a run of text (`m:r`) into tokens: numbers are `mn`s, letters are `mi`s, and everything else is an `mo`.
+/// Each letter is a separate `mi` unless the run is "plain" (upright) text or `is_function_name` is true.
+/// The text is either in `m:t` elements or (in HTML from the clipboard) directly in the `m:r`.
+fn convert_run(node: Node, is_function_name: bool) -> Vec<MathNode> {
+    let text: String = node.descendants()
+        .filter(|descendant| descendant.is_text())
+        .filter(|text| !text.ancestors().any(|ancestor| ancestor.is_element() && ancestor.tag_name().name().ends_with("Pr")))
+        .filter_map(|text| text.text())
+        .collect();
+    if is_on(property(node, "rPr", "nor")) {
+        return vec![MathNode::mtext(&text)];
+    }
+    let is_plain = property(node, "rPr", "sty") == Some("p");
+    let join_letters = is_plain || is_function_name;
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        let start = i;
+        i += 1;
+        if ch.is_whitespace() {
+            continue;
+        }
+        if ch.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_digit() ||
+                                      (chars[i] == '.' && chars.get(i+1).is_some_and(|ch| ch.is_ascii_digit()))) {
+                i += 1;
+            }
+            result.push(MathNode::mn(&chars[start..i].iter().collect::<String>()));
+        } else if ch.is_alphabetic() {
+            if join_letters {
+                while i < chars.len() && chars[i].is_alphabetic() {
+                    i += 1;
+                }
+            }
+            let mut mi = MathNode::mi(&chars[start..i].iter().collect::<String>());
+            if is_plain && i - start == 1 {
+                mi.set_attr("mathvariant", "normal");
+            }
+            result.push(mi);
+        } else {
+            result.push(MathNode::mo(&ch.to_string()));
+        }
+    }
+    return result;
+}
+
+/// Word uses combining chars for accents -- MathML uses the spacing (non-combining) versions
+fn spacing_accent(accent: &str) -> String {
+    return accent.chars().map(|ch| match ch {
+        '\u{0300}' => '`',
+        '\u{0301}' => '´',
+        '\u{0302}' => '^',
+        '\u{0303}' => '~',
+        '\u{0304}' | '\u{0305}' => '¯',
+        '\u{0306}' => '˘',
+        '\u{0307}' => '˙',
+        '\u{0308}' => '¨',
+        '\u{030C}' => 'ˇ',
+        '\u{20D6}' => '←',
+        '\u{20D7}' => '→',
+        '\u{20E1}' => '↔',
+        _ => ch,
+    }).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The MathML for `omml` (the contents of an `m:oMath`) without the `<math>` wrapper
+    fn mathml(omml: &str) -> String {
+        let mathml = omml_to_mathml(&format!("<m:oMath>{}</m:oMath>", omml)).unwrap();
+        return mathml.trim_start_matches("<math display=\"block\">").trim_end_matches("</math>").to_string();
+    }
+
+    #[test]
+    fn runs() {
+        assert_eq!(mathml("<m:r><m:t>2xy+1.5</m:t></m:r>"), "<mrow><mn>2</mn><mi>x</mi><mi>y</mi><mo>+</mo><mn>1.5</mn></mrow>");
+        assert_eq!(mathml("<m:r><m:rPr><m:sty m:val='p'/></m:rPr><m:t>d</m:t></m:r>"), "<mi mathvariant=\"normal\">d</mi>");
+        assert_eq!(mathml("<m:r><m:rPr><m:nor/></m:rPr><m:t>if x</m:t></m:r>"), "<mtext>if x</mtext>");
+    }
+
+    #[test]
+    fn fractions_and_scripts() {
+        assert_eq!(mathml("<m:f><m:num><m:r><m:t>a</m:t></m:r></m:num><m:den><m:r><m:t>b</m:t></m:r></m:den></m:f>"),
+                   "<mfrac><mi>a</mi><mi>b</mi></mfrac>");
+        assert_eq!(mathml("<m:f><m:fPr><m:type m:val='lin'/></m:fPr><m:num><m:r><m:t>a</m:t></m:r></m:num>\
+                           <m:den><m:r><m:t>b</m:t></m:r></m:den></m:f>"),
+                   "<mrow><mi>a</mi><mo>/</mo><mi>b</mi></mrow>");
+        assert_eq!(mathml("<m:sSubSup><m:e><m:r><m:t>x</m:t></m:r></m:e><m:sub><m:r><m:t>i</m:t></m:r></m:sub>\
+                           <m:sup><m:r><m:t>2</m:t></m:r></m:sup></m:sSubSup>"),
+                   "<msubsup><mi>x</mi><mi>i</mi><mn>2</mn></msubsup>");
+    }
+
+    #[test]
+    fn delimiters() {
+        assert_eq!(mathml("<m:d><m:e><m:r><m:t>a</m:t></m:r></m:e><m:e><m:r><m:t>b</m:t></m:r></m:e></m:d>"),
+                   "<mrow><mo>(</mo><mi>a</mi><mo>|</mo><mi>b</mi><mo>)</mo></mrow>");
+        assert_eq!(mathml("<m:d><m:dPr><m:begChr m:val='['/><m:endChr m:val=''/></m:dPr><m:e><m:r><m:t>a</m:t></m:r></m:e></m:d>"),
+                   "<mrow><mo>[</mo><mi>a</mi></mrow>");
+    }
+
+    #[test]
+    fn nary_and_functions() {
+        assert_eq!(mathml("<m:nary><m:naryPr><m:chr m:val='∑'/></m:naryPr><m:sub><m:r><m:t>i</m:t></m:r></m:sub>\
+                           <m:sup><m:r><m:t>n</m:t></m:r></m:sup><m:e><m:r><m:t>i</m:t></m:r></m:e></m:nary>"),
+                   "<mrow><munderover><mo>∑</mo><mi>i</mi><mi>n</mi></munderover><mi>i</mi></mrow>");
+        assert_eq!(mathml("<m:nary><m:sub><m:r><m:t>0</m:t></m:r></m:sub><m:sup/><m:e><m:r><m:t>f</m:t></m:r></m:e></m:nary>"),
+                   "<mrow><msub><mo>∫</mo><mn>0</mn></msub><mi>f</mi></mrow>");
+        assert_eq!(mathml("<m:func><m:fName><m:r><m:t>sin</m:t></m:r></m:fName><m:e><m:r><m:t>x</m:t></m:r></m:e></m:func>"),
+                   "<mrow><mi>sin</mi><mo>\u{2061}</mo><mi>x</mi></mrow>");
+    }
+
+    #[test]
+    fn accents() {
+        assert_eq!(mathml("<m:acc><m:accPr><m:chr m:val='\u{0303}'/></m:accPr><m:e><m:r><m:t>x</m:t></m:r></m:e></m:acc>"),
+                   "<mover accent=\"true\"><mi>x</mi><mo>~</mo></mover>");
+    }
+
+    #[test]
+    fn several_lines() {
+        let mathml = omml_to_mathml("<m:oMathPara><m:oMath><m:r><m:t>a</m:t></m:r></m:oMath>\
+                                     <m:oMath><m:r><m:t>b</m:t></m:r></m:oMath></m:oMathPara>").unwrap();
+        assert_eq!(mathml, "<math display=\"block\"><mtable><mtr><mtd><mi>a</mi></mtd></mtr><mtr><mtd><mi>b</mi></mtd></mtr></mtable></math>");
+    }
+
+    #[test]
+    fn errors() {
+        let e = parse_omml("<m:oMath><m:r></m:oMath>").unwrap_err();
+        assert_eq!(e.downcast_ref::<ParseError>().expect("a ParseError").message.split(':').next(), Some("The OMML isn't valid XML"));
+        let e = parse_omml("<p>no math</p>").unwrap_err();
+        assert_eq!(e.downcast_ref::<ParseError>().expect("a ParseError").message, "No <m:oMath> element found");
+    }
+
+    #[test]
+    fn omml_in_html() {
+        let html = "<p class=MsoNormal><!--[if gte msEquation 12]><m:oMathPara><m:oMath><i><span style='font-family:\"Cambria Math\"'>\
+                    <m:r>x</m:r></span></i><m:r>&nbsp;</m:r></m:oMath></m:oMathPara><![endif]--><![if !msEquation]>x<![endif]></p>\
+                    <p><m:oMath><m:r>y</m:r></m:oMath></p>";
+        assert_eq!(find_omml(html), vec!["<m:oMathPara><m:oMath><m:r>x</m:r><m:r> </m:r></m:oMath></m:oMathPara>",
+                                         "<m:oMath><m:r>y</m:r></m:oMath>"]);
+        assert!(find_omml("<p>x</p>").is_empty());
+        assert_eq!(omml_to_mathml(&find_omml(html)[0]).unwrap(), "<math display=\"block\"><mi>x</mi></math>");
+    }
+}
@@ -0,0 +1,95 @@
+//! Links that encode the input and all the settings so that someone else can see (and hear) exactly the same thing.
+//!
+//! The values are put in the URL fragment (`#math=...&language=en&...`) so they are never sent to the server.
+//! When reading a link, a query string (`?math=...`) is also accepted.
+use crate::settings::Settings;
+
+/// The name used for the input in a permalink
+const INPUT_NAME: &str = "math";
+
+/// Create a link to `base_url` (any query or fragment in it is dropped) that holds `input` and `settings`.
+pub fn make_permalink(base_url: &str, input: &str, settings: &Settings) -> String {
+    let base_url = base_url.split(['?', '#']).next().unwrap_or(base_url);
+    let mut link = format!("{}#{}={}", base_url, INPUT_NAME, percent_encode(input));
+    for (name, value) in settings.to_pairs() {
+        link += &format!("&{}={}", name, percent_encode(&value));
+    }
+    return link;
+}
+
+/// Set any settings found in the query or fragment of `url`, and return the input if there is one.
+/// Unknown names are ignored.
+pub fn read_permalink(url: &str, settings: &mut Settings) -> Option<String> {
+    let mut input = None;
+    let parameters = url.split_once(['?', '#']).map_or("", |(_, parameters)| parameters);
+    for parameter in parameters.split(['&', '#', '?']) {
+        let (name, value) = match parameter.split_once('=') {
+            Some(name_value) => name_value,
+            None => continue,
+        };
+        let value = percent_decode(value);
+        if name == INPUT_NAME {
+            input = Some(value);
+        } else {
+            settings.set(name, &value);
+        }
+    }
+    return input;
+}
+
+/// Encode everything except the "unreserved" URL chars as `%XX` (UTF-8)
+pub fn percent_encode(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            result.push(byte as char);
+        } else {
+            result += &format!("%{:02X}", byte);
+        }
+    }
+    return result;
+}
+
+/// Decode `%XX` sequences and `+` (a space in a query string). Invalid sequences are left as is.
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 3 <= bytes.len() {
+            if let Some(byte) = std::str::from_utf8(&bytes[i+1..i+3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                result.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        result.push(if bytes[i] == b'+' {b' '} else {bytes[i]});
+        i += 1;
+    }
+    return String::from_utf8_lossy(&result).to_string();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let settings = Settings { language: "fr".to_string(), braille_code: "UEB".to_string(), tts: "Off".to_string(), ..Settings::default() };
+        let link = make_permalink("https://example.com/demo?x=1", "a & b; c=d", &settings);
+        let mut read = Settings::default();
+        assert_eq!(read_permalink(&link, &mut read).as_deref(), Some("a & b; c=d"));
+        assert_eq!(read, settings);
+    }
+
+    #[test]
+    fn invalid_values_are_dropped() {
+        let mut settings = Settings::default();
+        read_permalink("#nav_mode=Fast&speech_style=MathSpeak&verbosity=&braille_code=Braille&braille_dots78=Some&tts=Loud&language=",
+                       &mut settings);
+        assert_eq!(settings, Settings::default());
+        read_permalink("#nav_mode=Simple&speech_style=SimpleSpeak&tts=None&braille_dots78=All", &mut settings);
+        assert_eq!( (settings.nav_mode.as_str(), settings.speech_style.as_str(), settings.tts.as_str(), settings.braille_dots78.as_str()),
+                    ("Simple", "SimpleSpeak", "None", "All") );
+    }
+}
@@ -0,0 +1,265 @@
+//! The steps that take MathML to speech and braille (and navigation of that MathML).
+//! These are thin wrappers around MathCAT that make sure the user's settings are used.
+use libmathcat::*;
+use anyhow::{Result, Context};
+use phf::phf_map;
+use crate::settings::Settings;
+use crate::convert::input_to_mathml;
+use crate::document::{math_expressions, split_input, Segment};
+use crate::mathml::escape_xml;
+use crate::errors::Stage;
+
+/// Initialize MathCAT. This should be called once before using any of the other functions.
+pub fn init(rules_dir: &str) -> Result<()> {
+    set_rules_dir(rules_dir.to_string()).context("Didn't find rules dir")?;
+    set_preference("CheckRuleFiles".to_string(), "None".to_string())?;
+    return Ok( () );
+}
+
+/// Names for the languages MathCAT has (or might have) rules for.
+/// Regional variants that aren't listed are named using their base language (see `language_name`).
+static LANGUAGE_NAMES: phf::Map<&'static str, &'static str> = phf_map! {
+    "de" => "German",
+    "el" => "Greek",
+    "en" => "English",
+    "en-gb" => "English (UK)",
+    "en-us" => "English (US)",
+    "es" => "Spanish",
+    "fi" => "Finnish",
+    "fr" => "French",
+    "id" => "Indonesian",
+    "it" => "Italian",
+    "ja" => "Japanese",
+    "ko" => "Korean",
+    "nb" => "Norwegian Bokmål",
+    "nl" => "Dutch",
+    "pt" => "Portuguese",
+    "ru" => "Russian",
+    "sv" => "Swedish",
+    "vi" => "Vietnamese",
+    "zh" => "Chinese",
+    "zh-cn" => "Chinese (Simplified)",
+    "zh-tw" => "Chinese (Traditional)",
+};
+
+/// The languages there are speech rules for, as (code, name) pairs sorted by name.
+/// Regional variants (e.g., "en-gb") are included. MathCAT must have been initialized (see `init`).
+pub fn available_languages() -> Vec<(String, String)> {
+    let mut languages: Vec<(String, String)> = get_supported_languages().into_iter()
+        .map(|code| {
+            let name = language_name(&code);
+            (code, name)
+        })
+        .collect();
+    languages.sort_by(|(_, name1), (_, name2)| name1.cmp(name2));
+    return languages;
+}
+
+/// A name for the language `code` to show the user (e.g., "English (UK)" for "en-gb")
+pub fn language_name(code: &str) -> String {
+    let code = code.to_ascii_lowercase();
+    if let Some(name) = LANGUAGE_NAMES.get(code.as_str()) {
+        return name.to_string();
+    }
+    return match code.split_once('-') {
+        Some( (language, region) ) => format!("{} ({})", language_name(language), region.to_uppercase()),
+        None => code,
+    };
+}
+
+/// Give `mathml` to MathCAT. This adds ids and canonicalizes the MathML, which is returned.
+pub fn canonicalize_mathml(mathml: String) -> Result<String> {
+    // MathJax bug https://github.com/mathjax/MathJax/issues/2805:  newline at end causes MathJaX to hang(!)
+    let math = set_mathml(mathml).context(Stage::Canonicalization)?;
+    return Ok( math.trim_end().to_string() );
+}
+
+/// The speech for the MathML most recently given to `canonicalize_mathml` using the speech settings in `settings`.
+pub fn speech(settings: &Settings) -> Result<String> {
+    settings.apply_speech_preferences().context(Stage::Speech)?;
+    return get_spoken_text().context(Stage::Speech);
+}
+
+/// A short summary (the gist) of the MathML most recently given to `canonicalize_mathml`.
+/// If `nav_id` is not empty, the summary is of that node and the navigation moves to it.
+pub fn overview(settings: &Settings, nav_id: &str) -> Result<String> {
+    settings.apply_speech_preferences().context(Stage::Speech)?;
+    if nav_id.is_empty() {
+        return get_overview_text().context(Stage::Speech);
+    }
+    set_navigation_node(nav_id.to_string(), 0).context(Stage::Speech)?;
+    return do_navigate_command("DescribeCurrent".to_string()).context(Stage::Speech);
+}
+
+/// The braille (as Unicode braille) for the MathML most recently given to `canonicalize_mathml`.
+/// If `nav_id` is not empty, the braille for that node is marked as set in `settings`.
+pub fn braille(settings: &Settings, nav_id: &str) -> Result<String> {
+    settings.apply_braille_preferences().context(Stage::Braille)?;
+    return get_braille(nav_id.to_string()).context(Stage::Braille);
+}
+
+/// Convert a Unicode braille char to ASCII braille.
+/// The second value is `true` if the char included dots 7 or 8 (they have no ASCII representation).
+pub fn ascii_braille_char(ch: char) -> (char, bool) {
+    lazy_static! {
+        static ref UNICODE_TO_ASCII: Vec<char> =
+            " A1B'K2L@CIF/MSP\"E3H9O6R^DJG>NTQ,*5<-U8V.%[$+X!&;:4\\0Z7(_?W]#Y)=".chars().collect();
+    };
+
+    let i = (ch as usize).wrapping_sub(0x2800);
+    if i > 0xFF {
+        return (ch, false);     // not braille
+    }
+    return (UNICODE_TO_ASCII[i & 0x3F], i > 0x3F);      // eliminate dots 7 and 8 if present
+}
+
+/// Convert an ASCII braille char (any case) to Unicode braille. Chars that aren't ASCII braille are returned as is.
+pub fn unicode_braille_char(ch: char) -> char {
+    lazy_static! {
+        static ref ASCII_TO_UNICODE: Vec<char> = {
+            let mut table = vec!['\0'; 0x40];
+            for i in 0..0x40u32 {
+                let (ascii_ch, _) = ascii_braille_char(char::from_u32(0x2800 + i).unwrap());
+                table[ascii_ch as usize - 0x20] = char::from_u32(0x2800 + i).unwrap();
+            }
+            table
+        };
+    };
+
+    let ch = ch.to_ascii_uppercase();
+    return match (ch as usize).checked_sub(0x20) {
+        Some(i) if i < 0x40 => ASCII_TO_UNICODE[i],
+        _ => ch,
+    };
+}
+
+/// Convert a string of Unicode braille to ASCII braille, dropping dots 7 and 8.
+pub fn ascii_braille(braille: &str) -> String {
+    return braille.chars().map(|ch| ascii_braille_char(ch).0).collect();
+}
+
+/// The result of a navigation command
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavigationResult {
+    pub speech: String,
+    pub nav_id: String,
+    pub nav_offset: usize,
+    /// navigation commands can change the mode
+    pub nav_mode: String,
+}
+
+/// Navigate the MathML most recently given to `canonicalize_mathml`.
+/// The arguments use the same values as a (browser) keyboard event.
+pub fn navigate(key_code: usize, shift_key: bool, ctrl_key: bool, alt_key: bool, meta_key: bool) -> Result<NavigationResult> {
+    let speech = do_navigate_keypress(key_code, shift_key, ctrl_key, alt_key, meta_key)?;
+    let (nav_id, nav_offset) = get_navigation_mathml_id()?;
+    return Ok( NavigationResult {
+        speech,
+        nav_id,
+        nav_offset,
+        nav_mode: get_preference("NavMode".to_string())?,
+    } );
+}
+
+/// Move the navigation of the MathML most recently given to `canonicalize_mathml` to the element with the id `nav_id`
+/// and return the speech for that element.
+pub fn navigate_to(nav_id: &str) -> Result<NavigationResult> {
+    set_navigation_node(nav_id.to_string(), 0)?;
+    let speech = do_navigate_command("ReadCurrent".to_string())?;
+    let (nav_id, nav_offset) = get_navigation_mathml_id()?;
+    return Ok( NavigationResult {
+        speech,
+        nav_id,
+        nav_offset,
+        nav_mode: get_preference("NavMode".to_string())?,
+    } );
+}
+
+/// The speech and braille for some input
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendering {
+    /// The canonicalized MathML (with ids)
+    pub mathml: String,
+    pub speech: String,
+    /// Unicode braille
+    pub braille: String,
+}
+
+/// Run the whole pipeline: convert `math_str` (TeX, ASCIIMath, or MathML) to MathML, and generate speech and braille for it.
+pub fn render(math_str: &str, settings: &Settings) -> Result<Rendering> {
+    let mathml = input_to_mathml(math_str.trim())?;
+    settings.apply_canonicalization_preferences().context(Stage::Canonicalization)?;
+    let mathml = canonicalize_mathml(mathml)?;
+    return Ok( Rendering {
+        speech: speech(settings)?,
+        braille: braille(settings, "")?,
+        mathml,
+    } );
+}
+
+/// Run the whole pipeline on each of the (delimited) math expressions in `input`.
+pub fn render_all(input: &str, settings: &Settings) -> Vec<Result<Rendering>> {
+    return math_expressions(input).iter()
+        .map(|math| {
+            settings.apply_canonicalization_preferences().context(Stage::Canonicalization)?;
+            let mathml = canonicalize_mathml(math.to_mathml()?)?;
+            return Ok( Rendering {
+                speech: speech(settings)?,
+                braille: braille(settings, "")?,
+                mathml,
+            } );
+        })
+        .collect();
+}
+
+/// The speech and braille for text with embedded math
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentReading {
+    pub speech: String,
+    /// Unicode braille
+    pub braille: String,
+}
+
+/// Read `input`, which is text with embedded (delimited) math, as one continuous document.
+/// The text is passed through (escaped if the speech is SSML) and each piece of math is replaced by its speech.
+/// There is no literary braille translator, so the braille for the text is a char-by-char (computer braille) transcription.
+/// For Nemeth, the math is surrounded by the Nemeth opening and closing indicators.
+/// If some math can't be converted, its source is used in its place.
+pub fn read_document(input: &str, settings: &Settings) -> Result<DocumentReading> {
+    settings.apply_canonicalization_preferences().context(Stage::Canonicalization)?;
+    let is_ssml = settings.tts == "SSML";
+    let segments = split_input(input);
+    let has_text = segments.iter().any(|segment| matches!(segment, Segment::Text(_)));
+    let mut reading = DocumentReading { speech: String::new(), braille: String::new() };
+    for segment in segments {
+        let (speech_text, braille_text) = match segment {
+            Segment::Text(text) => {
+                let text = text.replace('\n', " ");
+                (if is_ssml {escape_xml(&text)} else {text.clone()}, text.chars().map(unicode_braille_char).collect())
+            },
+            Segment::Math(math) => {
+                let result = math.to_mathml()
+                    .and_then(canonicalize_mathml)
+                    .and_then(|_| Ok( (speech(settings)?, braille(settings, "")?) ));
+                match result {
+                    Ok( (speech_text, mut braille_text) ) => {
+                        let speech_text = speech_text.trim().to_string();
+                        if has_text && settings.braille_code == "Nemeth" {
+                            braille_text = format!("⠸⠩⠀{}⠀⠸⠱", braille_text);
+                        }
+                        (speech_text, braille_text)
+                    },
+                    Err(e) => {
+                        warn!("Couldn't convert '{}': {}", math.text, e);
+                        (if is_ssml {escape_xml(&math.text)} else {math.text.clone()}, math.text.chars().map(unicode_braille_char).collect())
+                    },
+                }
+            },
+        };
+        reading.speech.push_str(&speech_text);
+        reading.braille.push_str(&braille_text);
+    }
+    reading.speech = reading.speech.trim().to_string();
+    reading.braille = reading.braille.trim_matches(|ch: char| ch == '⠀' || ch.is_whitespace()).to_string();
+    return Ok(reading);
+}
@@ -0,0 +1,278 @@
+//! A description of MathCAT's preferences: their types, allowed values, and defaults.
+//!
+//! MathCAT doesn't have a way to list its preferences, so they are listed here. The GUI's preferences panel
+//! and the persisting of the settings are driven by this list: adding a preference here is all that is needed
+//! for it to show up in the panel, be saved, and be given to MathCAT (see `Settings::preference`).
+
+/// When a preference needs to be given to MathCAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreferenceGroup {
+    /// Used when MathCAT canonicalizes the MathML, so it must be set before the MathML is given to MathCAT
+    Canonicalization,
+    Speech,
+    Braille,
+    Navigation,
+}
+
+impl PreferenceGroup {
+    pub fn description(&self) -> &'static str {
+        return match self {
+            PreferenceGroup::Canonicalization => "Interpretation",
+            PreferenceGroup::Speech => "Speech",
+            PreferenceGroup::Braille => "Braille",
+            PreferenceGroup::Navigation => "Navigation",
+        };
+    }
+}
+
+pub static PREFERENCE_GROUPS: &[PreferenceGroup] = &[
+    PreferenceGroup::Canonicalization, PreferenceGroup::Speech, PreferenceGroup::Braille, PreferenceGroup::Navigation,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreferenceKind {
+    /// One of the listed values
+    Choice(&'static [&'static str]),
+    /// "true" or "false"
+    Bool,
+    /// A number in the range (min, max)
+    Number(f64, f64),
+    Text,
+    /// A language code -- the choices depend on the rules that are available (see `pipeline::available_languages`)
+    Language,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preference {
+    /// MathCAT's name for it
+    pub name: &'static str,
+    /// A short description to show the user
+    pub label: &'static str,
+    pub group: PreferenceGroup,
+    pub kind: PreferenceKind,
+    pub default: &'static str,
+}
+
+/// MathCAT's preferences, in the order they are given to MathCAT (e.g., the language is set before the speech style)
+pub static PREFERENCES: &[Preference] = &[
+    // Interpretation
+    Preference {
+        name: "Chemistry", label: "Chemistry", group: PreferenceGroup::Canonicalization,
+        kind: PreferenceKind::Choice(&["SpellOut", "Off"]), default: "Off",
+    },
+    Preference {
+        name: "DecimalSeparator", label: "Decimal separator", group: PreferenceGroup::Canonicalization,
+        kind: PreferenceKind::Choice(&["Auto", ".", ",", "Custom"]), default: "Auto",
+    },
+    Preference {
+        name: "DecimalSeparators", label: "Decimal separators (Custom)", group: PreferenceGroup::Canonicalization,
+        kind: PreferenceKind::Text, default: ".",
+    },
+    Preference {
+        name: "BlockSeparators", label: "Digit block separators (Custom)", group: PreferenceGroup::Canonicalization,
+        kind: PreferenceKind::Text, default: ", \u{00A0}\u{202F}",
+    },
+
+    // Speech
+    Preference {
+        name: "Language", label: "Language", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Language, default: "en",
+    },
+    Preference {
+        name: "SpeechStyle", label: "Speech style", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["ClearSpeak", "SimpleSpeak"]), default: "ClearSpeak",
+    },
+    Preference {
+        name: "Verbosity", label: "Verbosity", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Terse", "Medium", "Verbose"]), default: "Verbose",
+    },
+    Preference {
+        name: "TTS", label: "Speech markup", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["None", "SSML"]), default: "SSML",
+    },
+    Preference {
+        name: "SpeechOverrides_CapitalLetters", label: "Say before capital letters", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["", "cap"]), default: "",
+    },
+    Preference {
+        name: "Bookmark", label: "SSML marks (used to highlight what is being spoken)", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Bool, default: "true",
+    },
+    Preference {
+        name: "SubjectArea", label: "Subject area", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["General"]), default: "General",
+    },
+    Preference {
+        name: "SpeechSound", label: "Sound at start and end", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["None", "Beep"]), default: "None",
+    },
+    Preference {
+        name: "MathRate", label: "Math speech rate (%)", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Number(10.0, 300.0), default: "100",
+    },
+    Preference {
+        name: "PauseFactor", label: "Pause length (%)", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Number(0.0, 1000.0), default: "100",
+    },
+
+    // ClearSpeak (used only when the speech style is ClearSpeak -- see `Preference::is_clear_speak`)
+    Preference {
+        name: "ClearSpeak_AbsoluteValue", label: "Absolute value", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "AbsEnd", "Cardinality", "Determinant"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Currency", label: "Currency", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "Position", "Prefix"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Enclosed", label: "Enclosed (menclose)", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "EndEnclose"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Exponents", label: "Exponents", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "Ordinal", "OrdinalPower", "AfterPower"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Fractions", label: "Fractions", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "Ordinal", "Over", "FracOver", "General", "EndFrac", "GeneralEndFrac", "OverEndFrac", "Per"]),
+        default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Functions", label: "Functions", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "None"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_ImpliedTimes", label: "Implied multiplication", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "MoreImpliedTimes", "None"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Log", label: "Logarithms", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "LnAsNaturalLog"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Matrix", label: "Matrices", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "SpeakColNum", "SilentColNum", "EndMatrix", "Vector", "EndVector", "Combinatorics"]),
+        default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_MultiLineLabel", label: "Multi-line label", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "Case", "Constraint", "Equation", "Line", "None", "Row", "Step"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_MultiLineOverview", label: "Multi-line overview", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "None"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_MultiLinePausesBetweenColumns", label: "Multi-line pauses between columns", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "Long", "Short"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_MultSymbolDot", label: "Multiplication dot", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "Dot"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_MultSymbolX", label: "Multiplication ×", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "By", "Cross"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Paren", label: "Parentheses", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "CoordPoint", "Interval", "Silent", "Speak", "SpeakNestingLevel"]),
+        default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Prime", label: "Prime", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "Angle", "Length"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Roots", label: "Roots", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "PosNegSqRoot", "RootEnd", "PosNegSqRootEnd"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_SetMemberSymbol", label: "Set membership (∈)", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "Belongs", "Element", "Member", "In"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Sets", label: "Sets", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "SilentBracket", "woAll"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_TriangleSymbol", label: "Triangle (△)", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "Dot"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_Trig", label: "Trigonometry", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "TrigInverse", "ArcTrig"]), default: "Auto",
+    },
+    Preference {
+        name: "ClearSpeak_VerticalLine", label: "Vertical line (|)", group: PreferenceGroup::Speech,
+        kind: PreferenceKind::Choice(&["Auto", "Divides", "Given", "SuchThat"]), default: "Auto",
+    },
+
+    // Braille
+    Preference {
+        name: "BrailleCode", label: "Braille code", group: PreferenceGroup::Braille,
+        kind: PreferenceKind::Choice(&["Nemeth", "UEB", "CMU", "Vietnam", "Swedish", "Finnish", "LaTeX", "ASCIIMath"]),
+        default: "Nemeth",
+    },
+    Preference {
+        name: "BrailleNavHighlight", label: "Highlight with dots 7 and 8", group: PreferenceGroup::Braille,
+        kind: PreferenceKind::Choice(&["Off", "FirstChar", "EndPoints", "All"]), default: "EndPoints",
+    },
+    Preference {
+        name: "UEB_START_MODE", label: "UEB start mode", group: PreferenceGroup::Braille,
+        kind: PreferenceKind::Choice(&["Grade1", "Grade2"]), default: "Grade2",
+    },
+    Preference {
+        name: "UseSpacesAroundAllOperators", label: "Spaces around all operators (Nemeth)", group: PreferenceGroup::Braille,
+        kind: PreferenceKind::Bool, default: "false",
+    },
+
+    // Navigation
+    Preference {
+        name: "NavMode", label: "Navigation mode", group: PreferenceGroup::Navigation,
+        kind: PreferenceKind::Choice(&["Enhanced", "Simple", "Character"]), default: "Enhanced",
+    },
+    Preference {
+        name: "ResetNavMode", label: "Reset the mode for each expression", group: PreferenceGroup::Navigation,
+        kind: PreferenceKind::Bool, default: "false",
+    },
+    Preference {
+        name: "NavVerbosity", label: "Navigation verbosity", group: PreferenceGroup::Navigation,
+        kind: PreferenceKind::Choice(&["Terse", "Medium", "Verbose"]), default: "Verbose",
+    },
+    Preference {
+        name: "Overview", label: "Start with an overview", group: PreferenceGroup::Navigation,
+        kind: PreferenceKind::Bool, default: "false",
+    },
+    Preference {
+        name: "ResetOverview", label: "Reset the overview setting for each expression", group: PreferenceGroup::Navigation,
+        kind: PreferenceKind::Bool, default: "true",
+    },
+    Preference {
+        name: "AutoZoomOut", label: "Zoom out automatically", group: PreferenceGroup::Navigation,
+        kind: PreferenceKind::Bool, default: "true",
+    },
+];
+
+/// The description of the preference `name` (None if it isn't one of MathCAT's preferences)
+pub fn find_preference(name: &str) -> Option<&'static Preference> {
+    return PREFERENCES.iter().find(|preference| preference.name == name);
+}
+
+impl Preference {
+    /// True if this is one of ClearSpeak's options (they have no effect on the other speech styles)
+    pub fn is_clear_speak(&self) -> bool {
+        return self.name.starts_with("ClearSpeak_");
+    }
+
+    /// True if `value` is allowed for this preference
+    pub fn is_valid(&self, value: &str) -> bool {
+        return match self.kind {
+            PreferenceKind::Choice(values) => values.contains(&value),
+            PreferenceKind::Bool => value == "true" || value == "false",
+            PreferenceKind::Number(min, max) => value.parse::<f64>().is_ok_and(|n| min <= n && n <= max),
+            PreferenceKind::Text => true,
+            PreferenceKind::Language => !value.is_empty(),
+        };
+    }
+}
@@ -0,0 +1,240 @@
+//! The user-settable values that control speech, braille, and navigation (and how the input is processed).
+//! These are what gets persisted between sessions.
+//!
+//! The MathCAT preferences that the GUI has its own controls for have their own fields; the values of the rest of
+//! MathCAT's preferences (see `preferences::PREFERENCES`) are kept in `preferences`.
+use libmathcat::*;
+use anyhow::Result;
+use crate::preferences::{PREFERENCES, PreferenceGroup, find_preference};
+use crate::permalink::{percent_encode, percent_decode};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub nav_mode: String,
+    pub nav_verbosity: String,
+    pub language: String,
+    pub speech_style: String,
+    pub verbosity: String,
+    pub say_caps: bool,
+    pub tts: String,
+    pub braille_code: String,
+    pub braille_display_as: String,
+    pub braille_dots78: String,
+    /// Interpret (and speak) chemical formulas and reactions as chemistry
+    pub chemistry: bool,
+    /// Re-render the input as it is typed
+    pub live_update: bool,
+    /// Show a short overview of the expression along with the full speech
+    pub show_overview: bool,
+    /// The change (in percent, -50 to 50) to the pitch of the voice that speaks the speech (MathCAT doesn't have a pitch preference)
+    pub pitch: String,
+    /// (MathCAT name, value) for the other preferences -- only the ones that aren't the default are kept
+    pub preferences: Vec<(String, String)>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        return Settings {
+            nav_mode: "Enhanced".to_string(),
+            nav_verbosity: "Verbose".to_string(),
+            language: "en".to_string(),
+            speech_style: "ClearSpeak".to_string(),
+            verbosity: "Verbose".to_string(),
+            say_caps: false,
+            tts: "SSML".to_string(),
+            braille_code: "Nemeth".to_string(),
+            braille_display_as: "Dots".to_string(),
+            braille_dots78: "EndPoints".to_string(),
+            chemistry: false,
+            live_update: false,
+            show_overview: false,
+            pitch: "0".to_string(),
+            preferences: vec![],
+        };
+    }
+}
+
+impl Settings {
+    /// Returns the settings as a list of (name, value) pairs. The names are the ones used by `set`.
+    pub fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![
+            ("nav_mode", self.nav_mode.clone()),
+            ("nav_verbosity", self.nav_verbosity.clone()),
+            ("language", self.language.clone()),
+            ("speech_style", self.speech_style.clone()),
+            ("verbosity", self.verbosity.clone()),
+            ("say_caps", self.say_caps.to_string()),
+            ("braille_code", self.braille_code.clone()),
+            ("braille_display_as", self.braille_display_as.clone()),
+            ("braille_dots78", self.braille_dots78.clone()),
+            ("tts", self.tts.clone()),
+            ("chemistry", self.chemistry.to_string()),
+            ("live_update", self.live_update.to_string()),
+            ("show_overview", self.show_overview.to_string()),
+            ("pitch", self.pitch.clone()),
+        ];
+        for (name, value) in &self.preferences {
+            if let Some(preference) = find_preference(name) {
+                pairs.push( (preference.name, value.clone()) );
+            }
+        }
+        return pairs;
+    }
+
+    /// Returns the settings as a list of `name=value;` pairs (the format used for cookies).
+    /// The values are percent-encoded so that they can contain any char (e.g., `;` or a non-breaking space).
+    pub fn to_cookie_string(&self) -> String {
+        let mut cookie = String::with_capacity(1024);
+        for (name, value) in self.to_pairs() {
+            cookie += &format!("{}={};", name, percent_encode(&value));
+        }
+        return cookie;
+    }
+
+    /// Sets the values found in a `name=value;` list (the inverse of `to_cookie_string`).
+    /// Unknown names are ignored.
+    pub fn set_from_cookie_string(&mut self, cookies: &str) {
+        for cookie in cookies.split(';') {
+            if let Some( (key, value) ) = cookie.split_once('=') {
+                self.set(key.trim(), &percent_decode(value));
+            }
+        }
+    }
+
+    /// Set the setting 'name' to 'value'. Unknown names and values that aren't allowed are ignored.
+    pub fn set(&mut self, name: &str, value: &str) {
+        let value = value.to_string();
+        match name {
+            "nav_mode" => self.set_preference("NavMode", &value),
+            "nav_verbosity" => self.set_preference("NavVerbosity", &value),
+            "language" => self.set_preference("Language", &value),
+            "speech_style" => self.set_preference("SpeechStyle", &value),
+            "verbosity" => self.set_preference("Verbosity", &value),
+            "say_caps" => self.say_caps = value=="true",
+            "braille_code" => self.set_preference("BrailleCode", &value),
+            "braille_display_as" => if value == "Dots" || value == "ASCIIBraille" {self.braille_display_as = value},
+            "braille_dots78" => self.set_preference("BrailleNavHighlight", &value),
+            // "Off" (don't speak) isn't a MathCAT value (and `set_preference` won't change it)
+            "tts" => if value == "Off" || find_preference("TTS").is_some_and(|tts| tts.is_valid(&value)) {self.tts = value},
+            "chemistry" => self.chemistry = value=="true",
+            "live_update" => self.live_update = value=="true",
+            "show_overview" => self.show_overview = value=="true",
+            "pitch" => if value.parse::<i32>().is_ok_and(|pitch| (-50..=50).contains(&pitch)) {self.pitch = value},
+            _ => self.set_preference(name, &value),
+        }
+    }
+
+    /// The value of MathCAT's preference `name` (as it is given to MathCAT)
+    pub fn preference(&self, name: &str) -> String {
+        return match name {
+            "NavMode" => self.nav_mode.clone(),
+            "NavVerbosity" => self.nav_verbosity.clone(),
+            "Language" => self.language.clone(),
+            "SpeechStyle" => self.speech_style.clone(),
+            "Verbosity" => self.verbosity.clone(),
+            "SpeechOverrides_CapitalLetters" => (if self.say_caps {"cap"} else {""}).to_string(),
+            "TTS" => if self.tts == "Off" {"None".to_string()} else {self.tts.clone()},
+            "BrailleCode" => self.braille_code.clone(),
+            "BrailleNavHighlight" => self.braille_dots78.clone(),
+            "Chemistry" => (if self.chemistry {"SpellOut"} else {"Off"}).to_string(),
+            _ => match self.preferences.iter().find(|(n, _)| n == name) {
+                Some( (_, value) ) => value.clone(),
+                None => find_preference(name).map_or(String::default(), |preference| preference.default.to_string()),
+            },
+        };
+    }
+
+    /// Set MathCAT's preference `name` to `value` (MathCAT isn't told until one of the `apply...` functions is called).
+    /// Unknown names and values that aren't allowed are ignored.
+    pub fn set_preference(&mut self, name: &str, value: &str) {
+        let preference = match find_preference(name) {
+            Some(preference) if preference.is_valid(value) => preference,
+            _ => return,
+        };
+        let value = value.to_string();
+        match name {
+            "NavMode" => self.nav_mode = value,
+            "NavVerbosity" => self.nav_verbosity = value,
+            "Language" => self.language = value,
+            "SpeechStyle" => self.speech_style = value,
+            "Verbosity" => self.verbosity = value,
+            "SpeechOverrides_CapitalLetters" => self.say_caps = !value.is_empty(),
+            // "Off" (don't speak) isn't a MathCAT value, so it is kept (the GUI disables the TTS control while speech is off)
+            "TTS" => if self.tts != "Off" {self.tts = value},
+            "BrailleCode" => self.braille_code = value,
+            "BrailleNavHighlight" => self.braille_dots78 = value,
+            "Chemistry" => self.chemistry = value != "Off",
+            _ => {
+                self.preferences.retain(|(n, _)| n != name);
+                if value != preference.default {
+                    self.preferences.push( (name.to_string(), value) );
+                }
+            },
+        }
+    }
+
+    /// Give MathCAT the values of the preferences in `group`
+    fn apply_preferences(&self, group: PreferenceGroup) -> Result<()> {
+        for preference in PREFERENCES.iter().filter(|preference| preference.group == group) {
+            set_preference(preference.name.to_string(), self.preference(preference.name))?;
+        }
+        return Ok( () );
+    }
+
+    /// Tell MathCAT about the settings that affect how the MathML is interpreted (e.g., whether chemistry is chemistry).
+    /// MathCAT uses these when it canonicalizes the MathML, so they must be set before the MathML is given to MathCAT.
+    pub fn apply_canonicalization_preferences(&self) -> Result<()> {
+        return self.apply_preferences(PreferenceGroup::Canonicalization);
+    }
+
+    /// Tell MathCAT about the settings that affect speech
+    pub fn apply_speech_preferences(&self) -> Result<()> {
+        return self.apply_preferences(PreferenceGroup::Speech);
+    }
+
+    /// Tell MathCAT about the settings that affect braille
+    pub fn apply_braille_preferences(&self) -> Result<()> {
+        return self.apply_preferences(PreferenceGroup::Braille);
+    }
+
+    /// Tell MathCAT about the settings that affect navigation
+    pub fn apply_navigation_preferences(&self) -> Result<()> {
+        return self.apply_preferences(PreferenceGroup::Navigation);
+    }
+
+    /// Tell MathCAT about all the settings
+    pub fn apply(&self) -> Result<()> {
+        self.apply_canonicalization_preferences()?;
+        self.apply_navigation_preferences()?;
+        self.apply_speech_preferences()?;
+        self.apply_braille_preferences()?;
+        return Ok( () );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cookie_round_trip() {
+        let mut settings = Settings { language: "en-gb".to_string(), say_caps: true, ..Settings::default() };
+        settings.set_preference("BlockSeparators", "\u{00A0}\u{202F}");
+        settings.set_preference("DecimalSeparators", ";=,");
+        let cookie = settings.to_cookie_string();
+        assert!(!cookie.contains('\u{00A0}') && !cookie.contains(";=,"));
+
+        // the browser puts a space after each ';'
+        let mut read = Settings::default();
+        read.set_from_cookie_string(&cookie.replace(';', "; "));
+        assert_eq!(read, settings);
+    }
+
+    #[test]
+    fn unknown_names_and_invalid_values_are_ignored() {
+        let mut settings = Settings::default();
+        settings.set_from_cookie_string("color=red; verbosity=Loud; pitch=99; MathRate=50");
+        assert_eq!(settings.preferences, vec![ ("MathRate".to_string(), "50".to_string()) ]);
+        assert_eq!( (settings.verbosity.as_str(), settings.pitch.as_str()), ("Verbose", "0") );
+    }
+}
@@ -0,0 +1,106 @@
+//! Show (canonicalized) MathML source as syntax-highlighted HTML.
+//!
+//! Each element is wrapped in a `<span class="mml-element" data-id="...">` (using the element's `id`) so that
+//! the element being navigated can be found (and highlighted) and clicking on an element can move the navigation there.
+//! The pretty-printer (`write_xml`) is also used to show SSML (see `ssml::inspect_ssml`).
+use anyhow::Result;
+use roxmltree::Node;
+use crate::convert::ParseError;
+use crate::mathml::escape_xml;
+
+pub(crate) const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// The number of spaces for each level of nesting
+const INDENT: usize = 2;
+
+/// Pretty-print `mathml` as HTML (meant to go in a `<pre>`). Whitespace-only text is dropped.
+pub fn mathml_source_html(mathml: &str) -> Result<String> {
+    let document = match roxmltree::Document::parse(mathml) {
+        Ok(document) => document,
+        Err(e) => return Err( ParseError::from_xml_error("The MathML isn't valid XML", &e, mathml, 0).into() ),
+    };
+    let mut html = String::with_capacity(4 * mathml.len());
+    let mut element_span = |node: Node, _: &[Node]| match node.attribute("id") {
+        Some(id) => format!("<span class=\"mml-element\" data-id=\"{}\">", escape_xml(id)),
+        None => "<span class=\"mml-element\">".to_string(),
+    };
+    write_xml(document.root_element(), 0, "mml-", &mut element_span, &mut html);
+    return Ok(html);
+}
+
+/// The children of `node` that are shown (whitespace-only text is dropped)
+pub(crate) fn content<'a, 'input>(node: Node<'a, 'input>) -> Vec<Node<'a, 'input>> {
+    return node.children()
+        .filter(|child| child.is_element() || (child.is_text() && !child.text().unwrap_or("").trim().is_empty()))
+        .collect();
+}
+
+/// Pretty-print `node` (an element or text) as HTML, one element per line unless it only contains text.
+/// Each element is in the span started by `element_span` (it is given the element and its `content`);
+/// the tags, attributes, and text are in spans with the classes `tag`, `attr-name`, `attr-value`, and `text`
+/// (each starting with `class_prefix`).
+pub(crate) fn write_xml(node: Node, depth: usize, class_prefix: &str,
+                        element_span: &mut dyn FnMut(Node, &[Node]) -> String, html: &mut String) {
+    let indent = " ".repeat(depth * INDENT);
+    if node.is_text() {
+        let text = node.text().unwrap_or("").trim();
+        html.push_str(&format!("{}<span class=\"{}text\">{}</span>\n", indent, class_prefix, escape_xml(text)));
+        return;
+    }
+
+    let name = node.tag_name().name();
+    let children = content(node);
+    html.push_str(&indent);
+    html.push_str(&element_span(node, &children));
+    html.push_str(&format!("<span class=\"{}tag\">&lt;{}</span>", class_prefix, name));
+    for attr in node.attributes() {
+        let prefix = if attr.namespace() == Some(XML_NAMESPACE) {"xml:"} else {""};
+        html.push_str(&format!(" <span class=\"{0}attr-name\">{1}{2}</span>=<span class=\"{0}attr-value\">\"{3}\"</span>",
+                               class_prefix, prefix, attr.name(), escape_xml(attr.value())));
+    }
+    if children.is_empty() {
+        html.push_str(&format!("<span class=\"{}tag\">/&gt;</span></span>\n", class_prefix));
+        return;
+    }
+    html.push_str(&format!("<span class=\"{}tag\">&gt;</span>", class_prefix));
+    let is_leaf = children.iter().all(|child| child.is_text());
+    if is_leaf {
+        let text: String = children.iter().filter_map(|child| child.text()).collect();
+        html.push_str(&format!("<span class=\"{}text\">{}</span>", class_prefix, escape_xml(text.trim())));
+    } else {
+        html.push('\n');
+        for child in children {
+            write_xml(child, depth + 1, class_prefix, element_span, html);
+        }
+        html.push_str(&indent);
+    }
+    html.push_str(&format!("<span class=\"{}tag\">&lt;/{}&gt;</span></span>\n", class_prefix, name));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn element_spans() {
+        let html = mathml_source_html("<math id=\"m\"><mrow id='a&quot;b'><mi>x</mi>\n  <mo id=\"p\">&lt;</mo></mrow></math>").unwrap();
+        assert_eq!(html,
+            "<span class=\"mml-element\" data-id=\"m\"><span class=\"mml-tag\">&lt;math</span> \
+                <span class=\"mml-attr-name\">id</span>=<span class=\"mml-attr-value\">\"m\"</span><span class=\"mml-tag\">&gt;</span>\n\
+             \x20 <span class=\"mml-element\" data-id=\"a&quot;b\"><span class=\"mml-tag\">&lt;mrow</span> \
+                <span class=\"mml-attr-name\">id</span>=<span class=\"mml-attr-value\">\"a&quot;b\"</span><span class=\"mml-tag\">&gt;</span>\n\
+             \x20   <span class=\"mml-element\"><span class=\"mml-tag\">&lt;mi</span><span class=\"mml-tag\">&gt;</span>\
+                <span class=\"mml-text\">x</span><span class=\"mml-tag\">&lt;/mi&gt;</span></span>\n\
+             \x20   <span class=\"mml-element\" data-id=\"p\"><span class=\"mml-tag\">&lt;mo</span> \
+                <span class=\"mml-attr-name\">id</span>=<span class=\"mml-attr-value\">\"p\"</span><span class=\"mml-tag\">&gt;</span>\
+                <span class=\"mml-text\">&lt;</span><span class=\"mml-tag\">&lt;/mo&gt;</span></span>\n\
+             \x20 <span class=\"mml-tag\">&lt;/mrow&gt;</span></span>\n\
+             <span class=\"mml-tag\">&lt;/math&gt;</span></span>\n");
+    }
+
+    #[test]
+    fn invalid_xml() {
+        let e = mathml_source_html("<math><mi>x</math>").unwrap_err();
+        assert!(e.downcast_ref::<ParseError>().expect("a ParseError").message.starts_with("The MathML isn't valid XML"));
+    }
+}
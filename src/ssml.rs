@@ -0,0 +1,158 @@
+//! Inspect the SSML that MathCAT generates: pretty-print it and check it against SSML 1.1.
+//!
+//! MathCAT's SSML is a fragment (there is no `<speak>` element), so it is wrapped in one before being parsed.
+//! The checks are for well-formedness, elements that aren't in SSML 1.1, attributes that an element doesn't have,
+//! and values that aren't allowed for attributes that take a fixed set of values.
+//! The problems are returned as `ParseError`s whose positions are char offsets into the SSML.
+use phf::phf_map;
+use roxmltree::Node;
+use crate::convert::ParseError;
+use crate::mathml::escape_xml;
+use crate::source_view::{XML_NAMESPACE, content, write_xml};
+
+const SSML_NAMESPACE: &str = "http://www.w3.org/2001/10/synthesis";
+
+/// The SSML 1.1 elements and their attributes
+static SSML_ELEMENTS: phf::Map<&'static str, &'static [&'static str]> = phf_map! {
+    "speak" => &["version", "xml:lang", "xml:base", "onlangfailure", "schemaLocation"],
+    "lexicon" => &["uri", "xml:id", "type", "fetchtimeout", "fetchhint", "maxage", "maxstale"],
+    "lookup" => &["ref"],
+    "meta" => &["name", "http-equiv", "content"],
+    "metadata" => &[],
+    "p" => &["xml:lang", "onlangfailure"],
+    "s" => &["xml:lang", "onlangfailure"],
+    "lang" => &["xml:lang", "onlangfailure"],
+    "token" => &["xml:lang", "onlangfailure", "role"],
+    "w" => &["xml:lang", "onlangfailure", "role"],
+    "say-as" => &["interpret-as", "format", "detail"],
+    "phoneme" => &["ph", "alphabet", "type"],
+    "sub" => &["alias"],
+    "voice" => &["gender", "age", "variant", "name", "languages", "required", "ordering", "onvoicefailure"],
+    "emphasis" => &["level"],
+    "break" => &["time", "strength"],
+    "prosody" => &["pitch", "contour", "range", "rate", "duration", "volume"],
+    "audio" => &["src", "fetchtimeout", "fetchhint", "maxage", "maxstale", "clipBegin", "clipEnd",
+                 "repeatCount", "repeatDur", "soundLevel", "speed"],
+    "desc" => &["xml:lang"],
+    "mark" => &["name"],
+};
+
+/// (element, attribute) => the allowed values for attributes that take a fixed set of values
+static ATTRIBUTE_VALUES: phf::Map<&'static str, &'static [&'static str]> = phf_map! {
+    "break strength" => &["none", "x-weak", "weak", "medium", "strong", "x-strong"],
+    "emphasis level" => &["strong", "moderate", "none", "reduced"],
+    "voice gender" => &["male", "female", "neutral"],
+};
+
+/// Elements that can't have any content
+static EMPTY_ELEMENTS: &[&str] = &["break", "mark", "lexicon", "lookup", "meta"];
+
+/// The result of inspecting SSML
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SsmlInspection {
+    /// The pretty-printed SSML as HTML (meant to go in a `<pre>`). Elements with problems have the class `ssml-invalid`.
+    /// If the SSML isn't well-formed, this is the (escaped) SSML as is.
+    pub html: String,
+    pub issues: Vec<ParseError>,
+}
+
+/// Pretty-print `ssml` and check it against SSML 1.1
+pub fn inspect_ssml(ssml: &str) -> SsmlInspection {
+    // MathCAT doesn't generate a `<speak>` element, so one is added (and not shown)
+    let is_wrapped = !ssml.trim_start().starts_with("<speak");
+    let xml = if is_wrapped {format!("<speak>{}</speak>", ssml)} else {ssml.to_string()};
+    let skip = if is_wrapped {"<speak>".len()} else {0};
+    let document = match roxmltree::Document::parse(&xml) {
+        Ok(document) => document,
+        Err(e) => return SsmlInspection {
+            html: escape_xml(ssml),
+            issues: vec![ParseError::from_xml_error("The SSML isn't well-formed", &e, &xml, skip)],
+        },
+    };
+
+    let mut html = String::with_capacity(4 * ssml.len());
+    let mut issues = vec![];
+    // elements with problems are marked (and the problems are shown when hovering over them)
+    let mut element_span = |node: Node, children: &[Node]| {
+        let problems = check_element(node, children);
+        if problems.is_empty() {
+            return "<span class=\"ssml-element\">".to_string();
+        }
+        let start = xml[..node.range().start].chars().count().saturating_sub(skip);
+        for problem in &problems {
+            issues.push(ParseError::new(problem, start, start + 1));
+        }
+        return format!("<span class=\"ssml-element ssml-invalid\" title=\"{}\">", escape_xml(&problems.join("; ")));
+    };
+    let root = document.root_element();
+    if is_wrapped {
+        for child in content(root) {
+            write_xml(child, 0, "ssml-", &mut element_span, &mut html);
+        }
+    } else {
+        write_xml(root, 0, "ssml-", &mut element_span, &mut html);
+    }
+    return SsmlInspection { html, issues };
+}
+
+/// The problems with `node` (an element)
+fn check_element(node: Node, children: &[Node]) -> Vec<String> {
+    let name = node.tag_name().name();
+    let mut problems = vec![];
+    let attributes = match SSML_ELEMENTS.get(name) {
+        Some(attributes) if matches!(node.tag_name().namespace(), None | Some(SSML_NAMESPACE)) => attributes,
+        _ => return vec![format!("'{}' isn't an SSML 1.1 element", name)],
+    };
+    for attr in node.attributes() {
+        let attr_name = match attr.namespace() {
+            Some(XML_NAMESPACE) => format!("xml:{}", attr.name()),
+            _ => attr.name().to_string(),
+        };
+        if !attributes.contains(&attr_name.as_str()) {
+            problems.push(format!("'{}' isn't an attribute of '{}'", attr_name, name));
+        } else if let Some(values) = ATTRIBUTE_VALUES.get(format!("{} {}", name, attr_name).as_str()) {
+            if !values.contains(&attr.value()) {
+                problems.push(format!("'{}' isn't a value of '{}' (it should be one of {})", attr.value(), attr_name, values.join(", ")));
+            }
+        }
+    }
+    if name == "mark" && node.attribute("name").is_none() {
+        problems.push("'mark' needs a 'name'".to_string());
+    }
+    if EMPTY_ELEMENTS.contains(&name) && !children.is_empty() {
+        problems.push(format!("'{}' must be empty", name));
+    }
+    return problems;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pretty_print() {
+        let inspection = inspect_ssml("<prosody rate='90%'>x</prosody> plus <break time='300ms'/>");
+        assert!(inspection.issues.is_empty());
+        assert_eq!(inspection.html,
+            "<span class=\"ssml-element\"><span class=\"ssml-tag\">&lt;prosody</span> \
+                <span class=\"ssml-attr-name\">rate</span>=<span class=\"ssml-attr-value\">\"90%\"</span>\
+                <span class=\"ssml-tag\">&gt;</span><span class=\"ssml-text\">x</span>\
+                <span class=\"ssml-tag\">&lt;/prosody&gt;</span></span>\n\
+             <span class=\"ssml-text\">plus</span>\n\
+             <span class=\"ssml-element\"><span class=\"ssml-tag\">&lt;break</span> \
+                <span class=\"ssml-attr-name\">time</span>=<span class=\"ssml-attr-value\">\"300ms\"</span>\
+                <span class=\"ssml-tag\">/&gt;</span></span>\n");
+    }
+
+    #[test]
+    fn problems() {
+        let inspection = inspect_ssml("a <emphasis level='loud'>b</emphasis> <foo/>");
+        let issues: Vec<(usize, &str)> = inspection.issues.iter().map(|issue| (issue.start, issue.message.as_str())).collect();
+        assert_eq!(issues, vec![
+            (2, "'loud' isn't a value of 'level' (it should be one of strong, moderate, none, reduced)"),
+            (38, "'foo' isn't an SSML 1.1 element"),
+        ]);
+        assert!(inspection.html.contains("<span class=\"ssml-element ssml-invalid\" title=\"'foo' isn't an SSML 1.1 element\">"));
+        assert_eq!(inspect_ssml("<s>x</p>").issues[0].message.split(':').next(), Some("The SSML isn't well-formed"));
+    }
+}
@@ -0,0 +1,1031 @@
+//! Convert TeX (LaTeX math mode, common AMS environments, and mhchem's `\ce{...}`) to MathML.
+//!
+//! This is not a TeX engine -- there are no macro definitions and only math mode is supported.
+//! The output is modeled on what MathJax produces so that MathCAT sees similar MathML whichever converter is used.
+use anyhow::Result;
+use phf::phf_map;
+use crate::mathml::{MathNode, math_to_string};
+use crate::convert::ParseError;
+use crate::mhchem;
+
+/// Convert `tex` to a MathML string (display math)
+pub fn tex_to_mathml(tex: &str) -> Result<String> {
+    return Ok( math_to_string(parse_tex(tex)?, true) );
+}
+
+/// Convert `tex` to a list of MathML nodes (the children of the `math` element)
+pub fn parse_tex(tex: &str) -> Result<Vec<MathNode>> {
+    return parse_tex_at(tex, 0);
+}
+
+/// Like `parse_tex`, but error locations are reported relative to `offset`
+pub(crate) fn parse_tex_at(tex: &str, offset: usize) -> Result<Vec<MathNode>> {
+    let mut parser = TexParser::new(tex, offset);
+    return parser.parse_all();
+}
+
+
+static IDENTIFIERS: phf::Map<&'static str, &'static str> = phf_map! {
+    "alpha" => "α", "beta" => "β", "gamma" => "γ", "delta" => "δ", "epsilon" => "ϵ", "varepsilon" => "ε",
+    "zeta" => "ζ", "eta" => "η", "theta" => "θ", "vartheta" => "ϑ", "iota" => "ι", "kappa" => "κ",
+    "lambda" => "λ", "mu" => "μ", "nu" => "ν", "xi" => "ξ", "omicron" => "ο", "pi" => "π", "varpi" => "ϖ",
+    "rho" => "ρ", "varrho" => "ϱ", "sigma" => "σ", "varsigma" => "ς", "tau" => "τ", "upsilon" => "υ",
+    "phi" => "ϕ", "varphi" => "φ", "chi" => "χ", "psi" => "ψ", "omega" => "ω",
+    "infty" => "∞", "partial" => "∂", "nabla" => "∇", "emptyset" => "∅", "varnothing" => "∅",
+    "hbar" => "ℏ", "ell" => "ℓ", "aleph" => "ℵ", "beth" => "ℶ", "Re" => "ℜ", "Im" => "ℑ", "wp" => "℘",
+    "imath" => "ı", "jmath" => "ȷ", "angle" => "∠", "triangle" => "△", "top" => "⊤", "bot" => "⊥",
+    "forall" => "∀", "exists" => "∃", "nexists" => "∄", "complement" => "∁", "degree" => "°",
+    "Box" => "□", "Diamond" => "◇", "clubsuit" => "♣", "diamondsuit" => "♢", "heartsuit" => "♡",
+    "spadesuit" => "♠", "checkmark" => "✓", "#" => "#", "$" => "$", "%" => "%", "_" => "_",
+};
+
+/// Upper case Greek letters are upright (MathJax adds mathvariant='normal')
+static UPRIGHT_IDENTIFIERS: phf::Map<&'static str, &'static str> = phf_map! {
+    "Gamma" => "Γ", "Delta" => "Δ", "Theta" => "Θ", "Lambda" => "Λ", "Xi" => "Ξ", "Pi" => "Π",
+    "Sigma" => "Σ", "Upsilon" => "Υ", "Phi" => "Φ", "Psi" => "Ψ", "Omega" => "Ω",
+};
+
+static OPERATORS: phf::Map<&'static str, &'static str> = phf_map! {
+    // binary operators
+    "pm" => "±", "mp" => "∓", "times" => "×", "div" => "÷", "cdot" => "⋅", "cdotp" => "⋅", "ast" => "∗",
+    "star" => "⋆", "circ" => "∘", "bullet" => "∙", "oplus" => "⊕", "ominus" => "⊖", "otimes" => "⊗",
+    "oslash" => "⊘", "odot" => "⊙", "cap" => "∩", "cup" => "∪", "sqcap" => "⊓", "sqcup" => "⊔",
+    "vee" => "∨", "lor" => "∨", "wedge" => "∧", "land" => "∧", "setminus" => "∖", "smallsetminus" => "∖",
+    "wr" => "≀", "dagger" => "†", "ddagger" => "‡", "amalg" => "⨿", "uplus" => "⊎", "diamond" => "⋄",
+    "bigtriangleup" => "△", "bigtriangledown" => "▽", "triangleleft" => "◃", "triangleright" => "▹",
+    "boxplus" => "⊞", "boxminus" => "⊟", "boxtimes" => "⊠", "neg" => "¬", "lnot" => "¬", "prime" => "′",
+    // relations
+    "le" => "≤", "leq" => "≤", "ge" => "≥", "geq" => "≥", "leqslant" => "⩽", "geqslant" => "⩾",
+    "ne" => "≠", "neq" => "≠", "equiv" => "≡", "approx" => "≈", "approxeq" => "≊", "cong" => "≅",
+    "sim" => "∼", "simeq" => "≃", "propto" => "∝", "ll" => "≪", "gg" => "≫", "subset" => "⊂",
+    "supset" => "⊃", "subseteq" => "⊆", "supseteq" => "⊇", "subsetneq" => "⊊", "supsetneq" => "⊋",
+    "in" => "∈", "ni" => "∋", "notin" => "∉", "mid" => "∣", "nmid" => "∤", "parallel" => "∥",
+    "nparallel" => "∦", "perp" => "⊥", "vdash" => "⊢", "dashv" => "⊣", "models" => "⊨", "asymp" => "≍",
+    "doteq" => "≐", "prec" => "≺", "succ" => "≻", "preceq" => "⪯", "succeq" => "⪰", "lt" => "<",
+    "gt" => ">", "colon" => ":", "coloneqq" => "≔", "triangleq" => "≜", "nleq" => "≰", "ngeq" => "≱",
+    "nless" => "≮", "ngtr" => "≯", "lesssim" => "≲", "gtrsim" => "≳", "therefore" => "∴",
+    "because" => "∵", "sqsubset" => "⊏", "sqsupset" => "⊐", "sqsubseteq" => "⊑", "sqsupseteq" => "⊒",
+    "nsubseteq" => "⊈", "nsupseteq" => "⊉", "ncong" => "≇", "nsim" => "≁",
+    // arrows
+    "to" => "→", "rightarrow" => "→", "leftarrow" => "←", "gets" => "←", "leftrightarrow" => "↔",
+    "Rightarrow" => "⇒", "Leftarrow" => "⇐", "Leftrightarrow" => "⇔", "iff" => "⟺", "implies" => "⟹",
+    "impliedby" => "⟸", "longrightarrow" => "⟶", "longleftarrow" => "⟵", "longleftrightarrow" => "⟷",
+    "Longrightarrow" => "⟹", "Longleftarrow" => "⟸", "Longleftrightarrow" => "⟺", "mapsto" => "↦",
+    "longmapsto" => "⟼", "uparrow" => "↑", "downarrow" => "↓", "updownarrow" => "↕", "Uparrow" => "⇑",
+    "Downarrow" => "⇓", "Updownarrow" => "⇕", "nearrow" => "↗", "searrow" => "↘", "swarrow" => "↙",
+    "nwarrow" => "↖", "hookrightarrow" => "↪", "hookleftarrow" => "↩", "rightleftharpoons" => "⇌",
+    "leftrightharpoons" => "⇋", "rightharpoonup" => "⇀", "leftharpoonup" => "↼",
+    "rightharpoondown" => "⇁", "leftharpoondown" => "↽", "leadsto" => "⇝", "rightrightarrows" => "⇉",
+    "leftleftarrows" => "⇇", "rightleftarrows" => "⇄", "leftrightarrows" => "⇆",
+    // punctuation, dots, and delimiters
+    "ldots" => "…", "dots" => "…", "dotsc" => "…", "dotso" => "…", "cdots" => "⋯", "dotsb" => "⋯",
+    "dotsm" => "⋯", "vdots" => "⋮", "ddots" => "⋱", "lbrace" => "{", "rbrace" => "}", "{" => "{",
+    "}" => "}", "langle" => "⟨", "rangle" => "⟩", "lfloor" => "⌊", "rfloor" => "⌋", "lceil" => "⌈",
+    "rceil" => "⌉", "vert" => "|", "lvert" => "|", "rvert" => "|", "Vert" => "‖", "lVert" => "‖",
+    "rVert" => "‖", "|" => "‖", "lbrack" => "[", "rbrack" => "]", "backslash" => "\\", "&" => "&",
+};
+
+/// Large operators: the bool is `true` if the limits go above and below (display style)
+static LARGE_OPERATORS: phf::Map<&'static str, (&'static str, bool)> = phf_map! {
+    "sum" => ("∑", true), "prod" => ("∏", true), "coprod" => ("∐", true),
+    "int" => ("∫", false), "iint" => ("∬", false), "iiint" => ("∭", false), "oint" => ("∮", false),
+    "oiint" => ("∯", false), "bigcup" => ("⋃", true), "bigcap" => ("⋂", true), "bigoplus" => ("⨁", true),
+    "bigotimes" => ("⨂", true), "bigodot" => ("⨀", true), "bigvee" => ("⋁", true), "bigwedge" => ("⋀", true),
+    "bigsqcup" => ("⨆", true), "biguplus" => ("⨄", true),
+};
+
+/// Function names: the bool is `true` if the limits go above and below (e.g., `\lim`)
+static FUNCTIONS: phf::Map<&'static str, (&'static str, bool)> = phf_map! {
+    "sin" => ("sin", false), "cos" => ("cos", false), "tan" => ("tan", false), "cot" => ("cot", false),
+    "sec" => ("sec", false), "csc" => ("csc", false), "arcsin" => ("arcsin", false),
+    "arccos" => ("arccos", false), "arctan" => ("arctan", false), "sinh" => ("sinh", false),
+    "cosh" => ("cosh", false), "tanh" => ("tanh", false), "coth" => ("coth", false),
+    "sech" => ("sech", false), "csch" => ("csch", false), "log" => ("log", false), "ln" => ("ln", false),
+    "lg" => ("lg", false), "exp" => ("exp", false), "deg" => ("deg", false), "dim" => ("dim", false),
+    "hom" => ("hom", false), "ker" => ("ker", false), "arg" => ("arg", false), "gcd" => ("gcd", true),
+    "det" => ("det", true), "Pr" => ("Pr", true), "lim" => ("lim", true), "liminf" => ("lim inf", true),
+    "limsup" => ("lim sup", true), "max" => ("max", true), "min" => ("min", true), "sup" => ("sup", true),
+    "inf" => ("inf", true), "injlim" => ("inj lim", true), "projlim" => ("proj lim", true),
+};
+
+/// Accents: (char, is over, is stretchy)
+static ACCENTS: phf::Map<&'static str, (&'static str, bool, bool)> = phf_map! {
+    "hat" => ("^", true, false), "widehat" => ("^", true, true), "check" => ("ˇ", true, false),
+    "widecheck" => ("ˇ", true, true), "tilde" => ("~", true, false), "widetilde" => ("~", true, true),
+    "acute" => ("´", true, false), "grave" => ("`", true, false), "dot" => ("˙", true, false),
+    "ddot" => ("¨", true, false), "dddot" => ("⃛", true, false), "breve" => ("˘", true, false),
+    "bar" => ("¯", true, false), "vec" => ("→", true, false), "mathring" => ("˚", true, false),
+    "overline" => ("¯", true, true), "underline" => ("_", false, true),
+    "overrightarrow" => ("→", true, true), "overleftarrow" => ("←", true, true),
+    "overleftrightarrow" => ("↔", true, true), "underrightarrow" => ("→", false, true),
+    "underleftarrow" => ("←", false, true), "overbrace" => ("⏞", true, true),
+    "underbrace" => ("⏟", false, true),
+};
+
+/// Font commands and the corresponding `mathvariant`
+static FONTS: phf::Map<&'static str, &'static str> = phf_map! {
+    "mathbf" => "bold", "mathrm" => "normal", "mathit" => "italic", "mathbb" => "double-struck",
+    "mathcal" => "script", "mathscr" => "script", "mathfrak" => "fraktur", "mathsf" => "sans-serif",
+    "mathtt" => "monospace", "boldsymbol" => "bold-italic", "bm" => "bold-italic", "mathbfit" => "bold-italic",
+    "mathup" => "normal", "Bbb" => "double-struck",
+};
+
+/// Text commands and the corresponding `mathvariant` ("" means none)
+static TEXT_COMMANDS: phf::Map<&'static str, &'static str> = phf_map! {
+    "text" => "", "textrm" => "", "textnormal" => "", "mbox" => "", "hbox" => "", "textup" => "",
+    "textit" => "italic", "textbf" => "bold", "textsf" => "sans-serif", "texttt" => "monospace",
+};
+
+/// Spacing commands and their width
+static SPACES: phf::Map<&'static str, &'static str> = phf_map! {
+    "," => "0.167em", "thinspace" => "0.167em", ":" => "0.222em", ">" => "0.222em", "medspace" => "0.222em",
+    ";" => "0.278em", "thickspace" => "0.278em", "!" => "-0.167em", "negthinspace" => "-0.167em",
+    " " => "0.25em", "enspace" => "0.5em", "quad" => "1em", "qquad" => "2em",
+};
+
+/// Delimiters that can follow `\left`, `\right`, `\big`, etc.
+static DELIMITERS: phf::Map<&'static str, &'static str> = phf_map! {
+    "{" => "{", "}" => "}", "lbrace" => "{", "rbrace" => "}", "lbrack" => "[", "rbrack" => "]",
+    "langle" => "⟨", "rangle" => "⟩", "vert" => "|", "lvert" => "|", "rvert" => "|", "|" => "‖",
+    "Vert" => "‖", "lVert" => "‖", "rVert" => "‖", "lfloor" => "⌊", "rfloor" => "⌋", "lceil" => "⌈",
+    "rceil" => "⌉", "backslash" => "\\", "uparrow" => "↑", "downarrow" => "↓", "updownarrow" => "↕",
+    "Uparrow" => "⇑", "Downarrow" => "⇓", "Updownarrow" => "⇕", "lgroup" => "⟮", "rgroup" => "⟯",
+    "llbracket" => "⟦", "rrbracket" => "⟧",
+};
+
+/// Sizes for `\big`, `\Big`, `\bigg`, and `\Bigg`
+static BIG_SIZES: phf::Map<&'static str, &'static str> = phf_map! {
+    "big" => "1.2em", "Big" => "1.623em", "bigg" => "2.047em", "Bigg" => "2.470em",
+};
+
+/// Negated forms used by `\not`
+static NEGATIONS: phf::Map<&'static str, &'static str> = phf_map! {
+    "=" => "≠", "<" => "≮", ">" => "≯", "∈" => "∉", "∋" => "∌", "≡" => "≢", "⊂" => "⊄", "⊃" => "⊅",
+    "⊆" => "⊈", "⊇" => "⊉", "≤" => "≰", "≥" => "≱", "∼" => "≁", "≈" => "≉", "≅" => "≇", "∣" => "∤",
+    "∥" => "∦", "≃" => "≄", "≺" => "⊀", "≻" => "⊁", "∃" => "∄",
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// The name of the command (without the backslash)
+    Command(String),
+    Char(char),
+    BeginGroup,
+    EndGroup,
+    Superscript,
+    Subscript,
+    Ampersand,
+    Eof,
+}
+
+/// What caused `parse_expression` to stop
+#[derive(Debug, Clone, PartialEq)]
+enum Stop {
+    Eof,
+    EndGroup,
+    Ampersand,
+    NewRow,
+    Right(String),
+    End(String),
+}
+
+/// A parsed piece of TeX along with info about how scripts attach to it
+struct Atom {
+    node: MathNode,
+    /// `true` for things like `\sin` that should be followed by an invisible function application
+    is_function: bool,
+    /// `true` if sub/superscripts should be placed under/over the atom
+    limits: bool,
+}
+
+impl Atom {
+    fn new(node: MathNode) -> Atom {
+        return Atom { node, is_function: false, limits: false };
+    }
+}
+
+struct TexParser {
+    chars: Vec<char>,
+    pos: usize,
+    /// where the last token returned by `next_token` started
+    token_start: usize,
+    /// added to positions in error messages (used when parsing a part of a larger string)
+    offset: usize,
+    /// the mathvariant set by a font command such as `\mathbf`
+    font: Option<&'static str>,
+    /// numbers are a single digit when they are an argument (e.g, `\frac12`)
+    single_char: bool,
+}
+
+impl TexParser {
+    fn new(tex: &str, offset: usize) -> TexParser {
+        return TexParser {
+            chars: tex.chars().collect(),
+            pos: 0,
+            token_start: 0,
+            offset,
+            font: None,
+            single_char: false,
+        };
+    }
+
+    fn error(&self, start: usize, message: &str) -> anyhow::Error {
+        return ParseError::new(message, self.offset + start, self.offset + self.pos.max(start + 1)).into();
+    }
+
+    fn skip_white_space(&mut self) {
+        while let Some(&ch) = self.chars.get(self.pos) {
+            if ch == '%' {
+                while self.pos < self.chars.len() && self.chars[self.pos] != '\n' {
+                    self.pos += 1;
+                }
+            } else if ch.is_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Token {
+        self.skip_white_space();
+        self.token_start = self.pos;
+        let ch = match self.chars.get(self.pos) {
+            None => return Token::Eof,
+            Some(&ch) => ch,
+        };
+        self.pos += 1;
+        return match ch {
+            '{' => Token::BeginGroup,
+            '}' => Token::EndGroup,
+            '^' => Token::Superscript,
+            '_' => Token::Subscript,
+            '&' => Token::Ampersand,
+            '\\' => match self.chars.get(self.pos) {
+                None => Token::Command(String::default()),
+                Some(ch) if ch.is_ascii_alphabetic() => {
+                    let start = self.pos;
+                    while self.pos < self.chars.len() && self.chars[self.pos].is_ascii_alphabetic() {
+                        self.pos += 1;
+                    }
+                    Token::Command(self.chars[start..self.pos].iter().collect())
+                },
+                Some(&ch) => {
+                    self.pos += 1;
+                    Token::Command(ch.to_string())
+                },
+            },
+            _ => Token::Char(ch),
+        };
+    }
+
+    fn peek_token(&mut self) -> Token {
+        let (pos, token_start) = (self.pos, self.token_start);
+        let token = self.next_token();
+        self.pos = pos;
+        self.token_start = token_start;
+        return token;
+    }
+
+    /// Parse everything (the top level)
+    fn parse_all(&mut self) -> Result<Vec<MathNode>> {
+        let mut nodes = vec![];
+        loop {
+            let (mut more, stop) = self.parse_expression()?;
+            nodes.append(&mut more);
+            match stop {
+                Stop::Eof => return Ok(nodes),
+                Stop::NewRow => nodes.push(MathNode::new("mspace", vec![]).attr("linebreak", "newline")),
+                Stop::EndGroup => return Err(self.error(self.token_start, "Extra '}' (no matching '{')")),
+                Stop::Ampersand => return Err(self.error(self.token_start, "Misplaced '&' (only allowed in a table environment)")),
+                Stop::Right(_) => return Err(self.error(self.token_start, "Extra \\right (no matching \\left)")),
+                Stop::End(name) => return Err(self.error(self.token_start, &format!("\\end{{{}}} without matching \\begin{{{}}}", name, name))),
+            }
+        }
+    }
+
+    /// Parse the contents of `{...}` (the '{' has already been read)
+    fn parse_group(&mut self, start: usize) -> Result<Vec<MathNode>> {
+        let mut nodes = vec![];
+        loop {
+            let (mut more, stop) = self.parse_expression()?;
+            nodes.append(&mut more);
+            match stop {
+                Stop::EndGroup => return Ok(nodes),
+                Stop::NewRow => nodes.push(MathNode::new("mspace", vec![]).attr("linebreak", "newline")),
+                Stop::Eof => return Err(self.error(start, "Missing '}'")),
+                Stop::Ampersand => return Err(self.error(self.token_start, "Misplaced '&' (only allowed in a table environment)")),
+                Stop::Right(_) => return Err(self.error(self.token_start, "Extra \\right (no matching \\left)")),
+                Stop::End(name) => return Err(self.error(self.token_start, &format!("\\end{{{}}} inside of a group", name))),
+            }
+        }
+    }
+
+    /// Parse until something that ends an expression (e.g., '}' or '&') and return what stopped the parse
+    fn parse_expression(&mut self) -> Result<(Vec<MathNode>, Stop)> {
+        let mut nodes = vec![];
+        loop {
+            let start = self.pos;
+            match self.next_token() {
+                Token::Eof => return Ok( (nodes, Stop::Eof) ),
+                Token::EndGroup => return Ok( (nodes, Stop::EndGroup) ),
+                Token::Ampersand => return Ok( (nodes, Stop::Ampersand) ),
+                Token::Command(name) => match name.as_str() {
+                    "\\" | "cr" | "newline" => {
+                        self.read_optional_raw_arg()?;      // e.g., \\[2pt]
+                        return Ok( (nodes, Stop::NewRow) );
+                    },
+                    "right" => {
+                        let delimiter = self.read_delimiter()?;
+                        return Ok( (nodes, Stop::Right(delimiter)) );
+                    },
+                    "end" => {
+                        let (name, _) = self.read_raw_arg()?;
+                        return Ok( (nodes, Stop::End(name.trim().to_string())) );
+                    },
+                    "over" | "atop" | "choose" => {
+                        // infix: everything before is the numerator, everything after is the denominator
+                        let (denominator, stop) = self.parse_expression()?;
+                        let mut frac = MathNode::new("mfrac", vec![MathNode::mrow(nodes), MathNode::mrow(denominator)]);
+                        if name != "over" {
+                            frac.set_attr("linethickness", "0");
+                        }
+                        let frac = if name == "choose" {wrap_in_parens(frac, "(", ")")} else {frac};
+                        return Ok( (vec![frac], stop) );
+                    },
+                    "color" => {
+                        // applies to the rest of the group
+                        let (color, _) = self.read_raw_arg()?;
+                        let (rest, stop) = self.parse_expression()?;
+                        nodes.push( MathNode::new("mstyle", rest).attr("mathcolor", color.trim()) );
+                        return Ok( (nodes, stop) );
+                    },
+                    "displaystyle" | "textstyle" | "scriptstyle" | "scriptscriptstyle" => {
+                        let (rest, stop) = self.parse_expression()?;
+                        let mut style = MathNode::new("mstyle", rest);
+                        match name.as_str() {
+                            "displaystyle" => style.set_attr("displaystyle", "true"),
+                            "textstyle" => style.set_attr("displaystyle", "false"),
+                            "scriptstyle" => style.set_attr("scriptlevel", "1"),
+                            _ => style.set_attr("scriptlevel", "2"),
+                        }
+                        nodes.push(style);
+                        return Ok( (nodes, stop) );
+                    },
+                    _ => {
+                        self.pos = start;
+                        nodes.append(&mut self.parse_item()?);
+                    },
+                },
+                _ => {
+                    self.pos = start;
+                    nodes.append(&mut self.parse_item()?);
+                },
+            }
+        }
+    }
+
+    /// Parse an atom and any scripts on it.
+    /// Usually this is a single node, but function names are followed by an invisible function application,
+    /// and some commands (e.g., `\label`) produce nothing.
+    fn parse_item(&mut self) -> Result<Vec<MathNode>> {
+        let atom = match self.parse_atom()? {
+            None => return Ok(vec![]),
+            Some(atom) => atom,
+        };
+        let is_function = atom.is_function;
+        let node = self.parse_scripts(atom)?;
+        return Ok( if is_function {vec![node, MathNode::mo("\u{2061}")]} else {vec![node]} );
+    }
+
+    /// Parse any sub/superscripts (and primes) that follow `atom`
+    fn parse_scripts(&mut self, atom: Atom) -> Result<MathNode> {
+        let mut limits = atom.limits;
+        let mut sub = None;
+        let mut sup = None;
+        let mut primes = String::default();
+        loop {
+            let start = self.pos;
+            match self.next_token() {
+                Token::Superscript => {
+                    if sup.is_some() {
+                        return Err(self.error(self.token_start, "Double superscript (use braces to clarify)"));
+                    }
+                    sup = Some(self.parse_argument()?);
+                },
+                Token::Subscript => {
+                    if sub.is_some() {
+                        return Err(self.error(self.token_start, "Double subscript (use braces to clarify)"));
+                    }
+                    sub = Some(self.parse_argument()?);
+                },
+                Token::Char('\'') => primes.push('′'),
+                Token::Command(name) if name == "limits" => limits = true,
+                Token::Command(name) if name == "nolimits" => limits = false,
+                _ => {
+                    self.pos = start;
+                    break;
+                }
+            }
+        }
+        if !primes.is_empty() {
+            let primes = MathNode::mo(&primes);
+            sup = Some( match sup {
+                None => primes,
+                Some(sup) => MathNode::mrow(vec![primes, sup]),
+            });
+        }
+
+        let base = atom.node;
+        return Ok( match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) => MathNode::new(if limits {"munder"} else {"msub"}, vec![base, sub]),
+            (None, Some(sup)) => MathNode::new(if limits {"mover"} else {"msup"}, vec![base, sup]),
+            (Some(sub), Some(sup)) => MathNode::new(if limits {"munderover"} else {"msubsup"}, vec![base, sub, sup]),
+        });
+    }
+
+    /// Parse a required argument: either `{...}` or a single token
+    fn parse_argument(&mut self) -> Result<MathNode> {
+        let start = self.pos;
+        match self.peek_token() {
+            Token::BeginGroup => {
+                self.next_token();
+                let start = self.token_start;
+                // a braced argument is ordinary math, even when it is the argument of a command in a script (e.g., `x^\sqrt{12}`)
+                let single_char = self.single_char;
+                self.single_char = false;
+                let group = self.parse_group(start);
+                self.single_char = single_char;
+                return Ok( MathNode::mrow(group?) );
+            },
+            Token::Eof | Token::EndGroup | Token::Ampersand | Token::Superscript | Token::Subscript => {
+                self.next_token();
+                return Err(self.error(self.token_start, "Missing argument"));
+            },
+            _ => (),
+        }
+        let single_char = self.single_char;
+        self.single_char = true;
+        let atom = self.parse_atom();
+        self.single_char = single_char;
+        return match atom? {
+            Some(atom) => Ok(atom.node),
+            None => Err(self.error(start, "Missing argument")),
+        };
+    }
+
+    /// Parse the next thing (without any scripts)
+    fn parse_atom(&mut self) -> Result<Option<Atom>> {
+        let start = self.pos;
+        return match self.next_token() {
+            Token::BeginGroup => {
+                let start = self.token_start;
+                let nodes = self.parse_group(start)?;
+                Ok( Some(Atom::new(MathNode::mrow(nodes))) )
+            },
+            Token::Superscript | Token::Subscript => {
+                // script with no base
+                self.pos = start;
+                Ok( Some(Atom::new(MathNode::new("mrow", vec![]))) )
+            },
+            Token::Char(ch) => Ok( Some(self.parse_char(ch)) ),
+            Token::Command(name) => self.parse_command(&name),
+            Token::EndGroup => Err(self.error(self.token_start, "Unexpected '}'")),
+            Token::Ampersand => Err(self.error(self.token_start, "Misplaced '&'")),
+            Token::Eof => Err(self.error(self.token_start, "Unexpected end of input")),
+        };
+    }
+
+    fn parse_char(&mut self, ch: char) -> Atom {
+        if ch.is_ascii_digit() || (ch == '.' && self.chars.get(self.pos).is_some_and(|ch| ch.is_ascii_digit())) {
+            let start = self.pos - 1;
+            if !self.single_char {
+                let mut seen_point = ch == '.';
+                while let Some(&ch) = self.chars.get(self.pos) {
+                    if ch.is_ascii_digit() {
+                        self.pos += 1;
+                    } else if ch == '.' && !seen_point && self.chars.get(self.pos + 1).is_some_and(|ch| ch.is_ascii_digit()) {
+                        seen_point = true;
+                        self.pos += 1;
+                    } else {
+                        break;
+                    }
+                }
+            }
+            let number: String = self.chars[start..self.pos].iter().collect();
+            return Atom::new(self.with_font(MathNode::mn(&number), false));
+        }
+
+        if ch.is_alphabetic() {
+            if self.font == Some("normal") && !self.single_char {
+                // e.g., \mathrm{abc} is a single identifier
+                let start = self.pos - 1;
+                while self.pos < self.chars.len() && self.chars[self.pos].is_alphabetic() {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                return Atom::new(self.with_font(MathNode::mi(&name), true));
+            }
+            return Atom::new(self.with_font(MathNode::mi(&ch.to_string()), true));
+        }
+
+        return Atom::new( match ch {
+            '-' => MathNode::mo("−"),
+            '*' => MathNode::mo("∗"),
+            '\'' => MathNode::mo("′"),
+            '~' => MathNode::mtext("\u{A0}"),
+            '(' | ')' | '[' | ']' | '|' => MathNode::mo(&ch.to_string()).attr("stretchy", "false"),
+            _ if ch.is_numeric() => MathNode::mn(&ch.to_string()),
+            _ => MathNode::mo(&ch.to_string()),
+        });
+    }
+
+    /// Add the current font (if any) as a mathvariant.
+    /// Single letter identifiers are italic by default, so that needs to be explicitly reset.
+    fn with_font(&self, node: MathNode, is_identifier: bool) -> MathNode {
+        return match self.font {
+            None => node,
+            Some("italic") if is_identifier && node.text.chars().count() == 1 => node,
+            Some("normal") if !is_identifier => node,
+            Some(variant) => node.attr("mathvariant", variant),
+        };
+    }
+
+    fn parse_command(&mut self, name: &str) -> Result<Option<Atom>> {
+        let start = self.token_start;
+        if let Some(&text) = IDENTIFIERS.get(name) {
+            return Ok( Some(Atom::new(self.with_font(MathNode::mi(text), true))) );
+        }
+        if let Some(&text) = UPRIGHT_IDENTIFIERS.get(name) {
+            let node = MathNode::mi(text);
+            let node = if self.font.is_none() {node.attr("mathvariant", "normal")} else {self.with_font(node, true)};
+            return Ok( Some(Atom::new(node)) );
+        }
+        if let Some(&text) = OPERATORS.get(name) {
+            return Ok( Some(Atom::new(MathNode::mo(text))) );
+        }
+        if let Some(&(text, limits)) = LARGE_OPERATORS.get(name) {
+            return Ok( Some(Atom{ node: MathNode::mo(text).attr("largeop", "true"), is_function: false, limits }) );
+        }
+        if let Some(&(text, limits)) = FUNCTIONS.get(name) {
+            return Ok( Some(Atom{ node: MathNode::mi(text), is_function: true, limits }) );
+        }
+        if let Some(&width) = SPACES.get(name) {
+            return Ok( Some(Atom::new(MathNode::new("mspace", vec![]).attr("width", width))) );
+        }
+        if let Some(&(accent, is_over, is_stretchy)) = ACCENTS.get(name) {
+            let base = self.parse_argument()?;
+            let accent = MathNode::mo(accent).attr("stretchy", if is_stretchy {"true"} else {"false"});
+            let node = if is_over {
+                MathNode::new("mover", vec![base, accent]).attr("accent", "true")
+            } else {
+                MathNode::new("munder", vec![base, accent]).attr("accentunder", "true")
+            };
+            // scripts on braces go above/below them
+            let limits = name == "overbrace" || name == "underbrace";
+            return Ok( Some(Atom{ node, is_function: false, limits }) );
+        }
+        if let Some(&variant) = FONTS.get(name) {
+            let font = self.font;
+            self.font = Some(variant);
+            let arg = self.parse_argument();
+            self.font = font;
+            return Ok( Some(Atom::new(arg?)) );
+        }
+        if let Some(&variant) = TEXT_COMMANDS.get(name) {
+            let (text, offset) = self.read_raw_arg()?;
+            return Ok( Some(Atom::new(self.parse_text(&text, offset, variant)?)) );
+        }
+        if let Some(&size) = BIG_SIZES.get(name.trim_end_matches(['l', 'r', 'm'])) {
+            let delimiter = self.read_delimiter()?;
+            return Ok( Some(Atom::new(MathNode::mo(&delimiter).attr("minsize", size).attr("maxsize", size))) );
+        }
+
+        let node = match name {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let numerator = self.parse_argument()?;
+                let denominator = self.parse_argument()?;
+                MathNode::new("mfrac", vec![numerator, denominator])
+            },
+            "binom" | "dbinom" | "tbinom" => {
+                let top = self.parse_argument()?;
+                let bottom = self.parse_argument()?;
+                wrap_in_parens(MathNode::new("mfrac", vec![top, bottom]).attr("linethickness", "0"), "(", ")")
+            },
+            "sqrt" => {
+                let index = self.read_optional_raw_arg()?;
+                let radicand = self.parse_argument()?;
+                match index {
+                    None => MathNode::new("msqrt", vec![radicand]),
+                    Some((index, offset)) => MathNode::new("mroot", vec![radicand, MathNode::mrow(self.parse_sub(&index, offset)?)]),
+                }
+            },
+            "left" => {
+                let open = self.read_delimiter()?;
+                let (mut nodes, stop) = self.parse_expression()?;
+                let close = match stop {
+                    Stop::Right(close) => close,
+                    _ => return Err(self.error(start, "Missing \\right")),
+                };
+                let mut children = Vec::with_capacity(nodes.len() + 2);
+                if !open.is_empty() {
+                    children.push(MathNode::mo(&open).attr("fence", "true").attr("stretchy", "true"));
+                }
+                children.append(&mut nodes);
+                if !close.is_empty() {
+                    children.push(MathNode::mo(&close).attr("fence", "true").attr("stretchy", "true"));
+                }
+                MathNode::new("mrow", children)
+            },
+            "middle" => {
+                let delimiter = self.read_delimiter()?;
+                MathNode::mo(&delimiter).attr("stretchy", "true")
+            },
+            "overset" | "underset" | "stackrel" => {
+                let script = self.parse_argument()?;
+                let base = self.parse_argument()?;
+                MathNode::new(if name == "underset" {"munder"} else {"mover"}, vec![base, script])
+            },
+            "xrightarrow" | "xleftarrow" | "xleftrightarrow" | "xRightarrow" | "xLeftarrow" => {
+                let arrow = match name {
+                    "xrightarrow" => "→", "xleftarrow" => "←", "xleftrightarrow" => "↔",
+                    "xRightarrow" => "⇒", _ => "⇐",
+                };
+                let arrow = MathNode::mo(arrow).attr("stretchy", "true");
+                let below = self.read_optional_raw_arg()?;
+                let above = self.parse_argument()?;
+                match below {
+                    None => MathNode::new("mover", vec![arrow, above]),
+                    Some((below, offset)) => MathNode::new("munderover", vec![arrow, MathNode::mrow(self.parse_sub(&below, offset)?), above]),
+                }
+            },
+            "operatorname" | "operatornamewithlimits" => {
+                let limits = self.chars.get(self.pos) == Some(&'*') || name == "operatornamewithlimits";
+                if self.chars.get(self.pos) == Some(&'*') {
+                    self.pos += 1;
+                }
+                let (text, _) = self.read_raw_arg()?;
+                let text = text.replace("\\,", " ").replace("\\", "");
+                return Ok( Some(Atom{ node: MathNode::mi(text.trim()), is_function: true, limits }) );
+            },
+            "mathop" => {
+                let node = self.parse_argument()?;
+                return Ok( Some(Atom{ node, is_function: false, limits: true }) );
+            },
+            "mathbin" | "mathrel" | "mathord" | "mathopen" | "mathclose" | "mathpunct" | "mathinner" => self.parse_argument()?,
+            "not" => {
+                let arg = self.parse_argument()?;
+                if arg.is_token() {
+                    match NEGATIONS.get(arg.text.as_str()) {
+                        Some(&negation) => MathNode::mo(negation),
+                        None => MathNode::mo(&(arg.text.clone() + "\u{338}")),
+                    }
+                } else {
+                    MathNode::new("menclose", vec![arg]).attr("notation", "updiagonalstrike")
+                }
+            },
+            "bmod" => MathNode::mo("mod"),
+            "pmod" | "mod" => {
+                let arg = self.parse_argument()?;
+                let space = MathNode::new("mspace", vec![]).attr("width", "1em");
+                if name == "pmod" {
+                    MathNode::new("mrow", vec![space, MathNode::mo("("), MathNode::mi("mod"), MathNode::new("mspace", vec![]).attr("width", "0.333em"), arg, MathNode::mo(")")])
+                } else {
+                    MathNode::new("mrow", vec![space, MathNode::mi("mod"), MathNode::new("mspace", vec![]).attr("width", "0.333em"), arg])
+                }
+            },
+            "boxed" | "fbox" => MathNode::new("menclose", vec![self.parse_argument()?]).attr("notation", "box"),
+            "cancel" => MathNode::new("menclose", vec![self.parse_argument()?]).attr("notation", "updiagonalstrike"),
+            "bcancel" => MathNode::new("menclose", vec![self.parse_argument()?]).attr("notation", "downdiagonalstrike"),
+            "xcancel" => MathNode::new("menclose", vec![self.parse_argument()?]).attr("notation", "updiagonalstrike downdiagonalstrike"),
+            "phantom" => MathNode::new("mphantom", vec![self.parse_argument()?]),
+            "textcolor" => {
+                let (color, _) = self.read_raw_arg()?;
+                MathNode::new("mstyle", vec![self.parse_argument()?]).attr("mathcolor", color.trim())
+            },
+            "colorbox" => {
+                let (color, _) = self.read_raw_arg()?;
+                MathNode::new("mstyle", vec![self.parse_argument()?]).attr("mathbackground", color.trim())
+            },
+            "hspace" | "kern" | "mkern" | "mspace" => {
+                let (width, _) = self.read_raw_arg()?;
+                MathNode::new("mspace", vec![]).attr("width", width.trim())
+            },
+            "begin" => {
+                let (env_name, _) = self.read_raw_arg()?;
+                self.parse_environment(env_name.trim(), start)?
+            },
+            "ce" => {
+                let (text, offset) = self.read_raw_arg()?;
+                MathNode::mrow(mhchem::parse_ce(&text, self.offset + offset)?)
+            },
+            "pu" => {
+                let (text, offset) = self.read_raw_arg()?;
+                MathNode::mrow(mhchem::parse_pu(&text, self.offset + offset)?)
+            },
+            // these don't produce any output
+            "label" | "tag" | "ref" | "eqref" => {
+                self.read_raw_arg()?;
+                return Ok(None);
+            },
+            "nonumber" | "notag" | "hline" | "hdashline" | "limits" | "nolimits" | "strut" | "relax" => return Ok(None),
+            _ => return Err(self.error(start, &format!("Unknown TeX command '\\{}'", name))),
+        };
+        return Ok( Some(Atom::new(node)) );
+    }
+
+    /// Parse the contents of `\text{...}` -- `$...$` inside of the text is math
+    fn parse_text(&mut self, text: &str, offset: usize, variant: &str) -> Result<MathNode> {
+        let mut nodes = vec![];
+        let mut start = 0;
+        for (i, part) in text.split('$').enumerate() {
+            if i % 2 == 0 {
+                if !part.is_empty() {
+                    let mtext = MathNode::mtext(&part.replace(' ', "\u{A0}"));
+                    nodes.push( if variant.is_empty() {mtext} else {mtext.attr("mathvariant", variant)} );
+                }
+            } else {
+                nodes.append(&mut self.parse_sub(part, offset + start)?);
+            }
+            start += part.chars().count() + 1;
+        }
+        return Ok( if nodes.is_empty() {MathNode::mtext("")} else {MathNode::mrow(nodes)} );
+    }
+
+    fn parse_environment(&mut self, name: &str, start: usize) -> Result<MathNode> {
+        let fences = match name {
+            "pmatrix" | "pmatrix*" => Some( ("(", ")") ),
+            "bmatrix" | "bmatrix*" => Some( ("[", "]") ),
+            "Bmatrix" | "Bmatrix*" => Some( ("{", "}") ),
+            "vmatrix" | "vmatrix*" => Some( ("|", "|") ),
+            "Vmatrix" | "Vmatrix*" => Some( ("‖", "‖") ),
+            _ => None,
+        };
+        if let Some((open, close)) = fences {
+            let table = self.parse_table(name, start, None)?;
+            return Ok( wrap_in_parens(table, open, close) );
+        }
+
+        return Ok( match name {
+            "matrix" | "matrix*" | "smallmatrix" => self.parse_table(name, start, None)?,
+            "array" | "darray" | "subarray" => {
+                let (column_spec, _) = self.read_raw_arg()?;
+                let columnalign = column_spec.chars()
+                        .filter_map(|ch| match ch {'l' => Some("left"), 'r' => Some("right"), 'c' => Some("center"), _ => None})
+                        .collect::<Vec<&str>>()
+                        .join(" ");
+                self.parse_table(name, start, Some(&columnalign))?
+            },
+            "cases" | "dcases" => wrap_in_parens(self.parse_table(name, start, Some("left left"))?, "{", ""),
+            "rcases" => wrap_in_parens(self.parse_table(name, start, Some("left left"))?, "", "}"),
+            "align" | "align*" | "aligned" | "split" | "eqnarray" | "eqnarray*" | "flalign" | "flalign*" |
+            "alignat" | "alignat*" | "alignedat" => {
+                if name.starts_with("alignat") || name == "alignedat" {
+                    self.read_raw_arg()?;       // number of columns
+                }
+                self.parse_table(name, start, Some("right left right left right left"))?.attr("displaystyle", "true")
+            },
+            "gather" | "gather*" | "gathered" | "multline" | "multline*" => {
+                self.parse_table(name, start, Some("center"))?.attr("displaystyle", "true")
+            },
+            "equation" | "equation*" | "displaymath" | "math" => {
+                let (nodes, stop) = self.parse_expression()?;
+                match stop {
+                    Stop::End(end_name) if end_name == name => MathNode::mrow(nodes),
+                    _ => return Err(self.error(start, &format!("Missing \\end{{{}}}", name))),
+                }
+            },
+            _ => return Err(self.error(start, &format!("Unknown environment '{}'", name))),
+        });
+    }
+
+    /// Parse rows (separated by `\\`) of cells (separated by `&`) until `\end{name}`
+    fn parse_table(&mut self, name: &str, start: usize, columnalign: Option<&str>) -> Result<MathNode> {
+        let mut rows = vec![];
+        let mut row = vec![];
+        loop {
+            let (cell, stop) = self.parse_expression()?;
+            let cell = MathNode::new("mtd", cell);
+            match stop {
+                Stop::Ampersand => row.push(cell),
+                Stop::NewRow => {
+                    row.push(cell);
+                    rows.push(MathNode::new("mtr", row));
+                    row = vec![];
+                },
+                Stop::End(end_name) => {
+                    if end_name != name {
+                        return Err(self.error(self.token_start, &format!("\\begin{{{}}} ended by \\end{{{}}}", name, end_name)));
+                    }
+                    // a trailing `\\` doesn't start a new row
+                    if !(row.is_empty() && cell.children.is_empty()) {
+                        row.push(cell);
+                        rows.push(MathNode::new("mtr", row));
+                    }
+                    break;
+                },
+                Stop::Eof => return Err(self.error(start, &format!("Missing \\end{{{}}}", name))),
+                Stop::EndGroup => return Err(self.error(self.token_start, "Extra '}' (no matching '{')")),
+                Stop::Right(_) => return Err(self.error(self.token_start, "Extra \\right (no matching \\left)")),
+            }
+        }
+        let table = MathNode::new("mtable", rows);
+        return Ok( match columnalign {
+            None => table,
+            Some(columnalign) => table.attr("columnalign", columnalign),
+        });
+    }
+
+    /// Parse `text` (part of the string being parsed) with a new parser.
+    /// This is used for things like optional arguments which are read without parsing them.
+    fn parse_sub(&self, text: &str, offset: usize) -> Result<Vec<MathNode>> {
+        let mut parser = TexParser::new(text, self.offset + offset);
+        parser.font = self.font;
+        return parser.parse_all();
+    }
+
+    /// Read the delimiter after `\left`, `\right`, `\big`, etc. `\left.` is returned as an empty string.
+    fn read_delimiter(&mut self) -> Result<String> {
+        return match self.next_token() {
+            Token::Char('.') => Ok( String::default() ),
+            Token::Char('<') => Ok( "⟨".to_string() ),
+            Token::Char('>') => Ok( "⟩".to_string() ),
+            Token::Char(ch) if "()[]|/".contains(ch) => Ok( ch.to_string() ),
+            Token::Command(name) if DELIMITERS.contains_key(name.as_str()) => Ok( DELIMITERS.get(name.as_str()).unwrap().to_string() ),
+            _ => Err(self.error(self.token_start, "Missing or unrecognized delimiter")),
+        };
+    }
+
+    /// Read a `{...}` argument without interpreting it.
+    /// The text inside the braces is returned along with its position.
+    fn read_raw_arg(&mut self) -> Result<(String, usize)> {
+        self.skip_white_space();
+        if self.chars.get(self.pos) != Some(&'{') {
+            let token = self.next_token();
+            if token == Token::Eof {
+                return Err(self.error(self.token_start, "Missing argument"));
+            }
+            return Ok( (self.chars[self.token_start..self.pos].iter().collect(), self.token_start) );
+        }
+        let start = self.pos;
+        let end = self.find_matching(start, '{', '}')
+                    .ok_or_else(|| self.error(start, "Missing '}'"))?;
+        self.pos = end + 1;
+        return Ok( (self.chars[start+1..end].iter().collect(), start+1) );
+    }
+
+    /// Read a `[...]` argument (if present) without interpreting it.
+    fn read_optional_raw_arg(&mut self) -> Result<Option<(String, usize)>> {
+        let pos = self.pos;
+        self.skip_white_space();
+        if self.chars.get(self.pos) != Some(&'[') {
+            self.pos = pos;
+            return Ok(None);
+        }
+        let start = self.pos;
+        let end = self.find_matching(start, '[', ']')
+                    .ok_or_else(|| self.error(start, "Missing ']'"))?;
+        self.pos = end + 1;
+        return Ok( Some( (self.chars[start+1..end].iter().collect(), start+1) ) );
+    }
+
+    /// Find the index of the `close` char that matches the `open` char at `start`.
+    /// Braces nest, and escaped chars (e.g., `\}`) are skipped.
+    fn find_matching(&self, start: usize, open: char, close: char) -> Option<usize> {
+        let mut depth = 0;
+        let mut brace_depth = 0;
+        let mut i = start;
+        while i < self.chars.len() {
+            let ch = self.chars[i];
+            if ch == '\\' {
+                i += 2;
+                continue;
+            }
+            if open != '{' {
+                if ch == '{' {
+                    brace_depth += 1;
+                } else if ch == '}' {
+                    brace_depth -= 1;
+                }
+            }
+            if brace_depth == 0 {
+                if ch == open {
+                    depth += 1;
+                } else if ch == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                }
+            }
+            i += 1;
+        }
+        return None;
+    }
+}
+
+/// Put fences around `node`. An empty string means no fence.
+fn wrap_in_parens(node: MathNode, open: &str, close: &str) -> MathNode {
+    let mut children = vec![];
+    if !open.is_empty() {
+        children.push(MathNode::mo(open));
+    }
+    children.push(node);
+    if !close.is_empty() {
+        children.push(MathNode::mo(close));
+    }
+    return MathNode::new("mrow", children);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The MathML for `tex` without the `<math>` wrapper
+    fn mathml(tex: &str) -> String {
+        let mathml = tex_to_mathml(tex).unwrap();
+        return mathml.trim_start_matches("<math display=\"block\">").trim_end_matches("</math>").to_string();
+    }
+
+    /// The position and message of the error for `tex`
+    fn error(tex: &str) -> (usize, String) {
+        let e = tex_to_mathml(tex).unwrap_err();
+        let e = e.downcast_ref::<ParseError>().expect("a ParseError");
+        return (e.start, e.message.clone());
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(mathml(r"\frac{a}{b}"), "<mfrac><mi>a</mi><mi>b</mi></mfrac>");
+        assert_eq!(mathml(r"{a \over b}"), "<mfrac><mi>a</mi><mi>b</mi></mfrac>");
+    }
+
+    #[test]
+    fn roots() {
+        assert_eq!(mathml(r"\sqrt[3]{x}"), "<mroot><mi>x</mi><mn>3</mn></mroot>");
+        assert_eq!(mathml(r"\sqrt{12}"), "<msqrt><mn>12</mn></msqrt>");
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(mathml("x_i^2"), "<msubsup><mi>x</mi><mi>i</mi><mn>2</mn></msubsup>");
+        assert_eq!(mathml("x^12"), "<msup><mi>x</mi><mn>1</mn></msup><mn>2</mn>");
+        assert_eq!(mathml("x^{12}"), "<msup><mi>x</mi><mn>12</mn></msup>");
+    }
+
+    #[test]
+    fn braced_argument_in_script() {
+        assert_eq!(mathml(r"x^\mathbf{12}"), "<msup><mi>x</mi><mn mathvariant=\"bold\">12</mn></msup>");
+        assert_eq!(mathml(r"x^\sqrt{12}"), "<msup><mi>x</mi><msqrt><mn>12</mn></msqrt></msup>");
+        assert_eq!(mathml(r"x_\mathrm{max}"), "<msub><mi>x</mi><mi mathvariant=\"normal\">max</mi></msub>");
+    }
+
+    #[test]
+    fn align() {
+        assert_eq!(mathml(r"\begin{align} a &= b \\ c &= d \end{align}"),
+            "<mtable columnalign=\"right left right left right left\" displaystyle=\"true\">\
+                <mtr><mtd><mi>a</mi></mtd><mtd><mo>=</mo><mi>b</mi></mtd></mtr>\
+                <mtr><mtd><mi>c</mi></mtd><mtd><mo>=</mo><mi>d</mi></mtd></mtr>\
+            </mtable>");
+    }
+
+    #[test]
+    fn pmatrix() {
+        assert_eq!(mathml(r"\begin{pmatrix} 1 & 2 \\ 3 & 4 \end{pmatrix}"),
+            "<mrow><mo>(</mo><mtable>\
+                <mtr><mtd><mn>1</mn></mtd><mtd><mn>2</mn></mtd></mtr>\
+                <mtr><mtd><mn>3</mn></mtd><mtd><mn>4</mn></mtd></mtr>\
+            </mtable><mo>)</mo></mrow>");
+    }
+
+    #[test]
+    fn cases() {
+        assert_eq!(mathml(r"\begin{cases} 1 & x > 0 \\ 0 & \text{otherwise} \end{cases}"),
+            "<mrow><mo>{</mo><mtable columnalign=\"left left\">\
+                <mtr><mtd><mn>1</mn></mtd><mtd><mi>x</mi><mo>&gt;</mo><mn>0</mn></mtd></mtr>\
+                <mtr><mtd><mn>0</mn></mtd><mtd><mtext>otherwise</mtext></mtd></mtr>\
+            </mtable></mrow>");
+    }
+
+    #[test]
+    fn text() {
+        // spaces at the ends are kept (as non-breaking spaces)
+        assert_eq!(mathml(r"\text{if } x"), "<mtext>if\u{A0}</mtext><mi>x</mi>");
+    }
+
+    #[test]
+    fn left_right() {
+        assert_eq!(mathml(r"\left( \frac{1}{2} \right]"),
+            "<mrow><mo fence=\"true\" stretchy=\"true\">(</mo>\
+                <mfrac><mn>1</mn><mn>2</mn></mfrac>\
+            <mo fence=\"true\" stretchy=\"true\">]</mo></mrow>");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error(r"\frac{1}"), (8, "Missing argument".to_string()));
+        assert_eq!(error("a}"), (1, "Extra '}' (no matching '{')".to_string()));
+        assert_eq!(error("x^"), (2, "Missing argument".to_string()));
+    }
+}
@@ -0,0 +1,97 @@
+//! Convert "plain" Unicode math such as `x² + √y ≤ π/2` to MathML.
+//!
+//! This is done by rewriting the Unicode-specific notation (superscript and subscript chars, radical signs,
+//! vulgar fractions) as ASCIIMath and then using the ASCIIMath parser. The other chars (e.g., `≤` or `π`)
+//! are already handled by the ASCIIMath parser.
+use anyhow::Result;
+use phf::phf_map;
+use crate::asciimath::parse_asciimath;
+use crate::convert::ParseError;
+use crate::mathml::{MathNode, math_to_string};
+
+static SUPERSCRIPTS: phf::Map<char, char> = phf_map! {
+    '⁰' => '0', '¹' => '1', '²' => '2', '³' => '3', '⁴' => '4', '⁵' => '5', '⁶' => '6', '⁷' => '7', '⁸' => '8', '⁹' => '9',
+    '⁺' => '+', '⁻' => '-', '⁼' => '=', '⁽' => '(', '⁾' => ')', 'ⁿ' => 'n', 'ⁱ' => 'i',
+};
+
+static SUBSCRIPTS: phf::Map<char, char> = phf_map! {
+    '₀' => '0', '₁' => '1', '₂' => '2', '₃' => '3', '₄' => '4', '₅' => '5', '₆' => '6', '₇' => '7', '₈' => '8', '₉' => '9',
+    '₊' => '+', '₋' => '-', '₌' => '=', '₍' => '(', '₎' => ')', 'ₐ' => 'a', 'ₑ' => 'e', 'ₒ' => 'o', 'ₓ' => 'x',
+    'ₕ' => 'h', 'ₖ' => 'k', 'ₗ' => 'l', 'ₘ' => 'm', 'ₙ' => 'n', 'ₚ' => 'p', 'ₛ' => 's', 'ₜ' => 't', 'ᵢ' => 'i', 'ⱼ' => 'j',
+};
+
+/// Chars that are rewritten as ASCIIMath
+static REWRITES: phf::Map<char, &'static str> = phf_map! {
+    '√' => "sqrt ", '∛' => "root(3)", '∜' => "root(4)",
+    '½' => "frac(1)(2)", '⅓' => "frac(1)(3)", '⅔' => "frac(2)(3)", '¼' => "frac(1)(4)", '¾' => "frac(3)(4)",
+    '⅕' => "frac(1)(5)", '⅙' => "frac(1)(6)", '⅛' => "frac(1)(8)",
+};
+
+/// Returns true if `ch` is only used in Unicode math (ASCIIMath and TeX use other notations for it)
+pub fn is_unicode_math_notation(ch: char) -> bool {
+    return SUPERSCRIPTS.contains_key(&ch) || SUBSCRIPTS.contains_key(&ch) || REWRITES.contains_key(&ch);
+}
+
+/// Convert `math` to a MathML string (display math)
+pub fn unicode_math_to_mathml(math: &str) -> Result<String> {
+    return Ok( math_to_string(parse_unicode_math(math)?, true) );
+}
+
+/// Convert `math` to a list of MathML nodes (the children of the `math` element)
+pub fn parse_unicode_math(math: &str) -> Result<Vec<MathNode>> {
+    let (ascii_math, positions) = to_asciimath(math);
+    return parse_asciimath(&ascii_math).map_err(|mut e| {
+        // map the error position back to the position in `math`
+        if let Some(parse_error) = e.downcast_mut::<ParseError>() {
+            let n_chars = math.chars().count();
+            let start = positions.get(parse_error.start).copied().unwrap_or(n_chars);
+            let end = positions.get(parse_error.end).copied().unwrap_or(n_chars + 1).max(start + 1);
+            *parse_error = ParseError::new(&parse_error.message, start, end);
+        }
+        e
+    });
+}
+
+/// Rewrite `math` as ASCIIMath. The second value gives the position in `math` for each char in the result.
+fn to_asciimath(math: &str) -> (String, Vec<usize>) {
+    let chars: Vec<char> = math.chars().collect();
+    let mut result = String::with_capacity(math.len());
+    let mut positions = Vec::with_capacity(chars.len());
+    let mut push = |text: &str, position: usize| {
+        for ch in text.chars() {
+            result.push(ch);
+            positions.push(position);
+        }
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        let scripts = if SUPERSCRIPTS.contains_key(&ch) {
+            Some( ("^", &SUPERSCRIPTS) )
+        } else if SUBSCRIPTS.contains_key(&ch) {
+            Some( ("_", &SUBSCRIPTS) )
+        } else {
+            None
+        };
+        match scripts {
+            Some( (script, map) ) => {
+                push(script, i);
+                push("(", i);
+                while let Some(&script_ch) = chars.get(i).and_then(|ch| map.get(ch)) {
+                    push(&script_ch.to_string(), i);
+                    i += 1;
+                }
+                push(")", i - 1);
+            },
+            None => {
+                match REWRITES.get(&ch) {
+                    Some(rewrite) => push(rewrite, i),
+                    None => push(&ch.to_string(), i),
+                }
+                i += 1;
+            },
+        }
+    }
+    return (result, positions);
+}